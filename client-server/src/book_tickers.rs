@@ -0,0 +1,32 @@
+use std::collections::HashSet;
+use anyhow::Result;
+
+use queue_client::producer::Producer;
+use queue_client::queue_data::market_data::MarketSubscriptionMessage;
+
+/// Asks the websocket feed to build the books of the markets clients trade,
+/// asking once per market.
+pub struct BookTickers {
+    producer: Producer<MarketSubscriptionMessage>,
+    requested: HashSet<String>
+}
+
+impl BookTickers {
+
+    pub fn new(producer: Producer<MarketSubscriptionMessage>) -> BookTickers {
+        BookTickers {
+            producer,
+            requested: HashSet::new()
+        }
+    }
+
+    /// Ask for the book of a market unless it has already been asked for.
+    pub async fn request(&mut self, market_ticker: &str) -> Result<()> {
+        if self.requested.contains(market_ticker) {
+            return Ok(());
+        }
+        self.producer.publish(MarketSubscriptionMessage { market_ticker: market_ticker.to_string() }).await?;
+        self.requested.insert(market_ticker.to_string());
+        Ok(())
+    }
+}
@@ -1,2 +1,20 @@
-pub const MQ_ADDR: &str = "amqp://localhost:5672";
-pub const CLIENT_NAME_SIZE_BYTES: usize = 10;
\ No newline at end of file
+use std::time::Duration;
+
+pub const CLIENT_NAME_SIZE_BYTES: usize = 10;
+pub const STATUS_LOOKUP_PAGE_SIZE: i32 = 100;
+pub const STATUS_LOOKUP_LOOKBACK: Duration = Duration::from_secs(24 * 60 * 60);
+pub const SESSION_RENEW_INTERVAL: Duration = Duration::from_secs(20 * 60);
+pub const SESSION_RENEW_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+pub const MAX_HELD_FILLS: usize = 10_000;
+pub const RECONCILE_PAGE_SIZE: i32 = 100;
+pub const RECONCILE_LOOKBACK: Duration = Duration::from_secs(24 * 60 * 60);
+// shorter than any client name, so it never collides with one
+pub const UNATTRIBUTED_POSITION_CLIENT_ID: &str = "(account)";
+pub const MARKET_DATA_MAX_PENDING_TRADES: usize = 1000;
+pub const JOURNAL_DIR: &str = "data/journal/client-server";
+pub const JOURNAL_FSYNC_INTERVAL: Duration = Duration::from_millis(100);
+pub const JOURNAL_MAX_FILE_BYTES: u64 = 64 * 1024 * 1024;
+pub const PAPER_ORDER_ID_PREFIX: &str = "paper";
+pub const PAPER_EXPIRY_INTERVAL: Duration = Duration::from_secs(1);
+// the fill feed publishes its status every few seconds, so this long without one means it is down
+pub const FEED_STATUS_TIMEOUT: Duration = Duration::from_secs(30);
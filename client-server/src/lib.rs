@@ -0,0 +1,1074 @@
+use anyhow::Result;
+use tokio::net::TcpListener;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::sync::{Mutex, MutexGuard, RwLock};
+use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use queue_client::producer::Producer;
+use queue_client::queue_data::orders::OrderState;
+use queue_client::queue_data::{
+    orders::CreateOrderMessage, 
+    cancels::CancelOrderMessage, 
+    cancels::CancelConfirmMessage, 
+    cancels::CancelRejectMessage,
+    orders::OrderConfirmMessage, 
+    orders::OrderRejectMessage,
+    orders::OrderUpdateMessage,
+    fills::{FeedStatusMessage, Fill, FillMessage},
+    market_data::{BookMessage, MarketLifecycleMessage, MarketStatus, MarketSubscriptionMessage, TickerMessage, TradeMessage}
+};
+use protocol::read::{IncomingMessage, Login, StatusRequest, PositionRequest};
+use protocol::messages::{OrderStatusEndMessage, OrderStatusMessage, PositionMessage, PositionEndMessage};
+use protocol::write::ClientMessage;
+use journal::{FsyncPolicy, Journal, JournalConfig, RecordKind, SharedJournal};
+use tracing::{debug, error, info, warn};
+
+use kalshi_rest::{Credentials, KalshiSession};
+use oms_config::{OmsConfig, RiskConfig};
+use queue_client::queues::Queues;
+
+use crate::book_tickers::BookTickers;
+use crate::market_data::{ClientFeed, MarketDataSubscriptions};
+use crate::markets::Markets;
+use crate::order_store::{Order, OrderStore};
+use crate::paper::{PaperEvent, PaperExchange};
+use crate::positions::Positions;
+
+mod book_tickers;
+mod constants;
+mod market_data;
+mod markets;
+mod order_store;
+mod paper;
+mod positions;
+mod reconcile;
+mod recovery;
+mod risk;
+mod status;
+
+/// Run the client-server until its listener fails, with its queues on the broker or in the
+/// process it shares with the exchange servers.
+pub async fn run(config: OmsConfig, queues: Queues) -> Result<()> {
+
+    // Choose the Kalshi environment, refusing to start if its URLs or credentials belong to the other one
+
+    let environment = config.kalshi.environment_config()?;
+    info!("==== Trading in {} ====", environment);
+
+    // Login to the Kalshi REST API for market, status and reconciliation lookups, with an API key or an email and password,
+    // and keep the session renewed for as long as the client-server runs
+
+    let credentials = Credentials::from_env()?;
+    let exchange_session = KalshiSession::open(&environment, &credentials, constants::SESSION_RENEW_INTERVAL).await?;
+    info!("Logged in to Kalshi at {} with {}", exchange_session.client().base_url(), credentials);
+    let exchange_session_handle = Arc::new(RwLock::new(exchange_session));
+    tokio::spawn(renew_session(exchange_session_handle.clone()));
+
+    // Open the journal every message crossing the client-server is recorded in
+
+    let journal = Journal::open(JournalConfig {
+        dir: constants::JOURNAL_DIR.into(),
+        fsync: FsyncPolicy::Interval(constants::JOURNAL_FSYNC_INTERVAL),
+        max_file_bytes: constants::JOURNAL_MAX_FILE_BYTES
+    })?.shared();
+
+    let order_producer_handle = Arc::new(Mutex::new(queues.producer::<CreateOrderMessage>().await?.with_journal(journal.clone())));
+    let cancel_producer_handle = Arc::new(Mutex::new(queues.producer::<CancelOrderMessage>().await?.with_journal(journal.clone())));
+    let book_tickers_handle = Arc::new(Mutex::new(BookTickers::new(queues.producer::<MarketSubscriptionMessage>().await?)));
+
+    let client_map_handle = Arc::new(Mutex::new(HashMap::<String, Arc<Mutex<OwnedWriteHalf>>>::new()));
+    let order_store_handle = Arc::new(Mutex::new(OrderStore::new()));
+    let positions_handle = Arc::new(Mutex::new(Positions::new()));
+    let market_data_handle = Arc::new(Mutex::new(MarketDataSubscriptions::new()));
+    let markets_handle = Arc::new(Mutex::new(Markets::new()));
+    // the fill feed is taken to be live until it says otherwise
+    let feed_status_handle = Arc::new(Mutex::new(FeedStatusMessage { connected: true, stale: false, silent_for_ms: 0 }));
+
+    // Paper-trading clients trade on a simulated exchange with an order store and positions of their own
+
+    let paper_handle = Arc::new(PaperExchange::new());
+    let paper_clients: HashSet<String> = config.client_server.paper_clients.iter().cloned().collect();
+    if !paper_clients.is_empty() {
+        info!("Clients always paper trading: {:?}", paper_clients);
+    }
+
+    // Rebuild the order store and positions from the journal, then from the exchange, before accepting clients
+
+    { // hold the locks until reconciliation finishes so no task sees a half-built state
+        let mut positions = positions_handle.lock().await;
+        let mut order_store = order_store_handle.lock().await;
+        let mut markets = markets_handle.lock().await;
+        match recovery::replay(std::path::Path::new(constants::JOURNAL_DIR), queues.names(), &mut order_store, &mut positions, &mut markets) {
+            Ok(report) => info!("Replayed the journal: {:?}", report),
+            Err(e) => error!("Replaying the journal failed: {:?}", e)
+        }
+        match reconcile::reconcile(exchange_session_handle.read().await.client(), &mut order_store, &mut positions).await {
+            Ok(report) if report.is_clean() => info!("Startup reconciliation found no mismatches: {:?}", report),
+            Ok(report) => warn!("Startup reconciliation found mismatches with local state: {:?}", report),
+            Err(e) => error!("Startup reconciliation failed: {:?}", e)
+        }
+    }
+
+    let listen_addr = config.listen_addr()?;
+    let listener = TcpListener::bind(listen_addr).await.map_err(|e| anyhow::anyhow!("Failed to bind {}: {}", listen_addr, e))?;
+    info!("Listening for clients on {}", listen_addr);
+    let listener_task = tokio::spawn(handle_incoming_connections(
+        listener, 
+        Arc::clone(&client_map_handle), 
+        Arc::clone(&order_store_handle),
+        Arc::clone(&positions_handle),
+        Arc::clone(&exchange_session_handle),
+        Arc::clone(&order_producer_handle), 
+        Arc::clone(&cancel_producer_handle),
+        Arc::clone(&book_tickers_handle),
+        Arc::clone(&market_data_handle),
+        Arc::clone(&markets_handle),
+        Arc::clone(&paper_handle),
+        paper_clients,
+        config.risk,
+        config.client_server.cancel_on_disconnect_grace_period(),
+        Arc::clone(&feed_status_handle),
+        journal.clone()));
+
+    tokio::spawn(expire_paper_orders(client_map_handle.clone(), paper_handle.clone()));
+    tokio::spawn(wait_for_order_confirms(queues.clone(), client_map_handle.clone(), order_store_handle.clone(), positions_handle.clone(), cancel_producer_handle.clone(), journal.clone()));
+    tokio::spawn(wait_for_order_rejects(queues.clone(), client_map_handle.clone(), order_store_handle.clone(), journal.clone()));
+    tokio::spawn(wait_for_cancel_confirms(queues.clone(), client_map_handle.clone(), order_store_handle.clone(), journal.clone()));
+    tokio::spawn(wait_for_cancel_rejects(queues.clone(), client_map_handle.clone(), order_store_handle.clone(), journal.clone()));
+    tokio::spawn(wait_for_order_updates(queues.clone(), client_map_handle.clone(), order_store_handle.clone(), positions_handle.clone(), journal.clone()));
+    tokio::spawn(wait_for_fills(queues.clone(), client_map_handle.clone(), order_store_handle.clone(), positions_handle.clone(), journal.clone()));
+    tokio::spawn(wait_for_market_lifecycle(queues.clone(), client_map_handle.clone(), order_store_handle.clone(), positions_handle.clone(), markets_handle.clone(), paper_handle.clone(), journal.clone()));
+    tokio::spawn(wait_for_books(queues.clone(), client_map_handle.clone(), market_data_handle.clone(), positions_handle.clone(), paper_handle.clone()));
+    tokio::spawn(wait_for_trades(queues.clone(), market_data_handle.clone()));
+    tokio::spawn(wait_for_tickers(queues.clone(), market_data_handle.clone()));
+    tokio::spawn(wait_for_feed_status(queues.clone(), client_map_handle.clone(), feed_status_handle.clone()));
+
+    listener_task.await??;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_incoming_connections(
+    listener: TcpListener, 
+    clients: Arc<Mutex<HashMap<String, Arc<Mutex<OwnedWriteHalf>>>>>, 
+    order_store: Arc<Mutex<OrderStore>>,
+    positions: Arc<Mutex<Positions>>,
+    exchange_session: Arc<RwLock<KalshiSession>>,
+    order_handle: Arc<Mutex<Producer<CreateOrderMessage>>>,
+    cancel_handle: Arc<Mutex<Producer<CancelOrderMessage>>>,
+    book_tickers: Arc<Mutex<BookTickers>>,
+    market_data: Arc<Mutex<MarketDataSubscriptions>>,
+    markets: Arc<Mutex<Markets>>,
+    paper: Arc<PaperExchange>,
+    paper_clients: HashSet<String>,
+    risk_limits: RiskConfig,
+    grace_period: Duration,
+    feed_status: Arc<Mutex<FeedStatusMessage>>,
+    journal: SharedJournal
+) -> Result<()> {
+    loop {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        
+        if let Ok(mut login) = protocol::read::read_login(&mut socket).await {
+            if login.name.len() != constants::CLIENT_NAME_SIZE_BYTES {
+                warn!("Rejecting login from client {:?}: names must be {} bytes.", login.name, constants::CLIENT_NAME_SIZE_BYTES);
+                continue;
+            }
+            login.paper_trading |= paper_clients.contains(&login.name);
+            if let Err(e) = journal::append(&journal, RecordKind::ClientFrame, &login.name, &login) {
+                error!("Failed to journal login from client {:?}: {:?}", login.name, e);
+            }
+
+            // reads and writes each get their own handle so that routing messages to the
+            // client never waits on the client sending its next message
+            let (read_socket, write_socket) = socket.into_split();
+            let socket_handle = Arc::new(Mutex::new(write_socket));
+
+            { // block off client interaction so the map exits scope and is freed sooner
+                let mut clients = clients.lock().await;
+                clients.insert(login.name.clone(), socket_handle.clone());
+            }
+            info!("Client {:?} logged in (cancel on disconnect: {}, paper trading: {}).", login.name, login.cancel_on_disconnect, login.paper_trading);
+
+            // a client logging in while fills are stale would otherwise not hear of it until they recover
+            let status = feed_status.lock().await.clone();
+            if status.stale {
+                if let Err(e) = write_next_frame(&status, socket_handle.lock().await).await {
+                    warn!("Failed to write feed status to client {:?}: {:?}", login.name, e);
+                }
+            }
+
+            // a paper-trading client's orders and positions never mix with the live ones
+            let (order_store, positions, paper) = match login.paper_trading {
+                true => (paper.order_store.clone(), paper.positions.clone(), Some(paper.clone())),
+                false => (order_store.clone(), positions.clone(), None)
+            };
+            let order_handle = order_handle.clone();
+            let cancel_handle = cancel_handle.clone();
+            tokio::spawn(handle_client(
+                read_socket,
+                socket_handle.clone(),
+                login,
+                clients.clone(),
+                order_store,
+                positions,
+                paper,
+                exchange_session.clone(),
+                order_handle,
+                cancel_handle,
+                book_tickers.clone(),
+                market_data.clone(),
+                markets.clone(),
+                risk_limits,
+                grace_period,
+                journal.clone()));
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_client(
+    mut socket: OwnedReadHalf,
+    socket_handle: Arc<Mutex<OwnedWriteHalf>>,
+    login: Login,
+    clients: Arc<Mutex<HashMap<String, Arc<Mutex<OwnedWriteHalf>>>>>,
+    order_store: Arc<Mutex<OrderStore>>,
+    positions: Arc<Mutex<Positions>>,
+    paper: Option<Arc<PaperExchange>>,
+    exchange_session: Arc<RwLock<KalshiSession>>,
+    order_handle: Arc<Mutex<Producer<CreateOrderMessage>>>,
+    cancel_handle: Arc<Mutex<Producer<CancelOrderMessage>>>,
+    book_tickers: Arc<Mutex<BookTickers>>,
+    market_data: Arc<Mutex<MarketDataSubscriptions>>,
+    markets: Arc<Mutex<Markets>>,
+    risk_limits: RiskConfig,
+    grace_period: Duration,
+    journal: SharedJournal
+) -> Result<()> {
+
+    let name = login.name;
+
+    // market data is written by its own task, so prices a slow client has not read yet are conflated rather than queued
+    let feed = Arc::new(ClientFeed::new());
+    tokio::spawn(market_data::write_feed(name.clone(), feed.clone(), socket_handle.clone()));
+
+    loop {
+        match read_next_journaled(&mut socket, &name, &journal).await {
+            Ok(IncomingMessage::Order(mut order)) => {
+                let client_order_id = order.client_order_id.clone();
+                order.client_order_id = namespace_client_order_id(&name, &client_order_id);
+                let known = markets::ensure_known(&markets, exchange_session.read().await.client(), &order.ticker).await;
+                if let Err(e) = known {
+                    warn!("Could not look up the status of market {:?}: {:?}", order.ticker, e);
+                    renew_if_unauthorized(&exchange_session, &e).await;
+                }
+                let accepted = {
+                    let market_open = markets.lock().await.check_open(&order.ticker);
+                    let positions = positions.lock().await;
+                    let mut order_store = order_store.lock().await;
+                    market_open
+                        .and_then(|_| risk::check_order(&name, &order, &positions, &order_store, &risk_limits))
+                        .and_then(|_| order_store.insert_new(&name, &order))
+                };
+                if let Err(e) = accepted {
+                    warn!("Rejecting order {:?} from client {:?}: {:?}", client_order_id, name, e);
+                    let order_reject = OrderRejectMessage::new(client_order_id, e.to_string());
+                    if let Err(e) = write_next_frame(&order_reject, socket_handle.lock().await).await {
+                        warn!("Failed to write order rejection to client {:?}: {:?}", name, e);
+                    }
+                    continue;
+                }
+                let namespaced_client_order_id = order.client_order_id.clone();
+                let ticker = order.ticker.clone();
+                if let Some(ref paper) = paper {
+                    debug!("Placing paper order {:?} from client {:?}", namespaced_client_order_id, name);
+                    route_paper_events(&clients, paper.submit(order).await).await;
+                } else {
+                    debug!("Relaying order {:?} from client {:?} to MQ", namespaced_client_order_id, name);
+                    if let Err(e) = order_handle.lock().await.publish(order).await {
+                        error!("Failed to publish order {:?} from client {:?}: {:?}", client_order_id, name, e);
+                        order_store.lock().await.remove(&namespaced_client_order_id);
+                        continue;
+                    }
+                }
+                if let Err(e) = book_tickers.lock().await.request(&ticker).await {
+                    warn!("Failed to request the book for {:?}: {:?}", ticker, e);
+                }
+            },
+            Ok(IncomingMessage::Cancel(mut cancel)) => {
+                cancel.client_order_id = namespace_client_order_id(&name, &cancel.client_order_id);
+                if let Err(e) = order_store.lock().await.on_cancel_request(&cancel.client_order_id) {
+                    warn!("Dropping cancel from client {:?}: {:?}", name, e);
+                    continue;
+                }
+                if let Some(ref paper) = paper {
+                    debug!("Cancelling paper order {:?} for client {:?}", cancel.client_order_id, name);
+                    route_paper_events(&clients, paper.cancel(cancel).await).await;
+                } else {
+                    debug!("Relaying cancel {:?} from client {:?} to MQ", cancel.client_order_id, name);
+                    if let Err(e) = cancel_handle.lock().await.publish(cancel).await {
+                        error!("Failed to publish cancel from client {:?}: {:?}", name, e);
+                    }
+                }
+            },
+            Ok(IncomingMessage::StatusRequest(request)) => {
+                debug!("Answering status request {:?} from client {:?}", request, name);
+                // paper orders are only ever on the simulated exchange, so Kalshi is never asked about them
+                let exchange_session = if paper.is_none() { Some(exchange_session.clone()) } else { None };
+                // asking Kalshi can take several requests, which must not hold up the client's next message
+                let (name, order_store, socket_handle) = (name.clone(), order_store.clone(), socket_handle.clone());
+                tokio::spawn(async move {
+                    if let Err(e) = answer_status_request(request, &name, &order_store, exchange_session.as_deref(), &socket_handle).await {
+                        warn!("Failed to answer status request from client {:?}: {:?}", name, e);
+                    }
+                });
+            },
+            Ok(IncomingMessage::PositionRequest(request)) => {
+                debug!("Answering position request {:?} from client {:?}", request, name);
+                if let Err(e) = answer_position_request(request, &name, &positions, &socket_handle).await {
+                    warn!("Failed to answer position request from client {:?}: {:?}", name, e);
+                }
+            },
+            Ok(IncomingMessage::MarketDataSubscribe(request)) => {
+                debug!("Subscribing client {:?} to market data {:?}", name, request);
+                let ticker = request.ticker.clone();
+                market_data.lock().await.subscribe(request, feed.clone());
+                if let Err(e) = book_tickers.lock().await.request(&ticker).await {
+                    warn!("Failed to request market data for {:?}: {:?}", ticker, e);
+                }
+            },
+            Ok(IncomingMessage::MarketDataUnsubscribe(ticker)) => {
+                debug!("Unsubscribing client {:?} from market data for {:?}", name, ticker);
+                market_data.lock().await.unsubscribe(&ticker, &feed);
+            },
+            Ok(IncomingMessage::Login(_)) => warn!("Ignoring repeated login from client {:?}.", name),
+            Err(e) => {
+                info!("Client {:?} disconnected: {:?}", name, e);
+                break;
+            }
+        }
+    }
+
+    market_data.lock().await.remove_feed(&feed);
+    feed.close();
+
+    { // only forget the client if it has not already logged back in on a new connection
+        let mut clients = clients.lock().await;
+        if clients.get(&name).is_some_and(|handle| Arc::ptr_eq(handle, &socket_handle)) {
+            clients.remove(&name);
+        }
+    }
+
+    if login.cancel_on_disconnect {
+        cancel_on_disconnect(name, clients, order_store, paper, cancel_handle, grace_period).await?;
+    }
+
+    Ok(())
+}
+
+/// Renew the Kalshi session whenever its token is old enough, so that lookups never find it expired.
+async fn renew_session(exchange_session: Arc<RwLock<KalshiSession>>) {
+    let mut renew_check = tokio::time::interval(constants::SESSION_RENEW_CHECK_INTERVAL);
+    loop {
+        renew_check.tick().await;
+        if exchange_session.read().await.is_due() {
+            exchange_session.write().await.renew_if_due().await;
+        }
+    }
+}
+
+/// Log in again after Kalshi refused a lookup as unauthorized, so that the next one is accepted.
+async fn renew_if_unauthorized(exchange_session: &RwLock<KalshiSession>, error: &anyhow::Error) {
+    if kalshi_rest::is_unauthorized(error) {
+        exchange_session.write().await.on_unauthorized().await;
+    }
+}
+
+/// Read the client's next message, recording it in the journal before it is acted on.
+async fn read_next_journaled(socket: &mut OwnedReadHalf, name: &str, journal: &SharedJournal) -> Result<IncomingMessage> {
+    let message = protocol::read::read_next(socket).await?;
+    journal::append(journal, RecordKind::ClientFrame, name, &message)?;
+    Ok(message)
+}
+
+/// Answer a client's status request from the order store, asking the exchange, if
+/// there is one to ask, about any order the store does not know.
+async fn answer_status_request(
+    request: StatusRequest,
+    name: &str,
+    order_store: &Arc<Mutex<OrderStore>>,
+    exchange_session: Option<&RwLock<KalshiSession>>,
+    socket_handle: &Arc<Mutex<OwnedWriteHalf>>
+) -> Result<()> {
+
+    let statuses = match request {
+        StatusRequest::Order(client_order_id) => {
+            let namespaced_client_order_id = namespace_client_order_id(name, &client_order_id);
+            let known_status = order_store.lock().await
+                .get(&namespaced_client_order_id)
+                .map(|order| status::from_store(order, client_order_id.clone()));
+            match (known_status, exchange_session) {
+                (Some(known_status), _) => vec![known_status],
+                (None, Some(exchange_session)) => {
+                    // the client is still owed an answer, and the end of it, when Kalshi cannot be asked
+                    let looked_up = status::from_exchange(exchange_session.read().await.client(), &namespaced_client_order_id, client_order_id.clone()).await;
+                    match looked_up {
+                        Ok(status) => vec![status],
+                        Err(e) => {
+                            warn!("Could not look up order {:?} on Kalshi for client {:?}; answering that it is unknown: {:?}", client_order_id, name, e);
+                            renew_if_unauthorized(exchange_session, &e).await;
+                            vec![OrderStatusMessage::unknown(client_order_id)]
+                        }
+                    }
+                },
+                (None, None) => vec![OrderStatusMessage::unknown(client_order_id)]
+            }
+        },
+        StatusRequest::AllOpenOrders => {
+            let order_store = order_store.lock().await;
+            let statuses: Vec<_> = order_store.open_orders_for(name).into_iter()
+                .filter_map(|order| match split_client_name(&order.client_order_id) {
+                    Ok((_, client_order_id)) => Some(status::from_store(order, client_order_id)),
+                    Err(_) => None
+                })
+                .collect();
+            statuses
+        }
+    };
+
+    let mut socket = socket_handle.lock().await;
+    for status in statuses.iter() {
+        protocol::write::write_message(&mut *socket, status).await?;
+    }
+    protocol::write::write_message(&mut *socket, &OrderStatusEndMessage { count: statuses.len() as u32 }).await
+}
+
+/// Answer a client's position request with its positions marked to the latest prices.
+async fn answer_position_request(
+    request: PositionRequest,
+    name: &str,
+    positions: &Arc<Mutex<Positions>>,
+    socket_handle: &Arc<Mutex<OwnedWriteHalf>>
+) -> Result<()> {
+
+    let messages: Vec<PositionMessage> = {
+        let positions = positions.lock().await;
+        let messages: Vec<_> = positions.for_client(name).into_iter()
+            .filter(|(ticker, _)| match request {
+                PositionRequest::Ticker(ref requested) => *ticker == requested,
+                PositionRequest::AllTickers => true
+            })
+            .map(|(ticker, position)| PositionMessage {
+                ticker: ticker.clone(),
+                net_contracts: position.net_contracts,
+                average_price: position.average_price,
+                realized_pnl: position.realized_pnl,
+                unrealized_pnl: positions.get_mark(ticker).map(|mark| position.unrealized_pnl(mark))
+            })
+            .collect();
+        messages
+    };
+
+    let mut socket = socket_handle.lock().await;
+    for message in messages.iter() {
+        protocol::write::write_message(&mut *socket, message).await?;
+    }
+    protocol::write::write_message(&mut *socket, &PositionEndMessage { count: messages.len() as u32 }).await
+}
+
+/// Cancel all of a disconnected client's open orders, unless the client logs back in
+/// before the grace period is over. A paper-trading client's orders are cancelled on the
+/// simulated exchange.
+async fn cancel_on_disconnect(
+    name: String,
+    clients: Arc<Mutex<HashMap<String, Arc<Mutex<OwnedWriteHalf>>>>>,
+    order_store: Arc<Mutex<OrderStore>>,
+    paper: Option<Arc<PaperExchange>>,
+    cancel_handle: Arc<Mutex<Producer<CancelOrderMessage>>>,
+    grace_period: Duration
+) -> Result<()> {
+
+    tokio::time::sleep(grace_period).await;
+
+    if clients.lock().await.contains_key(&name) {
+        info!("Client {:?} reconnected within the grace period. Leaving its open orders in place.", name);
+        return Ok(());
+    }
+
+    let cancels: Vec<CancelOrderMessage> = {
+        let mut order_store = order_store.lock().await;
+        let open_orders: Vec<&Order> = order_store.open_orders_for(&name).into_iter()
+            .filter(|order| order.state != OrderState::PendingCancel)
+            .collect();
+        let cancels: Vec<CancelOrderMessage> = open_orders.iter()
+            .filter_map(|order| order.order_id.as_ref().map(|order_id| CancelOrderMessage {
+                order_id: order_id.clone(),
+                client_order_id: order.client_order_id.clone()
+            })).collect();
+        let unconfirmed: Vec<String> = open_orders.iter()
+            .filter(|order| order.order_id.is_none())
+            .map(|order| order.client_order_id.clone())
+            .collect();
+        for cancel in cancels.iter() {
+            if let Err(e) = order_store.on_cancel_request(&cancel.client_order_id) {
+                warn!("{:?}", e);
+            }
+        }
+        // unconfirmed orders have no exchange order id yet, so they are cancelled when their confirm arrives
+        for client_order_id in unconfirmed.iter() {
+            debug!("Cancelling unconfirmed order {:?} for disconnected client {:?} once it is confirmed.", client_order_id, name);
+            if let Err(e) = order_store.cancel_on_confirm(client_order_id) {
+                warn!("{:?}", e);
+            }
+        }
+        cancels
+    };
+
+    info!("Cancelling {} open orders for disconnected client {:?}.", cancels.len(), name);
+    if let Some(paper) = paper {
+        for cancel in cancels {
+            route_paper_events(&clients, paper.cancel(cancel).await).await;
+        }
+        return Ok(());
+    }
+    cancel_handle.lock().await.publish_batch(cancels).await
+}
+
+/// The socket of a connected client, taken out of the client map so that the map is not held
+/// while writing to a client that is slow to read.
+async fn client_socket(clients: &Mutex<HashMap<String, Arc<Mutex<OwnedWriteHalf>>>>, client_id: &str) -> Option<Arc<Mutex<OwnedWriteHalf>>> {
+    let socket = clients.lock().await.get(client_id).cloned();
+    if socket.is_none() {
+        warn!("No client found corresponding to client id {:?}. Cannot route to destination client.", client_id);
+    }
+    socket
+}
+
+/// Send each client what the simulated exchange had to tell it about its paper orders.
+async fn route_paper_events(clients: &Mutex<HashMap<String, Arc<Mutex<OwnedWriteHalf>>>>, events: Vec<(String, PaperEvent)>) {
+    if events.is_empty() {
+        return;
+    }
+    for (client_id, event) in events {
+        let client = match client_socket(clients, &client_id).await {
+            None => continue,
+            Some(client) => client
+        };
+        let client = client.lock().await;
+        let written = match event {
+            PaperEvent::Confirm(confirm) => write_next_frame(&confirm, client).await,
+            PaperEvent::Reject(reject) => write_next_frame(&reject, client).await,
+            PaperEvent::CancelConfirm(cancel_confirm) => write_next_frame(&cancel_confirm, client).await,
+            PaperEvent::CancelReject(cancel_reject) => write_next_frame(&cancel_reject, client).await,
+            PaperEvent::Fill(fill) => write_next_frame(&fill, client).await,
+            PaperEvent::Status(status) => write_next_frame(&status, client).await
+        };
+        if let Err(e) = written {
+            warn!("Failed to write paper trading update to client {:?}: {:?}", client_id, e);
+        }
+    }
+}
+
+/// Expire paper orders as their expiration times pass, as Kalshi does for live ones.
+async fn expire_paper_orders(clients: Arc<Mutex<HashMap<String, Arc<Mutex<OwnedWriteHalf>>>>>, paper: Arc<PaperExchange>) -> Result<()> {
+    let mut interval = tokio::time::interval(constants::PAPER_EXPIRY_INTERVAL);
+    loop {
+        interval.tick().await;
+        route_paper_events(&clients, paper.expire_due().await).await;
+    }
+}
+
+async fn wait_for_cancel_confirms(
+    queues: Queues,
+    clients: Arc<Mutex<HashMap<String, Arc<Mutex<OwnedWriteHalf>>>>>,
+    order_store: Arc<Mutex<OrderStore>>,
+    journal: SharedJournal
+) -> Result<()> {
+
+    let cancel_confirm_consumer = queues.consumer::<CancelConfirmMessage>().await?.with_journal(journal);
+
+    loop {
+        let mut next_cancel = match cancel_confirm_consumer.get_next().await? {
+            None => continue,
+            Some(cancel) => cancel
+        };
+
+        let (client_id, client_order_id) = match split_client_name(&next_cancel.client_order_id) {
+            Ok(p) => p,
+            Err(_) => {
+                warn!("Could not split client_order_id {:?} from CancelConfirmMessage. Cannot route to destination client.", &next_cancel.client_order_id);
+                continue;
+            }
+        };
+
+        if let Err(e) = order_store.lock().await.on_cancel_confirm(&next_cancel.client_order_id) {
+            warn!("{:?}", e);
+        }
+        next_cancel.client_order_id = client_order_id;
+            
+        let client = match client_socket(&clients, &client_id).await {
+            None => continue,
+            Some(client) => client
+        };
+        if let Err(e) = write_next_frame(&next_cancel, client.lock().await).await {
+            warn!("Failed to write cancel confirmation to client {:?}: {:?}", client_id, e);
+        }
+    }
+}
+
+/// Listen to the queues for cancels the exchange refused, returning each order to working in the
+/// order store and telling the client that owns it.
+async fn wait_for_cancel_rejects(
+    queues: Queues,
+    clients: Arc<Mutex<HashMap<String, Arc<Mutex<OwnedWriteHalf>>>>>,
+    order_store: Arc<Mutex<OrderStore>>,
+    journal: SharedJournal
+) -> Result<()> {
+
+    let cancel_reject_consumer = queues.consumer::<CancelRejectMessage>().await?.with_journal(journal);
+
+    loop {
+        let mut next_reject = match cancel_reject_consumer.get_next().await? {
+            None => continue,
+            Some(reject) => reject
+        };
+
+        let (client_id, client_order_id) = match split_client_name(&next_reject.client_order_id) {
+            Ok(p) => p,
+            Err(_) => {
+                warn!("Could not split client_order_id {:?} from CancelRejectMessage. Cannot route to destination client.", &next_reject.client_order_id);
+                continue;
+            }
+        };
+
+        match order_store.lock().await.on_cancel_reject(&next_reject.client_order_id) {
+            Ok(state) => info!("Cancel of order {:?} was refused: {}. The order is {:?}.", next_reject.client_order_id, next_reject.reason, state),
+            Err(e) => warn!("{:?}", e)
+        }
+        next_reject.client_order_id = client_order_id;
+
+        let client = match client_socket(&clients, &client_id).await {
+            None => continue,
+            Some(client) => client
+        };
+        if let Err(e) = write_next_frame(&next_reject, client.lock().await).await {
+            warn!("Failed to write cancel rejection to client {:?}: {:?}", client_id, e);
+        }
+    }
+}
+
+/// Listen to the queues for order confirmation messages and route them to the appropriate clients,
+/// cancelling any order whose client disconnected with cancel on disconnect before it was confirmed.
+async fn wait_for_order_confirms(
+    queues: Queues,
+    clients: Arc<Mutex<HashMap<String, Arc<Mutex<OwnedWriteHalf>>>>>,
+    order_store: Arc<Mutex<OrderStore>>,
+    positions: Arc<Mutex<Positions>>,
+    cancel_handle: Arc<Mutex<Producer<CancelOrderMessage>>>,
+    journal: SharedJournal
+) -> Result<()> {
+    let order_confirm_consumer = queues.consumer::<OrderConfirmMessage>().await?.with_journal(journal);
+
+    loop {
+        let mut next_confirm = match order_confirm_consumer.get_next().await? {
+            None => continue,
+            Some(confirm) => confirm
+        };
+
+        let (client_id, client_order_id) = match next_confirm.client_order_id {
+            None => {
+                warn!("Received order confirmation message with no client_order_id. Cannot route to destination client!");
+                continue;
+            },
+            Some(ref clordid) => {
+                match split_client_name(clordid) {
+                    Ok(p) => {
+                        let owed_cancel = {
+                            let mut order_store = order_store.lock().await;
+                            if let Err(e) = order_store.on_confirm(clordid, &next_confirm.order_id) {
+                                warn!("{:?}", e);
+                            }
+                            order_store.take_cancel_on_confirm(clordid)
+                        };
+                        if let Some(cancel) = owed_cancel {
+                            info!("Cancelling order {:?}, confirmed after its client disconnected.", clordid);
+                            if let Err(e) = cancel_handle.lock().await.publish(cancel).await {
+                                error!("Failed to publish cancel of order {:?}: {:?}", clordid, e);
+                            }
+                        }
+                        p
+                    },
+                    Err(_) => {
+                        warn!("Could not split client_order_id {:?} from OrderConfirmMessage. Cannot route to destination client.", clordid);
+                        continue;
+                    }
+                }
+            }
+        };
+
+        next_confirm.client_order_id = Some(client_order_id);
+
+        if let Some(client) = client_socket(&clients, &client_id).await {
+            if let Err(e) = write_next_frame(&next_confirm, client.lock().await).await {
+                warn!("Failed to write order confirmation to client {:?}: {:?}", client_id, e);
+            }
+        }
+
+        // fills that beat the confirm are applied now that the order id is known
+        apply_held_fills(&next_confirm.order_id, &clients, &order_store, &positions).await;
+    }
+}
+
+/// Listen to the queues for order rejection messages and route them to the appropriate clients.
+async fn wait_for_order_rejects(
+    queues: Queues,
+    clients: Arc<Mutex<HashMap<String, Arc<Mutex<OwnedWriteHalf>>>>>,
+    order_store: Arc<Mutex<OrderStore>>,
+    journal: SharedJournal
+) -> Result<()> {
+    let order_reject_consumer = queues.consumer::<OrderRejectMessage>().await?.with_journal(journal);
+
+    loop {
+        let mut next_reject = match order_reject_consumer.get_next().await? {
+            None => continue,
+            Some(reject) => reject
+        };
+
+        let (client_id, client_order_id) = match split_client_name(&next_reject.client_order_id) {
+            Ok(p) => p,
+            Err(_) => {
+                warn!("Could not split client_order_id {:?} from OrderRejectMessage. Cannot route to destination client.", &next_reject.client_order_id);
+                continue;
+            }
+        };
+
+        if let Err(e) = order_store.lock().await.on_reject(&next_reject.client_order_id) {
+            warn!("{:?}", e);
+        }
+        next_reject.client_order_id = client_order_id;
+
+        let client = match client_socket(&clients, &client_id).await {
+            None => continue,
+            Some(client) => client
+        };
+        if let Err(e) = write_next_frame(&next_reject, client.lock().await).await {
+            warn!("Failed to write order rejection to client {:?}: {:?}", client_id, e);
+        }
+    }
+}
+
+/// Listen to the queues for Kalshi's order updates, applying them to the order store and
+/// sending the owning client the order's new status whenever an update changes it.
+async fn wait_for_order_updates(
+    queues: Queues,
+    clients: Arc<Mutex<HashMap<String, Arc<Mutex<OwnedWriteHalf>>>>>,
+    order_store: Arc<Mutex<OrderStore>>,
+    positions: Arc<Mutex<Positions>>,
+    journal: SharedJournal
+) -> Result<()> {
+    let order_update_consumer = queues.consumer::<OrderUpdateMessage>().await?.with_journal(journal);
+
+    loop {
+        let next_update = match order_update_consumer.get_next().await? {
+            None => continue,
+            Some(update) => update
+        };
+
+        let mut store = order_store.lock().await;
+        if store.get(&next_update.client_order_id).is_none() {
+            debug!("Ignoring order update for order {:?}, which was not placed through the OMS.", next_update.order_id);
+            continue;
+        }
+        let (client_id, client_order_id) = match split_client_name(&next_update.client_order_id) {
+            Ok(p) => p,
+            Err(_) => {
+                warn!("Could not split client_order_id {:?} from OrderUpdateMessage. Cannot route to destination client.", &next_update.client_order_id);
+                continue;
+            }
+        };
+        let status = match store.on_order_update(&next_update) {
+            Ok(Some(order)) => status::from_store(order, client_order_id),
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("{:?}", e);
+                continue;
+            }
+        };
+        drop(store);
+
+        if let Some(client) = client_socket(&clients, &client_id).await {
+            if let Err(e) = write_next_frame(&status, client.lock().await).await {
+                warn!("Failed to write order status to client {:?}: {:?}", client_id, e);
+            }
+        }
+
+        // an update can be the first the store hears of the order id, as for an order confirmed while the gateway was down
+        apply_held_fills(&next_update.order_id, &clients, &order_store, &positions).await;
+    }
+}
+
+/// Listen to the queues for fill messages and route them to the clients that own the filled orders.
+async fn wait_for_fills(
+    queues: Queues,
+    clients: Arc<Mutex<HashMap<String, Arc<Mutex<OwnedWriteHalf>>>>>,
+    order_store: Arc<Mutex<OrderStore>>,
+    positions: Arc<Mutex<Positions>>,
+    journal: SharedJournal
+) -> Result<()> {
+    let fill_consumer = queues.consumer::<FillMessage>().await?.with_journal(journal);
+
+    loop {
+        let next_fill = match fill_consumer.get_next().await? {
+            None => continue,
+            Some(fill) => fill.msg
+        };
+
+        apply_fill(next_fill, &clients, &order_store, &positions).await;
+    }
+}
+
+/// Apply a fill to the order store and its owner's positions, and send it to the owner. A fill
+/// for an order not confirmed yet is held by the order store until its order id is known.
+async fn apply_fill(
+    fill: Fill,
+    clients: &Mutex<HashMap<String, Arc<Mutex<OwnedWriteHalf>>>>,
+    order_store: &Mutex<OrderStore>,
+    positions: &Mutex<Positions>
+) {
+    let client_id = match order_store.lock().await.on_fill(&fill) {
+        Ok(Some(order)) => order.client_id.clone(),
+        Ok(None) => {
+            debug!("Holding fill {:?} until order {:?} is confirmed.", fill.trade_id, fill.order_id);
+            return;
+        },
+        Err(e) => {
+            warn!("Could not apply fill {:?}: {:?}. Cannot route to destination client.", fill.trade_id, e);
+            return;
+        }
+    };
+    positions.lock().await.apply_fill(&client_id, &fill);
+
+    let client = match client_socket(clients, &client_id).await {
+        None => return,
+        Some(client) => client
+    };
+    if let Err(e) = write_next_frame(&fill, client.lock().await).await {
+        warn!("Failed to write fill to client {:?}: {:?}", client_id, e);
+    }
+}
+
+/// Apply the fills the order store held for an order id it has just learned.
+async fn apply_held_fills(
+    order_id: &str,
+    clients: &Mutex<HashMap<String, Arc<Mutex<OwnedWriteHalf>>>>,
+    order_store: &Mutex<OrderStore>,
+    positions: &Mutex<Positions>
+) {
+    let held = order_store.lock().await.take_held_fills(order_id);
+    for fill in held {
+        apply_fill(fill, clients, order_store, positions).await;
+    }
+}
+
+/// Listen to the queues for market lifecycle events. When a market stops trading its resting orders
+/// expire, and their owners are sent the orders' new status; when it settles, positions in it are closed out.
+async fn wait_for_market_lifecycle(
+    queues: Queues,
+    clients: Arc<Mutex<HashMap<String, Arc<Mutex<OwnedWriteHalf>>>>>,
+    order_store: Arc<Mutex<OrderStore>>,
+    positions: Arc<Mutex<Positions>>,
+    markets: Arc<Mutex<Markets>>,
+    paper: Arc<PaperExchange>,
+    journal: SharedJournal
+) -> Result<()> {
+    let lifecycle_consumer = queues.consumer::<MarketLifecycleMessage>().await?.with_journal(journal);
+
+    loop {
+        let next_update = match lifecycle_consumer.get_next().await? {
+            None => continue,
+            Some(update) => update
+        };
+
+        let ticker = next_update.market_ticker.clone();
+        let (market_status, result) = (next_update.status, next_update.result.clone());
+        if markets.lock().await.on_lifecycle(next_update) == Some(market_status) {
+            continue;
+        }
+        info!("Market {:?} is now {:?}.", ticker, market_status);
+        if market_status == MarketStatus::Open {
+            continue;
+        }
+
+        // paper positions settle and paper orders expire with the real market
+        if market_status == MarketStatus::Settled {
+            paper.positions.lock().await.settle(&ticker, result.as_ref());
+        }
+        route_paper_events(&clients, paper.on_market_close(&ticker).await).await;
+
+        let statuses = {
+            let mut positions = positions.lock().await;
+            let mut order_store = order_store.lock().await;
+            if market_status == MarketStatus::Settled {
+                info!("Settling positions in {:?} with result {:?}.", ticker, result);
+                positions.settle(&ticker, result.as_ref());
+            }
+            let expired = match order_store.on_market_close(&ticker) {
+                Ok(expired) => expired,
+                Err(e) => {
+                    warn!("{:?}", e);
+                    continue;
+                }
+            };
+            let mut statuses = Vec::new();
+            for namespaced_client_order_id in expired {
+                let (client_id, client_order_id) = match split_client_name(&namespaced_client_order_id) {
+                    Ok(p) => p,
+                    Err(_) => {
+                        warn!("Could not split client_order_id {:?} of expired order. Cannot route to destination client.", namespaced_client_order_id);
+                        continue;
+                    }
+                };
+                if let Some(order) = order_store.get(&namespaced_client_order_id) {
+                    statuses.push((client_id, status::from_store(order, client_order_id)));
+                }
+            }
+            statuses
+        };
+        if !statuses.is_empty() {
+            info!("Expired {} resting orders on closed market {:?}.", statuses.len(), ticker);
+        }
+
+        for (client_id, order_status) in statuses {
+            let client = match client_socket(&clients, &client_id).await {
+                None => continue,
+                Some(client) => client
+            };
+            if let Err(e) = write_next_frame(&order_status, client.lock().await).await {
+                warn!("Failed to write order status to client {:?}: {:?}", client_id, e);
+            }
+        }
+    }
+}
+
+/// Listen to the queues for book updates, marking live and paper positions to the mid, passing the top of book to subscribed clients
+/// and crossing paper orders with the live liquidity.
+async fn wait_for_books(
+    queues: Queues,
+    clients: Arc<Mutex<HashMap<String, Arc<Mutex<OwnedWriteHalf>>>>>,
+    market_data: Arc<Mutex<MarketDataSubscriptions>>,
+    positions: Arc<Mutex<Positions>>,
+    paper: Arc<PaperExchange>
+) -> Result<()> {
+    let book_consumer = queues.consumer::<BookMessage>().await?;
+
+    loop {
+        let next_book = match book_consumer.get_next().await? {
+            None => continue,
+            Some(book) => book
+        };
+
+        if let Some(mid) = next_book.yes_mid() {
+            positions.lock().await.mark(&next_book.market_ticker, mid);
+            paper.positions.lock().await.mark(&next_book.market_ticker, mid);
+        }
+        market_data.lock().await.on_book(&next_book);
+        route_paper_events(&clients, paper.on_book(&next_book).await).await;
+    }
+}
+
+/// Listen to the queues for market trades and pass them to subscribed clients.
+async fn wait_for_trades(queues: Queues, market_data: Arc<Mutex<MarketDataSubscriptions>>) -> Result<()> {
+    let trade_consumer = queues.consumer::<TradeMessage>().await?;
+
+    loop {
+        let next_trade = match trade_consumer.get_next().await? {
+            None => continue,
+            Some(trade) => trade
+        };
+        market_data.lock().await.on_trade(&next_trade);
+    }
+}
+
+/// Listen to the queues for ticker updates and pass them to subscribed clients.
+async fn wait_for_tickers(queues: Queues, market_data: Arc<Mutex<MarketDataSubscriptions>>) -> Result<()> {
+    let ticker_consumer = queues.consumer::<TickerMessage>().await?;
+
+    loop {
+        let next_ticker = match ticker_consumer.get_next().await? {
+            None => continue,
+            Some(ticker) => ticker
+        };
+        market_data.lock().await.on_ticker(&next_ticker);
+    }
+}
+
+/// Listen to the queues for the fill feed's status, telling every client when fills go stale and
+/// when they recover. A feed that stops publishing its status altogether is taken to be stale too.
+async fn wait_for_feed_status(
+    queues: Queues,
+    clients: Arc<Mutex<HashMap<String, Arc<Mutex<OwnedWriteHalf>>>>>,
+    feed_status: Arc<Mutex<FeedStatusMessage>>
+) -> Result<()> {
+    let feed_status_consumer = queues.consumer::<FeedStatusMessage>().await?;
+    let mut last_status_at = tokio::time::Instant::now();
+
+    loop {
+        let next_status = match feed_status_consumer.get_next().await? {
+            Some(status) => {
+                last_status_at = tokio::time::Instant::now();
+                status
+            },
+            None if last_status_at.elapsed() > constants::FEED_STATUS_TIMEOUT => FeedStatusMessage {
+                connected: false,
+                stale: true,
+                silent_for_ms: last_status_at.elapsed().as_millis() as u64
+            },
+            None => continue
+        };
+
+        let was_stale = {
+            let mut feed_status = feed_status.lock().await;
+            std::mem::replace(&mut *feed_status, next_status.clone()).stale
+        };
+        if next_status.stale == was_stale {
+            continue;
+        }
+        match next_status.stale {
+            true => warn!("Fills are stale: {:?}", next_status),
+            false => info!("Fills are live again.")
+        }
+
+        let sockets: Vec<(String, Arc<Mutex<OwnedWriteHalf>>)> = clients.lock().await.iter()
+            .map(|(client_id, socket)| (client_id.clone(), socket.clone()))
+            .collect();
+        for (client_id, socket) in sockets {
+            if let Err(e) = write_next_frame(&next_status, socket.lock().await).await {
+                warn!("Failed to write feed status to client {:?}: {:?}", client_id, e);
+            }
+        }
+    }
+}
+
+/// Prefix a client's order ID with its internal client identifier so that
+/// exchange-side order IDs are unique across clients.
+fn namespace_client_order_id(client_id: &str, client_order_id: &str) -> String {
+    format!("{}{}", client_id, client_order_id)
+}
+
+/// Deconstruct an exchange-side client order ID into an internal client identifier 
+/// and the client's provided order ID.
+fn split_client_name(client_order_id: &str) -> Result<(String, String)> {
+    if !client_order_id.is_char_boundary(constants::CLIENT_NAME_SIZE_BYTES) {
+        return Err(anyhow::anyhow!("Client order ID {:?} does not start with a client name", client_order_id));
+    }
+    let (client_id, client_order_id) = client_order_id.split_at(constants::CLIENT_NAME_SIZE_BYTES);
+    Ok((client_id.to_string(), client_order_id.to_string()))
+}
+
+/// Writes the message to the client as a header byte, a length byte and the serialized message.
+async fn write_next_frame<T: ClientMessage>(message: &T, mut stream: MutexGuard<'_, OwnedWriteHalf>) -> Result<()> {
+    protocol::write::write_message(&mut *stream, message).await
+}
@@ -0,0 +1,178 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::sync::{Mutex, Notify};
+use tracing::warn;
+
+use protocol::messages::TopOfBookMessage;
+use protocol::read::MarketDataRequest;
+use queue_client::queue_data::market_data::{BookMessage, TickerMessage, TradeMessage};
+
+use crate::constants::MARKET_DATA_MAX_PENDING_TRADES;
+
+/// Market data not yet written to a client.
+#[derive(Default)]
+struct PendingMarketData {
+    books: HashMap<String, TopOfBookMessage>,
+    tickers: HashMap<String, TickerMessage>,
+    trades: VecDeque<TradeMessage>,
+    dropped_trades: usize
+}
+
+/// The market data waiting to be written to one client. Top of book and ticker updates
+/// replace any for the same market not yet written, so a slow client skips to the latest
+/// prices instead of falling further behind. Trades are kept in order up to a limit.
+pub struct ClientFeed {
+    pending: std::sync::Mutex<PendingMarketData>,
+    notify: Notify,
+    closed: AtomicBool
+}
+
+impl ClientFeed {
+
+    pub fn new() -> ClientFeed {
+        ClientFeed {
+            pending: std::sync::Mutex::new(PendingMarketData::default()),
+            notify: Notify::new(),
+            closed: AtomicBool::new(false)
+        }
+    }
+
+    pub fn push_book(&self, book: TopOfBookMessage) {
+        self.pending.lock().unwrap().books.insert(book.ticker.clone(), book);
+        self.notify.notify_one();
+    }
+
+    pub fn push_ticker(&self, ticker: TickerMessage) {
+        self.pending.lock().unwrap().tickers.insert(ticker.market_ticker.clone(), ticker);
+        self.notify.notify_one();
+    }
+
+    pub fn push_trade(&self, trade: TradeMessage) {
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if pending.trades.len() == MARKET_DATA_MAX_PENDING_TRADES {
+                pending.trades.pop_front();
+                pending.dropped_trades += 1;
+            }
+            pending.trades.push_back(trade);
+        }
+        self.notify.notify_one();
+    }
+
+    /// Stop the feed's writer once the client is gone.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        self.notify.notify_one();
+    }
+
+    fn take(&self) -> PendingMarketData {
+        std::mem::take(&mut *self.pending.lock().unwrap())
+    }
+}
+
+/// Write a client's market data to it as it arrives, until the feed is closed or a write fails.
+pub async fn write_feed(name: String, feed: Arc<ClientFeed>, socket_handle: Arc<Mutex<OwnedWriteHalf>>) {
+    loop {
+        feed.notify.notified().await;
+        if feed.closed.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let pending = feed.take();
+        if pending.dropped_trades > 0 {
+            warn!("Client {:?} is reading market data too slowly. Dropped {} trades.", name, pending.dropped_trades);
+        }
+
+        // the socket is locked for one message at a time, so order updates and fills are not held up behind a batch
+        let written = async {
+            for book in pending.books.values() {
+                protocol::write::write_message(&mut *socket_handle.lock().await, book).await?;
+            }
+            for ticker in pending.tickers.values() {
+                protocol::write::write_message(&mut *socket_handle.lock().await, ticker).await?;
+            }
+            for trade in pending.trades.iter() {
+                protocol::write::write_message(&mut *socket_handle.lock().await, trade).await?;
+            }
+            anyhow::Ok(())
+        }.await;
+        if let Err(e) = written {
+            warn!("Failed to write market data to client {:?}: {:?}", name, e);
+            return;
+        }
+    }
+}
+
+/// Which clients want which kinds of market data for each ticker, along with the
+/// latest top of book of each ticker so that new subscribers start with a price.
+pub struct MarketDataSubscriptions {
+    subscribers: HashMap<String, Vec<(MarketDataRequest, Arc<ClientFeed>)>>,
+    latest_books: HashMap<String, TopOfBookMessage>
+}
+
+impl MarketDataSubscriptions {
+
+    pub fn new() -> Self {
+        MarketDataSubscriptions {
+            subscribers: HashMap::new(),
+            latest_books: HashMap::new()
+        }
+    }
+
+    /// Send the requested market data on a ticker to a client's feed, replacing any earlier request for the ticker.
+    pub fn subscribe(&mut self, request: MarketDataRequest, feed: Arc<ClientFeed>) {
+        if request.top_of_book {
+            if let Some(book) = self.latest_books.get(&request.ticker) {
+                feed.push_book(book.clone());
+            }
+        }
+        let subscribers = self.subscribers.entry(request.ticker.clone()).or_default();
+        subscribers.retain(|(_, subscribed)| !Arc::ptr_eq(subscribed, &feed));
+        subscribers.push((request, feed));
+    }
+
+    pub fn unsubscribe(&mut self, ticker: &str, feed: &Arc<ClientFeed>) {
+        if let Some(subscribers) = self.subscribers.get_mut(ticker) {
+            subscribers.retain(|(_, subscribed)| !Arc::ptr_eq(subscribed, feed));
+        }
+    }
+
+    /// Drop every subscription of a client's feed.
+    pub fn remove_feed(&mut self, feed: &Arc<ClientFeed>) {
+        for subscribers in self.subscribers.values_mut() {
+            subscribers.retain(|(_, subscribed)| !Arc::ptr_eq(subscribed, feed));
+        }
+    }
+
+    pub fn on_book(&mut self, book: &BookMessage) {
+        let top_of_book = TopOfBookMessage::from(book);
+        for (request, feed) in self.subscribers_of(&book.market_ticker) {
+            if request.top_of_book {
+                feed.push_book(top_of_book.clone());
+            }
+        }
+        self.latest_books.insert(book.market_ticker.clone(), top_of_book);
+    }
+
+    pub fn on_trade(&self, trade: &TradeMessage) {
+        for (request, feed) in self.subscribers_of(&trade.market_ticker) {
+            if request.trades {
+                feed.push_trade(trade.clone());
+            }
+        }
+    }
+
+    pub fn on_ticker(&self, ticker: &TickerMessage) {
+        for (request, feed) in self.subscribers_of(&ticker.market_ticker) {
+            if request.ticker_updates {
+                feed.push_ticker(ticker.clone());
+            }
+        }
+    }
+
+    fn subscribers_of(&self, ticker: &str) -> &[(MarketDataRequest, Arc<ClientFeed>)] {
+        self.subscribers.get(ticker).map_or(&[], |subscribers| subscribers.as_slice())
+    }
+}
@@ -0,0 +1,71 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+use kalshi_rest::KalshiRestClient;
+use queue_client::queue_data::market_data::{MarketLifecycleMessage, MarketStatus};
+
+/// The latest known status of each market the OMS has been asked to trade or has heard
+/// about on the lifecycle feed, used to refuse orders on markets that no longer trade.
+pub struct Markets {
+    markets: HashMap<String, MarketLifecycleMessage>
+}
+
+impl Markets {
+
+    pub fn new() -> Self {
+        Markets {
+            markets: HashMap::new()
+        }
+    }
+
+    pub fn is_known(&self, ticker: &str) -> bool {
+        self.markets.contains_key(ticker)
+    }
+
+    /// Record a market's new status, returning the status it had before, if it was known.
+    pub fn on_lifecycle(&mut self, update: MarketLifecycleMessage) -> Option<MarketStatus> {
+        self.markets.insert(update.market_ticker.clone(), update).map(|previous| previous.status)
+    }
+
+    /// Refuse an order on a market that has closed or whose close time has passed.
+    /// Markets the OMS knows nothing about are left for the exchange to judge.
+    pub fn check_open(&self, ticker: &str) -> Result<()> {
+        let market = match self.markets.get(ticker) {
+            None => return Ok(()),
+            Some(market) => market
+        };
+        let stage = match market.status {
+            MarketStatus::Open => None,
+            MarketStatus::Closed => Some("closed"),
+            MarketStatus::Determined => Some("determined"),
+            MarketStatus::Settled => Some("settled")
+        };
+        if let Some(stage) = stage {
+            return Err(anyhow::anyhow!("Market {} is {} and no longer accepts orders", ticker, stage));
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs() as i64);
+        match market.close_ts {
+            Some(close_ts) if now >= close_ts => Err(anyhow::anyhow!("Market {} closed at {} and no longer accepts orders", ticker, close_ts)),
+            _ => Ok(())
+        }
+    }
+}
+
+/// Make sure the market's status is known before an order on it is checked, polling Kalshi's
+/// REST API the first time the OMS sees the ticker. The lifecycle feed keeps it current after that.
+pub async fn ensure_known(markets: &Mutex<Markets>, exchange_client: &KalshiRestClient, ticker: &str) -> Result<()> {
+    if markets.lock().await.is_known(ticker) {
+        return Ok(());
+    }
+    let market = exchange_client.get_market(ticker).await?;
+    let market = MarketLifecycleMessage::try_from(market)?;
+
+    // a lifecycle event that arrived during the lookup is newer than what the lookup found
+    let mut markets = markets.lock().await;
+    if !markets.is_known(ticker) {
+        markets.on_lifecycle(market);
+    }
+    Ok(())
+}
@@ -0,0 +1,621 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+
+use kalshi::{Action, Side};
+use queue_client::queue_data::cancels::CancelOrderMessage;
+use queue_client::queue_data::fills::Fill;
+use queue_client::queue_data::orders::{CreateOrderMessage, OrderState, OrderUpdateMessage};
+
+use crate::constants::MAX_HELD_FILLS;
+
+/// An order placed through the OMS and everything the OMS knows about it.
+#[derive(Debug)]
+pub struct Order {
+    pub client_id: String,
+    pub client_order_id: String,
+    pub order_id: Option<String>,
+    pub ticker: String,
+    pub side: Side,
+    pub action: Action,
+    pub count: i32,
+    pub filled_count: i32,
+    pub fill_cost: i64,
+    pub state: OrderState,
+    /// Cancel the order as soon as the exchange confirms it, because its client
+    /// disconnected before the order had an exchange order id to cancel by.
+    pub cancel_on_confirm: bool
+}
+
+impl Order {
+
+    /// The number of contracts still working on the exchange
+    pub fn remaining_count(&self) -> i32 {
+        self.count - self.filled_count
+    }
+
+    /// The average price, in cents on the order's side, at which the order has filled
+    pub fn average_fill_price(&self) -> Option<f64> {
+        match self.filled_count {
+            0 => None,
+            filled => Some(self.fill_cost as f64 / filled as f64)
+        }
+    }
+}
+
+/// The central store of every order placed through the OMS, keyed by namespaced
+/// client order id with an index from exchange order id back to the owning order.
+/// Confirms, cancel confirms, fills and rejects drive each order through its states.
+pub struct OrderStore {
+    orders: HashMap<String, Order>,
+    client_order_ids: HashMap<String, String>,
+    trade_ids: HashSet<String>,
+    /// Fills for exchange order ids not yet known, held until the order's confirm or update maps them.
+    held_fills: HashMap<String, Vec<Fill>>,
+    held_trade_ids: HashSet<String>
+}
+
+impl OrderStore {
+
+    pub fn new() -> Self {
+        OrderStore {
+            orders: HashMap::new(),
+            client_order_ids: HashMap::new(),
+            trade_ids: HashSet::new(),
+            held_fills: HashMap::new(),
+            held_trade_ids: HashSet::new()
+        }
+    }
+
+    /// Record a new order sent to the exchange on behalf of a client.
+    pub fn insert_new(&mut self, client_id: &str, order: &CreateOrderMessage) -> Result<()> {
+        if self.orders.contains_key(&order.client_order_id) {
+            return Err(anyhow::anyhow!("Duplicate client order id {:?}", order.client_order_id));
+        }
+        self.orders.insert(order.client_order_id.clone(), Order {
+            client_id: client_id.to_string(),
+            client_order_id: order.client_order_id.clone(),
+            order_id: None,
+            ticker: order.ticker.clone(),
+            side: order.side.clone(),
+            action: order.action.clone(),
+            count: order.count,
+            filled_count: 0,
+            fill_cost: 0,
+            state: OrderState::PendingNew,
+            cancel_on_confirm: false
+        });
+        Ok(())
+    }
+
+    /// Start tracking an order found on the exchange that the OMS did not know about, as the
+    /// exchange reports it: its size, how much of it has filled and at what cost, and its state.
+    /// Its fills may be older than any fill lookup reaches, so its counts are not rebuilt from them.
+    #[allow(clippy::too_many_arguments)]
+    pub fn adopt(&mut self, client_id: &str, client_order_id: &str, order_id: &str, ticker: &str, side: Side, action: Action, count: i32, filled_count: i32, fill_cost: i64, state: OrderState) {
+        self.orders.insert(client_order_id.to_string(), Order {
+            client_id: client_id.to_string(),
+            client_order_id: client_order_id.to_string(),
+            order_id: Some(order_id.to_string()),
+            ticker: ticker.to_string(),
+            side,
+            action,
+            count,
+            filled_count,
+            fill_cost,
+            state,
+            cancel_on_confirm: false
+        });
+        self.client_order_ids.insert(order_id.to_string(), client_order_id.to_string());
+    }
+
+    /// Move an order to the state the exchange reports for it, if the order lifecycle allows it.
+    pub fn reconcile_state(&mut self, client_order_id: &str, state: OrderState) -> Result<OrderState> {
+        let order = self.get_mut(client_order_id)?;
+        if order.state != state {
+            transition(order, state)?;
+        }
+        Ok(order.state)
+    }
+
+    /// Record a fill already counted in an adopted order's filled count, so that it is not
+    /// applied to the order again. Returns false if its trade id has been seen before.
+    pub fn record_adopted_fill(&mut self, fill: &Fill) -> bool {
+        !self.held_trade_ids.contains(&fill.trade_id) && self.trade_ids.insert(fill.trade_id.clone())
+    }
+
+    /// Stop tracking an order that never reached the exchange.
+    pub fn remove(&mut self, client_order_id: &str) -> Option<Order> {
+        let order = self.orders.remove(client_order_id)?;
+        if let Some(ref order_id) = order.order_id {
+            self.client_order_ids.remove(order_id);
+        }
+        Some(order)
+    }
+
+    pub fn get(&self, client_order_id: &str) -> Option<&Order> {
+        self.orders.get(client_order_id)
+    }
+
+    pub fn get_by_order_id(&self, order_id: &str) -> Option<&Order> {
+        self.client_order_ids.get(order_id).and_then(|clordid| self.orders.get(clordid))
+    }
+
+    /// Every client that has placed an order in the ticker.
+    pub fn clients_trading(&self, ticker: &str) -> HashSet<&str> {
+        self.orders.values()
+            .filter(|order| order.ticker == ticker)
+            .map(|order| order.client_id.as_str())
+            .collect()
+    }
+
+    /// All orders currently working on the exchange, across every client.
+    pub fn open_orders(&self) -> Vec<&Order> {
+        self.orders.values().filter(|order| order.state.is_open()).collect()
+    }
+
+    /// All orders the given client currently has working on the exchange.
+    pub fn open_orders_for(&self, client_id: &str) -> Vec<&Order> {
+        self.orders.values()
+            .filter(|order| order.client_id == client_id && order.state.is_open())
+            .collect()
+    }
+
+    /// The exchange accepted the order and assigned it an order id. An order the order-update
+    /// feed has already closed is left as it is, since its confirm has been overtaken.
+    pub fn on_confirm(&mut self, client_order_id: &str, order_id: &str) -> Result<OrderState> {
+        let order = self.get_mut(client_order_id)?;
+        if !order.state.is_open() {
+            return Ok(order.state);
+        }
+        order.order_id = Some(order_id.to_string());
+        if order.state == OrderState::PendingNew {
+            transition(order, OrderState::Open)?;
+        }
+        let state = order.state;
+        self.client_order_ids.insert(order_id.to_string(), client_order_id.to_string());
+        Ok(state)
+    }
+
+    /// The exchange refused the order.
+    pub fn on_reject(&mut self, client_order_id: &str) -> Result<OrderState> {
+        let order = self.get_mut(client_order_id)?;
+        transition(order, OrderState::Rejected)?;
+        Ok(order.state)
+    }
+
+    /// The client asked for the order to be cancelled, which it can only be once confirmed.
+    pub fn on_cancel_request(&mut self, client_order_id: &str) -> Result<OrderState> {
+        let order = self.get_mut(client_order_id)?;
+        if order.order_id.is_none() {
+            return Err(anyhow::anyhow!("Order {:?} has not been confirmed, so it cannot be cancelled yet", client_order_id));
+        }
+        transition(order, OrderState::PendingCancel)?;
+        Ok(order.state)
+    }
+
+    /// The client disconnected before the exchange confirmed the order, so there is no order id
+    /// to cancel it by yet. It stays pending new until its confirm or reject arrives, and
+    /// `take_cancel_on_confirm` hands over its cancel once confirmed.
+    pub fn cancel_on_confirm(&mut self, client_order_id: &str) -> Result<OrderState> {
+        let order = self.get_mut(client_order_id)?;
+        if order.state != OrderState::PendingNew {
+            return Err(anyhow::anyhow!("Order {:?} is {:?}, not waiting on its confirm", client_order_id, order.state));
+        }
+        order.cancel_on_confirm = true;
+        Ok(order.state)
+    }
+
+    /// The cancel owed to a confirmed order whose client disconnected before the confirm,
+    /// if it is still working, marking the order pending cancel. It is handed over only once.
+    pub fn take_cancel_on_confirm(&mut self, client_order_id: &str) -> Option<CancelOrderMessage> {
+        let order = self.orders.get_mut(client_order_id)?;
+        if !order.cancel_on_confirm || !matches!(order.state, OrderState::Open | OrderState::PartiallyFilled) {
+            return None;
+        }
+        let order_id = order.order_id.clone()?;
+        transition(order, OrderState::PendingCancel).ok()?;
+        order.cancel_on_confirm = false;
+        Some(CancelOrderMessage { order_id, client_order_id: client_order_id.to_string() })
+    }
+
+    /// The exchange cancelled the order. The order-update feed may already have reported the cancel.
+    pub fn on_cancel_confirm(&mut self, client_order_id: &str) -> Result<OrderState> {
+        let order = self.get_mut(client_order_id)?;
+        if order.state != OrderState::Cancelled {
+            transition(order, OrderState::Cancelled)?;
+        }
+        Ok(order.state)
+    }
+
+    /// The exchange refused to cancel the order, so it is working again as it was before the cancel
+    /// was asked for. An order the fill or order-update feed has already closed is left as it is.
+    pub fn on_cancel_reject(&mut self, client_order_id: &str) -> Result<OrderState> {
+        let order = self.get_mut(client_order_id)?;
+        if order.state != OrderState::PendingCancel {
+            return Ok(order.state);
+        }
+        let working = if order.filled_count > 0 { OrderState::PartiallyFilled } else { OrderState::Open };
+        transition(order, working)?;
+        Ok(order.state)
+    }
+
+    /// The order expired on the exchange without being completely filled.
+    pub fn on_expire(&mut self, client_order_id: &str) -> Result<OrderState> {
+        let order = self.get_mut(client_order_id)?;
+        transition(order, OrderState::Expired)?;
+        Ok(order.state)
+    }
+
+    /// The market stopped trading, which expires every order still resting on it.
+    /// Returns the client order ids of the expired orders.
+    pub fn on_market_close(&mut self, ticker: &str) -> Result<Vec<String>> {
+        let expiring: Vec<String> = self.orders.values()
+            .filter(|order| order.ticker == ticker)
+            .filter(|order| matches!(order.state, OrderState::Open | OrderState::PartiallyFilled | OrderState::PendingCancel))
+            .map(|order| order.client_order_id.clone())
+            .collect();
+        for client_order_id in expiring.iter() {
+            transition(self.get_mut(client_order_id)?, OrderState::Expired)?;
+        }
+        Ok(expiring)
+    }
+
+    /// Kalshi's order-update feed reported a change to the order, which may have happened
+    /// off the OMS's REST path. Acknowledgements, amends, cancels and expiries are applied;
+    /// fills are left to the fill feed. Returns the order if the update changed it.
+    pub fn on_order_update(&mut self, update: &OrderUpdateMessage) -> Result<Option<&Order>> {
+        let order = self.get_mut(&update.client_order_id)?;
+        let mut changed = false;
+
+        if order.order_id.is_none() {
+            order.order_id = Some(update.order_id.clone());
+            changed = true;
+        }
+
+        // an amend changes the size of the order, which Kalshi reports as filled plus remaining
+        let amended_count = update.filled_count + update.remaining_count;
+        if update.state.is_open() && amended_count != order.count {
+            order.count = amended_count;
+            changed = true;
+        }
+
+        let next = match (order.state, update.state) {
+            (OrderState::PendingNew, OrderState::Open | OrderState::PartiallyFilled) => Some(OrderState::Open),
+            // an order can be cancelled or expire before its confirm arrives
+            (OrderState::PendingNew | OrderState::Open | OrderState::PartiallyFilled | OrderState::PendingCancel, OrderState::Cancelled | OrderState::Expired) => Some(update.state),
+            _ => None
+        };
+        if let Some(next) = next {
+            transition(order, next)?;
+            changed = true;
+        }
+
+        if !changed {
+            return Ok(None);
+        }
+        self.client_order_ids.insert(update.order_id.clone(), update.client_order_id.clone());
+        Ok(Some(&self.orders[&update.client_order_id]))
+    }
+
+    /// Some or all of the order traded. Returns the order after the fill is applied, or `None`
+    /// if the fill is for an order id not known yet, as when the fill feed beats the confirm:
+    /// the fill is held until `take_held_fills` hands it back. A fill is an execution whatever
+    /// the order's state, so one racing a cancel is applied too. Only a fill whose trade id has
+    /// already been applied or held is refused.
+    pub fn on_fill(&mut self, fill: &Fill) -> Result<Option<&Order>> {
+        if self.trade_ids.contains(&fill.trade_id) || self.held_trade_ids.contains(&fill.trade_id) {
+            return Err(anyhow::anyhow!("Duplicate fill for trade id {:?}", fill.trade_id));
+        }
+        let client_order_id = match self.client_order_ids.get(&fill.order_id) {
+            None => {
+                self.hold(fill)?;
+                return Ok(None);
+            },
+            Some(clordid) => clordid.clone()
+        };
+        let order = self.get_mut(&client_order_id)?;
+        let completes = order.filled_count + fill.count >= order.count;
+        let next = match (order.state, completes) {
+            (OrderState::Filled, _) => None,
+            (_, true) => Some(OrderState::Filled),
+            (OrderState::PendingNew | OrderState::Open | OrderState::PartiallyFilled, false) => Some(OrderState::PartiallyFilled),
+            // a fill racing a cancel leaves the cancel pending, and one that traded before a cancel or expiry leaves the order closed
+            _ => None
+        };
+        if let Some(next) = next {
+            transition(order, next)?;
+        }
+        let price = match fill.side {
+            Side::Yes => fill.yes_price,
+            Side::No => fill.no_price
+        };
+        order.filled_count += fill.count;
+        order.fill_cost += price as i64 * fill.count as i64;
+        self.trade_ids.insert(fill.trade_id.clone());
+        Ok(Some(&self.orders[&client_order_id]))
+    }
+
+    /// The fills held for an order id the store has just learned, to be applied with `on_fill`.
+    pub fn take_held_fills(&mut self, order_id: &str) -> Vec<Fill> {
+        if !self.client_order_ids.contains_key(order_id) {
+            return Vec::new();
+        }
+        let fills = self.held_fills.remove(order_id).unwrap_or_default();
+        for fill in fills.iter() {
+            self.held_trade_ids.remove(&fill.trade_id);
+        }
+        fills
+    }
+
+    fn hold(&mut self, fill: &Fill) -> Result<()> {
+        if self.held_trade_ids.len() >= MAX_HELD_FILLS {
+            return Err(anyhow::anyhow!("Fill for unknown order id {:?}, with {} fills already held", fill.order_id, MAX_HELD_FILLS));
+        }
+        self.held_trade_ids.insert(fill.trade_id.clone());
+        self.held_fills.entry(fill.order_id.clone()).or_default().push(fill.clone());
+        Ok(())
+    }
+
+    fn get_mut(&mut self, client_order_id: &str) -> Result<&mut Order> {
+        match self.orders.get_mut(client_order_id) {
+            None => Err(anyhow::anyhow!("Unknown client order id {:?}", client_order_id)),
+            Some(order) => Ok(order)
+        }
+    }
+}
+
+/// Move an order to its next state, refusing transitions the order lifecycle does not allow.
+fn transition(order: &mut Order, next: OrderState) -> Result<()> {
+    use OrderState::*;
+    let valid = match (order.state, next) {
+        (PendingNew, Open | PartiallyFilled | Filled | Cancelled | Expired | Rejected) => true,
+        (Open, PartiallyFilled | Filled | PendingCancel | Cancelled | Expired) => true,
+        (PartiallyFilled, PartiallyFilled | Filled | PendingCancel | Cancelled | Expired) => true,
+        (PendingCancel, Open | PartiallyFilled | Filled | Cancelled | Expired) => true,
+        // the rest of the order traded before the cancel or expiry took effect
+        (Cancelled | Expired, Filled) => true,
+        _ => false
+    };
+    if !valid {
+        return Err(anyhow::anyhow!(
+            "Invalid transition for order {:?}: {:?} -> {:?}", order.client_order_id, order.state, next));
+    }
+    order.state = next;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kalshi::OrderType;
+
+    fn order(client_order_id: &str, count: i32) -> CreateOrderMessage {
+        CreateOrderMessage {
+            action: Action::Buy,
+            client_order_id: client_order_id.to_string(),
+            count,
+            side: Side::Yes,
+            ticker: "TICKER".to_string(),
+            input_type: OrderType::Limit,
+            buy_max_cost: None,
+            expiration_ts: None,
+            no_price: None,
+            sell_position_floor: None,
+            yes_price: Some(40)
+        }
+    }
+
+    fn fill(trade_id: &str, order_id: &str, count: i32) -> Fill {
+        Fill {
+            trade_id: trade_id.to_string(),
+            order_id: order_id.to_string(),
+            market_ticker: "TICKER".to_string(),
+            is_taker: true,
+            side: Side::Yes,
+            yes_price: 40,
+            no_price: 60,
+            count,
+            action: Action::Buy,
+            ts: 0
+        }
+    }
+
+    fn update(client_order_id: &str, order_id: &str, state: OrderState, filled_count: i32, remaining_count: i32) -> OrderUpdateMessage {
+        OrderUpdateMessage {
+            order_id: order_id.to_string(),
+            client_order_id: client_order_id.to_string(),
+            ticker: "TICKER".to_string(),
+            state,
+            yes_price: 40,
+            filled_count,
+            remaining_count,
+            ts: 0
+        }
+    }
+
+    /// A store holding one confirmed order of `count` contracts, `a` with order id `order-a`.
+    fn confirmed(count: i32) -> OrderStore {
+        let mut store = OrderStore::new();
+        store.insert_new("client", &order("a", count)).unwrap();
+        assert_eq!(store.on_confirm("a", "order-a").unwrap(), OrderState::Open);
+        store
+    }
+
+    #[test]
+    fn fills_move_an_order_through_partially_filled_to_filled() {
+        let mut store = confirmed(10);
+        assert_eq!(store.on_fill(&fill("t1", "order-a", 3)).unwrap().unwrap().state, OrderState::PartiallyFilled);
+        let order = store.on_fill(&fill("t2", "order-a", 7)).unwrap().unwrap();
+        assert_eq!(order.state, OrderState::Filled);
+        assert_eq!(order.filled_count, 10);
+        assert_eq!(order.remaining_count(), 0);
+        assert_eq!(order.average_fill_price(), Some(40.0));
+        assert!(store.open_orders().is_empty());
+    }
+
+    #[test]
+    fn a_trade_id_is_applied_only_once() {
+        let mut store = confirmed(10);
+        store.on_fill(&fill("t1", "order-a", 3)).unwrap();
+        assert!(store.on_fill(&fill("t1", "order-a", 3)).is_err());
+        assert_eq!(store.get("a").unwrap().filled_count, 3);
+    }
+
+    #[test]
+    fn a_fill_that_beats_its_confirm_is_held_until_the_order_id_is_known() {
+        let mut store = OrderStore::new();
+        store.insert_new("client", &order("a", 10)).unwrap();
+        assert!(store.on_fill(&fill("t1", "order-a", 4)).unwrap().is_none());
+        assert!(store.on_fill(&fill("t1", "order-a", 4)).is_err(), "a held fill is still a duplicate");
+        assert!(store.take_held_fills("order-a").is_empty(), "nothing is handed back before the confirm");
+
+        store.on_confirm("a", "order-a").unwrap();
+        let held = store.take_held_fills("order-a");
+        assert_eq!(held.len(), 1);
+        assert_eq!(store.on_fill(&held[0]).unwrap().unwrap().state, OrderState::PartiallyFilled);
+        assert!(store.take_held_fills("order-a").is_empty());
+    }
+
+    #[test]
+    fn fills_racing_a_cancel_are_applied() {
+        let mut store = confirmed(10);
+        assert_eq!(store.on_cancel_request("a").unwrap(), OrderState::PendingCancel);
+        assert_eq!(store.on_fill(&fill("t1", "order-a", 4)).unwrap().unwrap().state, OrderState::PendingCancel);
+        assert_eq!(store.on_cancel_confirm("a").unwrap(), OrderState::Cancelled);
+
+        // the rest traded before the cancel took effect
+        assert_eq!(store.on_fill(&fill("t2", "order-a", 6)).unwrap().unwrap().state, OrderState::Filled);
+    }
+
+    #[test]
+    fn a_refused_cancel_leaves_the_order_working() {
+        let mut store = confirmed(10);
+        store.insert_new("client", &order("b", 10)).unwrap();
+        store.on_confirm("b", "order-b").unwrap();
+        store.on_fill(&fill("t1", "order-b", 4)).unwrap();
+        store.on_cancel_request("a").unwrap();
+        store.on_cancel_request("b").unwrap();
+
+        assert_eq!(store.on_cancel_reject("a").unwrap(), OrderState::Open);
+        assert_eq!(store.on_cancel_reject("b").unwrap(), OrderState::PartiallyFilled);
+        assert_eq!(store.open_orders_for("client").len(), 2);
+
+        // a cancel refused because the order had already filled leaves it filled
+        store.on_cancel_request("a").unwrap();
+        store.on_fill(&fill("t2", "order-a", 10)).unwrap();
+        assert_eq!(store.on_cancel_reject("a").unwrap(), OrderState::Filled);
+    }
+
+    #[test]
+    fn transitions_the_lifecycle_does_not_allow_are_refused() {
+        let mut store = confirmed(10);
+        assert!(store.on_reject("a").is_err());
+        store.on_fill(&fill("t1", "order-a", 10)).unwrap();
+        assert!(store.on_cancel_request("a").is_err());
+        assert!(store.on_expire("a").is_err());
+        assert_eq!(store.get("a").unwrap().state, OrderState::Filled);
+        assert!(store.on_confirm("unknown", "order-x").is_err());
+    }
+
+    #[test]
+    fn a_rejected_order_is_closed() {
+        let mut store = OrderStore::new();
+        store.insert_new("client", &order("a", 10)).unwrap();
+        assert!(store.insert_new("client", &order("a", 10)).is_err());
+        assert_eq!(store.on_reject("a").unwrap(), OrderState::Rejected);
+        assert!(store.open_orders_for("client").is_empty());
+    }
+
+    #[test]
+    fn an_order_whose_client_disconnected_before_its_confirm_is_cancelled_once_confirmed() {
+        let mut store = OrderStore::new();
+        store.insert_new("client", &order("a", 10)).unwrap();
+        assert_eq!(store.cancel_on_confirm("a").unwrap(), OrderState::PendingNew);
+        assert!(store.take_cancel_on_confirm("a").is_none(), "there is no order id to cancel by yet");
+
+        assert_eq!(store.on_confirm("a", "order-a").unwrap(), OrderState::Open);
+        let cancel = store.take_cancel_on_confirm("a").unwrap();
+        assert_eq!(cancel.order_id, "order-a");
+        assert_eq!(cancel.client_order_id, "a");
+        assert_eq!(store.get("a").unwrap().state, OrderState::PendingCancel);
+        assert!(store.take_cancel_on_confirm("a").is_none());
+    }
+
+    #[test]
+    fn an_order_whose_client_disconnected_before_its_reject_is_rejected() {
+        let mut store = OrderStore::new();
+        store.insert_new("client", &order("a", 10)).unwrap();
+        store.cancel_on_confirm("a").unwrap();
+        assert_eq!(store.on_reject("a").unwrap(), OrderState::Rejected);
+        assert!(store.take_cancel_on_confirm("a").is_none());
+    }
+
+    #[test]
+    fn an_unconfirmed_order_cannot_be_cancelled() {
+        let mut store = OrderStore::new();
+        store.insert_new("client", &order("a", 10)).unwrap();
+        assert!(store.on_cancel_request("a").is_err());
+        assert_eq!(store.on_reject("a").unwrap(), OrderState::Rejected);
+    }
+
+    #[test]
+    fn order_updates_acknowledge_amend_and_close_orders() {
+        let mut store = OrderStore::new();
+        store.insert_new("client", &order("a", 10)).unwrap();
+        let acknowledged = store.on_order_update(&update("a", "order-a", OrderState::Open, 0, 10)).unwrap().unwrap();
+        assert_eq!(acknowledged.state, OrderState::Open);
+        assert_eq!(store.get_by_order_id("order-a").unwrap().client_order_id, "a");
+
+        assert_eq!(store.on_order_update(&update("a", "order-a", OrderState::Open, 0, 6)).unwrap().unwrap().count, 6);
+        assert!(store.on_order_update(&update("a", "order-a", OrderState::Open, 0, 6)).unwrap().is_none(), "nothing changed");
+
+        // fills are left to the fill feed
+        assert!(store.on_order_update(&update("a", "order-a", OrderState::PartiallyFilled, 2, 4)).unwrap().is_none());
+        assert_eq!(store.get("a").unwrap().state, OrderState::Open);
+
+        assert_eq!(store.on_order_update(&update("a", "order-a", OrderState::Cancelled, 2, 0)).unwrap().unwrap().state, OrderState::Cancelled);
+    }
+
+    #[test]
+    fn an_order_can_be_cancelled_or_expire_before_its_confirm() {
+        let mut store = OrderStore::new();
+        store.insert_new("client", &order("a", 10)).unwrap();
+        store.insert_new("client", &order("b", 10)).unwrap();
+        assert_eq!(store.on_order_update(&update("a", "order-a", OrderState::Cancelled, 0, 0)).unwrap().unwrap().state, OrderState::Cancelled);
+        assert_eq!(store.on_order_update(&update("b", "order-b", OrderState::Expired, 0, 0)).unwrap().unwrap().state, OrderState::Expired);
+
+        // the confirm arriving late leaves them closed
+        assert_eq!(store.on_confirm("a", "order-a").unwrap(), OrderState::Cancelled);
+        assert_eq!(store.on_confirm("b", "order-b").unwrap(), OrderState::Expired);
+    }
+
+    #[test]
+    fn a_market_closing_expires_its_working_orders() {
+        let mut store = confirmed(10);
+        store.insert_new("client", &order("b", 10)).unwrap();
+        store.on_confirm("b", "order-b").unwrap();
+        store.on_fill(&fill("t1", "order-b", 10)).unwrap();
+
+        assert_eq!(store.on_market_close("TICKER").unwrap(), vec!["a".to_string()]);
+        assert_eq!(store.get("a").unwrap().state, OrderState::Expired);
+        assert_eq!(store.get("b").unwrap().state, OrderState::Filled);
+    }
+
+    #[test]
+    fn adopted_orders_keep_their_exchange_counts_and_reconcile_through_the_lifecycle() {
+        let mut store = OrderStore::new();
+        store.adopt("client", "a", "order-a", "TICKER", Side::Yes, Action::Buy, 10, 4, 160, OrderState::PartiallyFilled);
+        let order = store.get_by_order_id("order-a").unwrap();
+        assert_eq!((order.filled_count, order.remaining_count()), (4, 6));
+        assert_eq!(store.clients_trading("TICKER"), HashSet::from(["client"]));
+
+        // a fill the adopted order already counts is only recorded
+        assert!(store.record_adopted_fill(&fill("t1", "order-a", 4)));
+        assert!(!store.record_adopted_fill(&fill("t1", "order-a", 4)));
+        assert!(store.on_fill(&fill("t1", "order-a", 4)).is_err());
+
+        assert_eq!(store.reconcile_state("a", OrderState::Cancelled).unwrap(), OrderState::Cancelled);
+        assert!(store.reconcile_state("a", OrderState::Open).is_err());
+        assert_eq!(store.get("a").unwrap().state, OrderState::Cancelled);
+    }
+}
@@ -0,0 +1,267 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use kalshi::{Action, OrderType, Side};
+use protocol::messages::OrderStatusMessage;
+use queue_client::queue_data::cancels::{CancelConfirmMessage, CancelOrderMessage, CancelRejectMessage};
+use queue_client::queue_data::fills::Fill;
+use queue_client::queue_data::market_data::BookMessage;
+use queue_client::queue_data::orders::{CreateOrderMessage, OrderConfirmMessage, OrderRejectMessage, OrderState, OrderUpdateMessage};
+use sim_exchange::{MatchingEngine, NewOrder, SimFill, SimOrder};
+
+use crate::constants::PAPER_ORDER_ID_PREFIX;
+use crate::order_store::OrderStore;
+use crate::positions::Positions;
+use crate::{split_client_name, status};
+
+/// A message from the simulated exchange for a paper-trading client, with the client's
+/// own client order id, as the live path would send it.
+pub enum PaperEvent {
+    Confirm(OrderConfirmMessage),
+    Reject(OrderRejectMessage),
+    CancelConfirm(CancelConfirmMessage),
+    CancelReject(CancelRejectMessage),
+    Fill(Fill),
+    Status(OrderStatusMessage)
+}
+
+/// Where paper-trading clients' orders go instead of the order queue: a simulated exchange,
+/// answered in-process, where they trade against each other and against the live book of
+/// each market, as the feed last published it. Their orders and positions are kept in an
+/// order store and positions of their own, so nothing they do reaches Kalshi or shows up in
+/// the live book, fills or P&L. None of it is journaled, so it starts empty on every restart.
+pub struct PaperExchange {
+    engine: Mutex<MatchingEngine>,
+    pub order_store: Arc<Mutex<OrderStore>>,
+    pub positions: Arc<Mutex<Positions>>
+}
+
+impl PaperExchange {
+
+    pub fn new() -> Self {
+        PaperExchange {
+            engine: Mutex::new(MatchingEngine::new(PAPER_ORDER_ID_PREFIX)),
+            order_store: Arc::new(Mutex::new(OrderStore::new())),
+            positions: Arc::new(Mutex::new(Positions::new()))
+        }
+    }
+
+    /// Place an order already in the paper order store, returning what each client affected
+    /// should be told: the owner's confirm or reject, then the fills on both sides of every
+    /// trade, and the status of an order the exchange finished without filling.
+    pub async fn submit(&self, order: CreateOrderMessage) -> Vec<(String, PaperEvent)> {
+        let mut events = self.expire_due().await;
+        let client_order_id = order.client_order_id.clone();
+        let new_order = NewOrder {
+            client_order_id: order.client_order_id,
+            ticker: order.ticker,
+            side: to_sim_side(&order.side),
+            action: to_sim_action(&order.action),
+            order_type: match order.input_type {
+                OrderType::Limit => sim_exchange::OrderType::Limit,
+                OrderType::Market => sim_exchange::OrderType::Market
+            },
+            count: order.count,
+            yes_price: order.yes_price,
+            no_price: order.no_price,
+            expiration_ts: order.expiration_ts
+        };
+
+        let execution = match self.engine.lock().await.submit(new_order, now()) {
+            Ok(execution) => execution,
+            Err(e) => {
+                if let Err(e) = self.order_store.lock().await.on_reject(&client_order_id) {
+                    warn!("{:?}", e);
+                }
+                events.extend(owned_by(&client_order_id, |client_order_id| PaperEvent::Reject(OrderRejectMessage::new(client_order_id, e.to_string()))));
+                return events;
+            }
+        };
+
+        if let Err(e) = self.order_store.lock().await.on_confirm(&client_order_id, &execution.order.order_id) {
+            warn!("{:?}", e);
+        }
+        events.extend(owned_by(&client_order_id, |client_order_id| {
+            PaperEvent::Confirm(OrderConfirmMessage::new(execution.order.order_id.clone(), Some(client_order_id)))
+        }));
+        events.extend(self.apply_fills(execution.fills).await);
+        events.extend(self.finish_unfilled(&execution.order).await);
+        events
+    }
+
+    /// Cancel a paper order, returning the cancel confirm or reject for its owner.
+    pub async fn cancel(&self, cancel: CancelOrderMessage) -> Vec<(String, PaperEvent)> {
+        let mut events = self.expire_due().await;
+        if let Err(e) = self.engine.lock().await.cancel(&cancel.order_id, now()) {
+            // an order that filled or expired before the cancel arrived has already been reported, and stays closed
+            warn!("Could not cancel paper order {:?}: {}", cancel.client_order_id, e);
+            if let Err(e) = self.order_store.lock().await.on_cancel_reject(&cancel.client_order_id) {
+                warn!("{:?}", e);
+            }
+            events.extend(owned_by(&cancel.client_order_id, |client_order_id| {
+                PaperEvent::CancelReject(CancelRejectMessage { order_id: cancel.order_id.clone(), client_order_id, reason: e.to_string() })
+            }));
+            return events;
+        }
+        if let Err(e) = self.order_store.lock().await.on_cancel_confirm(&cancel.client_order_id) {
+            warn!("{:?}", e);
+        }
+        events.extend(owned_by(&cancel.client_order_id, |client_order_id| {
+            PaperEvent::CancelConfirm(CancelConfirmMessage { order_id: cancel.order_id.clone(), client_order_id })
+        }));
+        events
+    }
+
+    /// Expire every paper order whose expiration time has passed, returning their statuses for their owners.
+    pub async fn expire_due(&self) -> Vec<(String, PaperEvent)> {
+        let expired = self.engine.lock().await.expire(now());
+        if expired.is_empty() {
+            return Vec::new();
+        }
+        let mut order_store = self.order_store.lock().await;
+        expired.iter()
+            .filter_map(|order| {
+                if let Err(e) = order_store.on_expire(&order.client_order_id) {
+                    warn!("{:?}", e);
+                }
+                let stored = order_store.get(&order.client_order_id)?;
+                owned_by(&order.client_order_id, |client_order_id| PaperEvent::Status(status::from_store(stored, client_order_id)))
+            })
+            .collect()
+    }
+
+    /// The live book of a market changed. Paper orders trade against its liquidity from now
+    /// on, and resting paper orders it crosses fill straight away; returns those fills for their owners.
+    pub async fn on_book(&self, book: &BookMessage) -> Vec<(String, PaperEvent)> {
+        let mut events = self.expire_due().await;
+        let bids = book.yes_bids.iter().map(|level| (level.price as i64, level.quantity)).collect();
+        // a NO bid at p is a YES ask at 100 - p
+        let asks = book.no_bids.iter().map(|level| (100 - level.price as i64, level.quantity)).collect();
+        let fills = self.engine.lock().await.set_live_book(&book.market_ticker, bids, asks, now());
+        events.extend(self.apply_fills(fills).await);
+        events
+    }
+
+    /// A market stopped trading, which takes every paper order in it off the simulated book.
+    /// Returns the expired orders' statuses, for their owners.
+    pub async fn on_market_close(&self, ticker: &str) -> Vec<(String, PaperEvent)> {
+        self.engine.lock().await.close_market(ticker);
+        let mut order_store = self.order_store.lock().await;
+        let expired = match order_store.on_market_close(ticker) {
+            Ok(expired) => expired,
+            Err(e) => {
+                warn!("{:?}", e);
+                return Vec::new();
+            }
+        };
+        expired.iter()
+            .filter_map(|namespaced_client_order_id| {
+                let order = order_store.get(namespaced_client_order_id)?;
+                owned_by(namespaced_client_order_id, |client_order_id| PaperEvent::Status(status::from_store(order, client_order_id)))
+            })
+            .collect()
+    }
+
+    /// Apply the simulated exchange's fills to the paper order store and positions.
+    async fn apply_fills(&self, fills: Vec<SimFill>) -> Vec<(String, PaperEvent)> {
+        let mut order_store = self.order_store.lock().await;
+        let mut positions = self.positions.lock().await;
+        let mut events = Vec::new();
+        for fill in fills {
+            let fill = to_fill(fill);
+            let client_id = match order_store.on_fill(&fill) {
+                Ok(Some(order)) => order.client_id.clone(),
+                Ok(None) => {
+                    warn!("Paper fill {:?} is for unknown order {:?}.", fill.trade_id, fill.order_id);
+                    continue;
+                },
+                Err(e) => {
+                    warn!("Could not apply paper fill {:?}: {:?}", fill.trade_id, e);
+                    continue;
+                }
+            };
+            positions.apply_fill(&client_id, &fill);
+            events.push((client_id, PaperEvent::Fill(fill)));
+        }
+        events
+    }
+
+    /// A market order that could not fill completely has its remainder cancelled by the
+    /// exchange, which the owner hears about as a status, just as Kalshi's order updates report it.
+    async fn finish_unfilled(&self, order: &SimOrder) -> Option<(String, PaperEvent)> {
+        if order.status == sim_exchange::OrderStatus::Resting || order.fill_count() == order.place_count {
+            return None;
+        }
+        let mut order_store = self.order_store.lock().await;
+        let update = OrderUpdateMessage {
+            order_id: order.order_id.clone(),
+            client_order_id: order.client_order_id.clone(),
+            ticker: order.ticker.clone(),
+            state: OrderState::Cancelled,
+            yes_price: order.yes_price as i32,
+            filled_count: order.fill_count(),
+            remaining_count: 0,
+            ts: now()
+        };
+        match order_store.on_order_update(&update) {
+            Ok(Some(stored)) => owned_by(&order.client_order_id, |client_order_id| PaperEvent::Status(status::from_store(stored, client_order_id))),
+            Ok(None) => None,
+            Err(e) => {
+                warn!("{:?}", e);
+                None
+            }
+        }
+    }
+}
+
+/// Address an event to the client that owns a namespaced client order id.
+fn owned_by(namespaced_client_order_id: &str, event: impl FnOnce(String) -> PaperEvent) -> Option<(String, PaperEvent)> {
+    match split_client_name(namespaced_client_order_id) {
+        Ok((client_id, client_order_id)) => Some((client_id, event(client_order_id))),
+        Err(e) => {
+            debug!("Cannot route paper event: {:?}", e);
+            None
+        }
+    }
+}
+
+fn to_fill(fill: SimFill) -> Fill {
+    Fill {
+        no_price: fill.no_price() as i32,
+        trade_id: fill.trade_id,
+        order_id: fill.order_id,
+        market_ticker: fill.ticker,
+        is_taker: fill.is_taker,
+        side: match fill.side {
+            sim_exchange::Side::Yes => Side::Yes,
+            sim_exchange::Side::No => Side::No
+        },
+        yes_price: fill.yes_price as i32,
+        count: fill.count,
+        action: match fill.action {
+            sim_exchange::Action::Buy => Action::Buy,
+            sim_exchange::Action::Sell => Action::Sell
+        },
+        ts: fill.ts
+    }
+}
+
+fn to_sim_side(side: &Side) -> sim_exchange::Side {
+    match side {
+        Side::Yes => sim_exchange::Side::Yes,
+        Side::No => sim_exchange::Side::No
+    }
+}
+
+fn to_sim_action(action: &Action) -> sim_exchange::Action {
+    match action {
+        Action::Buy => sim_exchange::Action::Buy,
+        Action::Sell => sim_exchange::Action::Sell
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
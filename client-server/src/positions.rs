@@ -0,0 +1,258 @@
+use std::collections::{HashMap, HashSet};
+
+use kalshi::{Action, Side};
+use queue_client::queue_data::fills::Fill;
+
+/// A client's position in one market. Quantities are in YES-equivalent contracts,
+/// so holding NO is a negative position, and prices are YES prices in cents.
+#[derive(Debug, Default, Clone)]
+pub struct Position {
+    pub net_contracts: i64,
+    pub average_price: f64,
+    pub realized_pnl: f64
+}
+
+impl Position {
+
+    /// Apply a trade of `quantity` YES-equivalent contracts (negative to sell) at `price`.
+    fn trade(&mut self, quantity: i64, price: f64) {
+        if quantity == 0 {
+            return;
+        }
+        if self.net_contracts == 0 || self.net_contracts.signum() == quantity.signum() {
+            let total = self.net_contracts.abs() + quantity.abs();
+            self.average_price = (self.average_price * self.net_contracts.abs() as f64 + price * quantity.abs() as f64) / total as f64;
+            self.net_contracts += quantity;
+            return;
+        }
+
+        // the trade closes some or all of the position, realizing its P&L
+        let closed = quantity.abs().min(self.net_contracts.abs());
+        self.realized_pnl += closed as f64 * (price - self.average_price) * self.net_contracts.signum() as f64;
+        self.net_contracts += quantity;
+        if self.net_contracts == 0 {
+            self.average_price = 0.0;
+        } else if self.net_contracts.signum() == quantity.signum() {
+            // the trade flipped the position, so what remains was opened at this price
+            self.average_price = price;
+        }
+    }
+
+    /// The P&L of the open position if it were closed at `mark`.
+    pub fn unrealized_pnl(&self, mark: f64) -> f64 {
+        self.net_contracts as f64 * (mark - self.average_price)
+    }
+}
+
+/// Net positions and P&L per client and per ticker, built from the fill stream
+/// and marked to market with the latest YES price seen for each ticker.
+pub struct Positions {
+    positions: HashMap<String, HashMap<String, Position>>,
+    marks: HashMap<String, f64>
+}
+
+impl Positions {
+
+    pub fn new() -> Self {
+        Positions {
+            positions: HashMap::new(),
+            marks: HashMap::new()
+        }
+    }
+
+    /// Apply a fill on one of the client's orders.
+    pub fn apply_fill(&mut self, client_id: &str, fill: &Fill) {
+        let quantity = yes_equivalent_quantity(&fill.side, &fill.action, fill.count as i64);
+        self.positions
+            .entry(client_id.to_string())
+            .or_default()
+            .entry(fill.market_ticker.clone())
+            .or_default()
+            .trade(quantity, fill.yes_price as f64);
+        self.mark(&fill.market_ticker, fill.yes_price as f64);
+    }
+
+    /// Record the latest YES price, in cents, for a ticker.
+    pub fn mark(&mut self, ticker: &str, yes_price: f64) {
+        self.marks.insert(ticker.to_string(), yes_price);
+    }
+
+    /// Close every client's position in a settled market at its payout: 100 cents a YES contract
+    /// if YES won and nothing if NO won, which finalizes realized P&L. A market settled without
+    /// a YES or NO result refunds its contracts, so they close at their average price.
+    pub fn settle(&mut self, ticker: &str, result: Option<&Side>) {
+        let payout = match result {
+            Some(Side::Yes) => Some(100.0),
+            Some(Side::No) => Some(0.0),
+            None => None
+        };
+        for position in self.positions.values_mut().filter_map(|positions| positions.get_mut(ticker)) {
+            let price = payout.unwrap_or(position.average_price);
+            position.trade(-position.net_contracts, price);
+        }
+        if let Some(payout) = payout {
+            self.mark(ticker, payout);
+        }
+    }
+
+    /// Set the client's net position in a ticker to what reconciliation found it to be. The
+    /// difference is booked at the ticker's mark if there is one, or else at the position's
+    /// average price, since the prices it actually traded at are not known.
+    pub fn correct(&mut self, client_id: &str, ticker: &str, net_contracts: i64) {
+        let mark = self.get_mark(ticker);
+        let position = self.positions
+            .entry(client_id.to_string())
+            .or_default()
+            .entry(ticker.to_string())
+            .or_default();
+        let price = mark.unwrap_or(position.average_price);
+        position.trade(net_contracts - position.net_contracts, price);
+    }
+
+    /// Every ticker any client holds a position in.
+    pub fn tickers(&self) -> HashSet<String> {
+        self.positions.values()
+            .flat_map(|positions| positions.iter())
+            .filter(|(_, position)| position.net_contracts != 0)
+            .map(|(ticker, _)| ticker.clone())
+            .collect()
+    }
+
+    pub fn get_mark(&self, ticker: &str) -> Option<f64> {
+        self.marks.get(ticker).copied()
+    }
+
+    /// The client's net position in a ticker, in YES-equivalent contracts.
+    pub fn net_contracts(&self, client_id: &str, ticker: &str) -> i64 {
+        self.get(client_id, ticker).map_or(0, |position| position.net_contracts)
+    }
+
+    /// The net position in a ticker summed across every client.
+    pub fn total_net_contracts(&self, ticker: &str) -> i64 {
+        self.positions.values()
+            .filter_map(|positions| positions.get(ticker))
+            .map(|position| position.net_contracts)
+            .sum()
+    }
+
+    pub fn get(&self, client_id: &str, ticker: &str) -> Option<&Position> {
+        self.positions.get(client_id).and_then(|positions| positions.get(ticker))
+    }
+
+    /// All of the client's positions, keyed by ticker.
+    pub fn for_client(&self, client_id: &str) -> Vec<(&String, &Position)> {
+        match self.positions.get(client_id) {
+            None => Vec::new(),
+            Some(positions) => positions.iter().collect()
+        }
+    }
+}
+
+/// Convert a trade on either side of a binary market into YES-equivalent contracts:
+/// buying NO is selling YES, and selling NO is buying YES.
+pub fn yes_equivalent_quantity(side: &Side, action: &Action, count: i64) -> i64 {
+    match (side, action) {
+        (Side::Yes, Action::Buy) | (Side::No, Action::Sell) => count,
+        (Side::Yes, Action::Sell) | (Side::No, Action::Buy) => -count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(side: Side, action: Action, yes_price: i32, count: i32) -> Fill {
+        Fill {
+            trade_id: String::new(),
+            order_id: String::new(),
+            market_ticker: "TICKER".to_string(),
+            is_taker: true,
+            side,
+            yes_price,
+            no_price: 100 - yes_price,
+            count,
+            action,
+            ts: 0
+        }
+    }
+
+    #[test]
+    fn closing_part_of_a_position_realizes_its_pnl() {
+        let mut positions = Positions::new();
+        positions.apply_fill("client", &fill(Side::Yes, Action::Buy, 40, 10));
+        positions.apply_fill("client", &fill(Side::Yes, Action::Sell, 50, 4));
+
+        let position = positions.get("client", "TICKER").unwrap();
+        assert_eq!(position.net_contracts, 6);
+        assert_eq!(position.average_price, 40.0);
+        assert_eq!(position.realized_pnl, 40.0);
+        assert_eq!(position.unrealized_pnl(positions.get_mark("TICKER").unwrap()), 60.0);
+    }
+
+    #[test]
+    fn no_contracts_count_as_negative_yes_contracts() {
+        let mut positions = Positions::new();
+        positions.apply_fill("client", &fill(Side::No, Action::Buy, 70, 5));
+        assert_eq!(positions.net_contracts("client", "TICKER"), -5);
+        assert_eq!(positions.get("client", "TICKER").unwrap().average_price, 70.0);
+
+        positions.apply_fill("client", &fill(Side::No, Action::Sell, 60, 5));
+        let position = positions.get("client", "TICKER").unwrap();
+        assert_eq!(position.net_contracts, 0);
+        assert_eq!(position.realized_pnl, 50.0);
+    }
+
+    #[test]
+    fn a_trade_through_flat_opens_the_rest_at_its_price() {
+        let mut positions = Positions::new();
+        positions.apply_fill("client", &fill(Side::Yes, Action::Buy, 40, 5));
+        positions.apply_fill("client", &fill(Side::Yes, Action::Sell, 60, 8));
+
+        let position = positions.get("client", "TICKER").unwrap();
+        assert_eq!(position.net_contracts, -3);
+        assert_eq!(position.average_price, 60.0);
+        assert_eq!(position.realized_pnl, 100.0);
+    }
+
+    #[test]
+    fn settlement_closes_positions_at_the_payout() {
+        let mut positions = Positions::new();
+        positions.apply_fill("long", &fill(Side::Yes, Action::Buy, 40, 10));
+        positions.apply_fill("short", &fill(Side::No, Action::Buy, 40, 10));
+        assert_eq!(positions.total_net_contracts("TICKER"), 0);
+        assert_eq!(positions.tickers(), HashSet::from(["TICKER".to_string()]));
+
+        positions.settle("TICKER", Some(&Side::Yes));
+        assert_eq!(positions.get("long", "TICKER").unwrap().realized_pnl, 600.0);
+        assert_eq!(positions.get("short", "TICKER").unwrap().realized_pnl, -600.0);
+        assert!(positions.tickers().is_empty());
+        assert_eq!(positions.get_mark("TICKER"), Some(100.0));
+    }
+
+    #[test]
+    fn a_market_settled_without_a_result_refunds_its_contracts() {
+        let mut positions = Positions::new();
+        positions.apply_fill("client", &fill(Side::Yes, Action::Buy, 40, 10));
+        positions.settle("TICKER", None);
+        let position = positions.get("client", "TICKER").unwrap();
+        assert_eq!((position.net_contracts, position.realized_pnl), (0, 0.0));
+    }
+
+    #[test]
+    fn a_corrected_position_is_booked_at_the_mark() {
+        let mut positions = Positions::new();
+        positions.apply_fill("client", &fill(Side::Yes, Action::Buy, 40, 10));
+        positions.mark("TICKER", 50.0);
+        positions.correct("client", "TICKER", 4);
+        let position = positions.get("client", "TICKER").unwrap();
+        assert_eq!((position.net_contracts, position.realized_pnl), (4, 60.0));
+
+        // with no mark the difference is booked at the average price, realizing nothing
+        let mut positions = Positions::new();
+        positions.apply_fill("client", &fill(Side::Yes, Action::Buy, 40, 10));
+        positions.marks.clear();
+        positions.correct("client", "TICKER", 12);
+        let position = positions.get("client", "TICKER").unwrap();
+        assert_eq!((position.net_contracts, position.average_price, position.realized_pnl), (12, 40.0, 0.0));
+    }
+}
@@ -0,0 +1,203 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+use kalshi_rest::{ExchangeOrder, FillsQuery, KalshiRestClient, OrdersQuery};
+use queue_client::queue_data::fills::Fill;
+
+use crate::constants::{RECONCILE_LOOKBACK, RECONCILE_PAGE_SIZE, UNATTRIBUTED_POSITION_CLIENT_ID};
+use crate::order_store::OrderStore;
+use crate::positions::Positions;
+use crate::status::exchange_state;
+use crate::split_client_name;
+
+/// The differences found between the OMS's state and Kalshi's.
+#[derive(Debug, Default)]
+pub struct ReconcileReport {
+    pub orders_adopted: usize,
+    pub order_state_mismatches: usize,
+    pub orders_missing_on_exchange: usize,
+    pub unattributed_orders: usize,
+    pub fills_applied: usize,
+    pub unattributed_fills: usize,
+    pub position_mismatches: usize
+}
+
+impl ReconcileReport {
+    pub fn is_clean(&self) -> bool {
+        self.orders_adopted == 0
+            && self.order_state_mismatches == 0
+            && self.orders_missing_on_exchange == 0
+            && self.unattributed_orders == 0
+            && self.unattributed_fills == 0
+            && self.position_mismatches == 0
+    }
+}
+
+/// Rebuild the order store and positions from Kalshi's resting orders, recent orders,
+/// recent fills and portfolio positions, reporting everything that disagrees with
+/// what the OMS already knew.
+pub async fn reconcile(exchange_client: &KalshiRestClient, order_store: &mut OrderStore, positions: &mut Positions) -> Result<ReconcileReport> {
+
+    let mut report = ReconcileReport::default();
+    let min_ts = (SystemTime::now().duration_since(UNIX_EPOCH)? - RECONCILE_LOOKBACK).as_secs() as i64;
+
+    // 1. Orders: everything still resting, plus everything placed within the lookback window
+
+    let mut exchange_orders = HashMap::<String, ExchangeOrder>::new();
+    for order in get_all_orders(exchange_client, None, Some("resting".to_string())).await? {
+        exchange_orders.insert(order.order_id.clone(), order);
+    }
+    for order in get_all_orders(exchange_client, Some(min_ts), None).await? {
+        exchange_orders.insert(order.order_id.clone(), order);
+    }
+
+    let mut adopted_order_ids = HashSet::new();
+    let mut reconciled_states = Vec::new();
+    for order in exchange_orders.values() {
+        let client_id = match split_client_name(&order.client_order_id) {
+            Ok((client_id, _)) => client_id,
+            Err(_) => {
+                warn!("Order {:?} on the exchange was not placed through the OMS.", order.order_id);
+                report.unattributed_orders += 1;
+                continue;
+            }
+        };
+        let state = match exchange_state(order) {
+            Some(state) => state,
+            None => {
+                warn!("Order {:?} on the exchange has unknown status {:?}.", order.order_id, order.status);
+                continue;
+            }
+        };
+        match order_store.get(&order.client_order_id) {
+            None => {
+                info!("Adopting order {:?} ({:?}) for client {:?} from the exchange.", order.client_order_id, state, client_id);
+                // the order's own counts, since its fills may be older than the lookback window
+                let filled_count = order.filled_count();
+                let count = filled_count + order.remaining_count;
+                order_store.adopt(&client_id, &order.client_order_id, &order.order_id, &order.ticker, order.side.clone(), order.action.clone(),
+                    count, filled_count, order.price() * filled_count as i64, state);
+                adopted_order_ids.insert(order.order_id.clone());
+                report.orders_adopted += 1;
+            },
+            Some(known) if known.state != state => {
+                warn!("Order {:?} is {:?} in the OMS but {:?} on the exchange.", order.client_order_id, known.state, state);
+                report.order_state_mismatches += 1;
+                reconciled_states.push((order.client_order_id.clone(), state));
+            },
+            Some(_) => {}
+        }
+    }
+
+    for order in order_store.open_orders() {
+        if !exchange_orders.values().any(|exchange_order| exchange_order.client_order_id == order.client_order_id) {
+            warn!("Order {:?} is {:?} in the OMS but unknown to the exchange.", order.client_order_id, order.state);
+            report.orders_missing_on_exchange += 1;
+        }
+    }
+
+    // 2. Fills: apply every fill in the lookback window the OMS has not yet seen
+
+    let mut cursor = None;
+    loop {
+        let (next_cursor, fills) = exchange_client
+            .get_fills(&FillsQuery { min_ts: Some(min_ts), limit: Some(RECONCILE_PAGE_SIZE), cursor, ..Default::default() })
+            .await?;
+
+        for fill in fills {
+            let fill = Fill::try_from(fill)?;
+            let client_id = match order_store.get_by_order_id(&fill.order_id) {
+                None => {
+                    report.unattributed_fills += 1;
+                    continue;
+                },
+                Some(order) => order.client_id.clone()
+            };
+            // an adopted order already counts its fills, which only its client's position still needs
+            let applied = if adopted_order_ids.contains(&fill.order_id) {
+                order_store.record_adopted_fill(&fill)
+            } else {
+                matches!(order_store.on_fill(&fill), Ok(Some(_)))
+            };
+            if applied {
+                positions.apply_fill(&client_id, &fill);
+                report.fills_applied += 1;
+            }
+        }
+
+        cursor = match next_cursor {
+            Some(next_cursor) if !next_cursor.is_empty() => Some(next_cursor),
+            _ => break
+        };
+    }
+
+    // with the fills applied, the orders the OMS already knew can be moved to the exchange's states
+    for (client_order_id, state) in reconciled_states {
+        if let Err(e) = order_store.reconcile_state(&client_order_id, state) {
+            warn!("Could not reconcile order {:?}: {:?}", client_order_id, e);
+        }
+    }
+
+    // 3. Positions: the account's position in each market should equal the sum of the clients',
+    // and where it does not the difference is booked to the client trading that market
+
+    let mut exchange_positions = HashMap::<String, i64>::new();
+    let mut cursor = None;
+    loop {
+        let (next_cursor, market_positions) = exchange_client
+            .get_positions(Some(RECONCILE_PAGE_SIZE), cursor)
+            .await?;
+
+        for market_position in market_positions {
+            exchange_positions.insert(market_position.ticker, market_position.position as i64);
+        }
+
+        cursor = match next_cursor {
+            Some(next_cursor) if !next_cursor.is_empty() => Some(next_cursor),
+            _ => break
+        };
+    }
+
+    // a market the OMS holds but the exchange does not list is flat on the exchange
+    for ticker in positions.tickers() {
+        exchange_positions.entry(ticker).or_insert(0);
+    }
+
+    for (ticker, exchange_position) in exchange_positions {
+        let oms_position = positions.total_net_contracts(&ticker);
+        if oms_position == exchange_position {
+            continue;
+        }
+        let clients = order_store.clients_trading(&ticker);
+        let client_id = match clients.iter().next() {
+            Some(client_id) if clients.len() == 1 => client_id.to_string(),
+            _ => UNATTRIBUTED_POSITION_CLIENT_ID.to_string()
+        };
+        let corrected = positions.net_contracts(&client_id, &ticker) + exchange_position - oms_position;
+        warn!("Position in {:?} is {} in the OMS but {} on the exchange; setting {:?}'s position to {}.",
+            ticker, oms_position, exchange_position, client_id, corrected);
+        positions.correct(&client_id, &ticker, corrected);
+        report.position_mismatches += 1;
+    }
+
+    Ok(report)
+}
+
+/// Page through every order on the account matching the given filters.
+async fn get_all_orders(exchange_client: &KalshiRestClient, min_ts: Option<i64>, status: Option<String>) -> Result<Vec<ExchangeOrder>> {
+    let mut all_orders = Vec::new();
+    let mut cursor = None;
+    loop {
+        let (next_cursor, orders) = exchange_client
+            .get_orders(&OrdersQuery { min_ts, status: status.clone(), limit: Some(RECONCILE_PAGE_SIZE), cursor, ..Default::default() })
+            .await?;
+        all_orders.extend(orders);
+
+        cursor = match next_cursor {
+            Some(next_cursor) if !next_cursor.is_empty() => Some(next_cursor),
+            _ => return Ok(all_orders)
+        };
+    }
+}
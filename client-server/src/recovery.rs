@@ -0,0 +1,130 @@
+use anyhow::Result;
+use std::path::Path;
+use tracing::{debug, info};
+
+use journal::{Record, RecordKind};
+use queue_client::queue_data::cancels::{CancelConfirmMessage, CancelOrderMessage, CancelRejectMessage};
+use queue_client::queue_data::data_core::QueueData;
+use queue_client::queue_data::fills::FillMessage;
+use queue_client::queue_data::market_data::{MarketLifecycleMessage, MarketStatus};
+use queue_client::queue_data::orders::{CreateOrderMessage, OrderConfirmMessage, OrderRejectMessage, OrderUpdateMessage};
+use queue_client::queue_names::QueueNames;
+
+use crate::markets::Markets;
+use crate::order_store::OrderStore;
+use crate::positions::Positions;
+use crate::split_client_name;
+
+/// What replaying the journal rebuilt.
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    pub records_applied: usize,
+    pub records_skipped: usize,
+    pub open_orders: usize
+}
+
+/// Rebuild the order store, its exchange order id index, positions and market statuses
+/// by replaying every message the client-server published or consumed, in journal order.
+/// Risk usage follows, as it is worked out from positions and open orders. Client frames
+/// are not replayed, because an order only counts once it has been published for the gateway.
+pub fn replay(journal_dir: &Path, queue_names: &QueueNames, order_store: &mut OrderStore, positions: &mut Positions, markets: &mut Markets) -> Result<ReplayReport> {
+    let records = journal::reader::read_all(journal_dir)?;
+    info!("Replaying {} journal records", records.len());
+    let mut report = ReplayReport::default();
+
+    for record in records.iter() {
+        let applied = match record.kind {
+            RecordKind::QueuePublish => replay_publish(record, queue_names, order_store),
+            RecordKind::QueueConsume => replay_consume(record, queue_names, order_store, positions, markets),
+            _ => continue
+        };
+        match applied {
+            Ok(()) => report.records_applied += 1,
+            Err(e) => {
+                debug!("Skipping journal record {} from {:?}: {:?}", record.seq, record.source, e);
+                report.records_skipped += 1;
+            }
+        }
+    }
+
+    report.open_orders = order_store.open_orders().len();
+    Ok(report)
+}
+
+/// Replay an order or cancel the client-server sent on to the gateway.
+fn replay_publish(record: &Record, queue_names: &QueueNames, order_store: &mut OrderStore) -> Result<()> {
+    if is_queue::<CreateOrderMessage>(record, queue_names) {
+        let order: CreateOrderMessage = record.decode()?;
+        let (client_id, _) = split_client_name(&order.client_order_id)?;
+        order_store.insert_new(&client_id, &order)
+    } else if is_queue::<CancelOrderMessage>(record, queue_names) {
+        let cancel: CancelOrderMessage = record.decode()?;
+        order_store.on_cancel_request(&cancel.client_order_id).map(|_| ())
+    } else {
+        Ok(())
+    }
+}
+
+/// Replay an answer from the exchange side, applying it just as the consumer that took it did.
+fn replay_consume(record: &Record, queue_names: &QueueNames, order_store: &mut OrderStore, positions: &mut Positions, markets: &mut Markets) -> Result<()> {
+    if is_queue::<OrderConfirmMessage>(record, queue_names) {
+        let confirm: OrderConfirmMessage = record.decode()?;
+        match confirm.client_order_id {
+            None => Err(anyhow::anyhow!("Order confirmation for {:?} has no client order id", confirm.order_id)),
+            Some(ref client_order_id) => {
+                order_store.on_confirm(client_order_id, &confirm.order_id)?;
+                apply_held_fills(&confirm.order_id, order_store, positions);
+                Ok(())
+            }
+        }
+    } else if is_queue::<OrderRejectMessage>(record, queue_names) {
+        let reject: OrderRejectMessage = record.decode()?;
+        order_store.on_reject(&reject.client_order_id).map(|_| ())
+    } else if is_queue::<CancelConfirmMessage>(record, queue_names) {
+        let cancel_confirm: CancelConfirmMessage = record.decode()?;
+        order_store.on_cancel_confirm(&cancel_confirm.client_order_id).map(|_| ())
+    } else if is_queue::<CancelRejectMessage>(record, queue_names) {
+        let cancel_reject: CancelRejectMessage = record.decode()?;
+        order_store.on_cancel_reject(&cancel_reject.client_order_id).map(|_| ())
+    } else if is_queue::<OrderUpdateMessage>(record, queue_names) {
+        let update: OrderUpdateMessage = record.decode()?;
+        order_store.on_order_update(&update)?;
+        apply_held_fills(&update.order_id, order_store, positions);
+        Ok(())
+    } else if is_queue::<FillMessage>(record, queue_names) {
+        let fill = record.decode::<FillMessage>()?.msg;
+        // a fill that beat its confirm is held, and applied when the confirm is replayed
+        if let Some(order) = order_store.on_fill(&fill)? {
+            let client_id = order.client_id.clone();
+            positions.apply_fill(&client_id, &fill);
+        }
+        Ok(())
+    } else if is_queue::<MarketLifecycleMessage>(record, queue_names) {
+        let update: MarketLifecycleMessage = record.decode()?;
+        let (ticker, status, result) = (update.market_ticker.clone(), update.status, update.result.clone());
+        markets.on_lifecycle(update);
+        if status == MarketStatus::Settled {
+            positions.settle(&ticker, result.as_ref());
+        }
+        if status != MarketStatus::Open {
+            order_store.on_market_close(&ticker)?;
+        }
+        Ok(())
+    } else {
+        Ok(())
+    }
+}
+
+/// Apply the fills held for an order id the order store has just learned, as the live consumers do.
+fn apply_held_fills(order_id: &str, order_store: &mut OrderStore, positions: &mut Positions) {
+    for fill in order_store.take_held_fills(order_id) {
+        if let Ok(Some(order)) = order_store.on_fill(&fill) {
+            let client_id = order.client_id.clone();
+            positions.apply_fill(&client_id, &fill);
+        }
+    }
+}
+
+fn is_queue<T: QueueData>(record: &Record, queue_names: &QueueNames) -> bool {
+    record.source == queue_names.name(&T::class())
+}
@@ -0,0 +1,90 @@
+use anyhow::Result;
+
+use oms_config::RiskConfig;
+use queue_client::queue_data::orders::CreateOrderMessage;
+
+use crate::order_store::OrderStore;
+use crate::positions::{Positions, yes_equivalent_quantity};
+
+/// Refuse an order that, filled completely on top of the client's position and every
+/// other order it has working in the same direction, would take the client's position
+/// in the ticker past the position limit.
+pub fn check_order(client_id: &str, order: &CreateOrderMessage, positions: &Positions, order_store: &OrderStore, limits: &RiskConfig) -> Result<()> {
+    let quantity = yes_equivalent_quantity(&order.side, &order.action, order.count as i64);
+
+    let working: i64 = order_store.open_orders_for(client_id).into_iter()
+        .filter(|open_order| open_order.ticker == order.ticker)
+        .map(|open_order| yes_equivalent_quantity(&open_order.side, &open_order.action, open_order.remaining_count() as i64))
+        .filter(|working_quantity| working_quantity.signum() == quantity.signum())
+        .sum();
+
+    let worst_case = positions.net_contracts(client_id, &order.ticker) + working + quantity;
+    if worst_case.abs() > limits.max_position_per_ticker {
+        return Err(anyhow::anyhow!(
+            "Order could take the position in {} to {} contracts, past the limit of {}", order.ticker, worst_case, limits.max_position_per_ticker));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kalshi::{Action, OrderType, Side};
+    use queue_client::queue_data::fills::Fill;
+
+    fn order(client_order_id: &str, side: Side, action: Action, count: i32) -> CreateOrderMessage {
+        CreateOrderMessage {
+            action,
+            client_order_id: client_order_id.to_string(),
+            count,
+            side,
+            ticker: "TICKER".to_string(),
+            input_type: OrderType::Limit,
+            buy_max_cost: None,
+            expiration_ts: None,
+            no_price: None,
+            sell_position_floor: None,
+            yes_price: Some(50)
+        }
+    }
+
+    /// A client long 6 contracts, with a working order to buy 3 more and one to sell 2.
+    fn setup() -> (Positions, OrderStore) {
+        let mut positions = Positions::new();
+        positions.apply_fill("client", &Fill {
+            trade_id: "t1".to_string(),
+            order_id: "filled".to_string(),
+            market_ticker: "TICKER".to_string(),
+            is_taker: true,
+            side: Side::Yes,
+            yes_price: 50,
+            no_price: 50,
+            count: 6,
+            action: Action::Buy,
+            ts: 0
+        });
+        let mut order_store = OrderStore::new();
+        order_store.insert_new("client", &order("buy", Side::Yes, Action::Buy, 3)).unwrap();
+        order_store.insert_new("client", &order("sell", Side::Yes, Action::Sell, 2)).unwrap();
+        (positions, order_store)
+    }
+
+    #[test]
+    fn orders_count_working_orders_in_the_same_direction() {
+        let (positions, order_store) = setup();
+        let limits = RiskConfig { max_position_per_ticker: 10 };
+        assert!(check_order("client", &order("ok", Side::Yes, Action::Buy, 1), &positions, &order_store, &limits).is_ok());
+        assert!(check_order("client", &order("too-big", Side::Yes, Action::Buy, 2), &positions, &order_store, &limits).is_err());
+        // buying NO sells YES, so it counts with the working sell towards the limit on the short side
+        assert!(check_order("client", &order("no", Side::No, Action::Buy, 14), &positions, &order_store, &limits).is_ok());
+        assert!(check_order("client", &order("no", Side::No, Action::Buy, 15), &positions, &order_store, &limits).is_err());
+    }
+
+    #[test]
+    fn other_clients_do_not_use_up_the_limit() {
+        let (positions, order_store) = setup();
+        let limits = RiskConfig { max_position_per_ticker: 10 };
+        assert!(check_order("other", &order("ok", Side::Yes, Action::Buy, 10), &positions, &order_store, &limits).is_ok());
+        assert!(check_order("other", &order("too-big", Side::Yes, Action::Buy, 11), &positions, &order_store, &limits).is_err());
+    }
+}
@@ -0,0 +1,60 @@
+use anyhow::Result;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use kalshi_rest::{ExchangeOrder, KalshiRestClient, OrdersQuery};
+use protocol::messages::OrderStatusMessage;
+use queue_client::queue_data::orders::OrderState;
+
+use crate::constants::{STATUS_LOOKUP_LOOKBACK, STATUS_LOOKUP_PAGE_SIZE};
+use crate::order_store::Order;
+
+/// Describe an order from the order store to the client that owns it.
+pub fn from_store(order: &Order, client_order_id: String) -> OrderStatusMessage {
+    OrderStatusMessage {
+        client_order_id,
+        order_id: order.order_id.clone(),
+        state: Some(order.state),
+        filled_count: order.filled_count,
+        average_fill_price: order.average_fill_price()
+    }
+}
+
+/// Ask Kalshi about an order the order store does not know, paging through the
+/// account's orders placed within the lookback window for a matching client order id.
+/// Older orders still resting were adopted into the order store at startup.
+pub async fn from_exchange(exchange_client: &KalshiRestClient, namespaced_client_order_id: &str, client_order_id: String) -> Result<OrderStatusMessage> {
+    let min_ts = (SystemTime::now().duration_since(UNIX_EPOCH)? - STATUS_LOOKUP_LOOKBACK).as_secs() as i64;
+    let mut cursor = None;
+    loop {
+        let (next_cursor, orders) = exchange_client
+            .get_orders(&OrdersQuery { min_ts: Some(min_ts), limit: Some(STATUS_LOOKUP_PAGE_SIZE), cursor, ..Default::default() })
+            .await?;
+
+        if let Some(order) = orders.into_iter().find(|order| order.client_order_id == namespaced_client_order_id) {
+            return Ok(OrderStatusMessage {
+                client_order_id,
+                state: exchange_state(&order),
+                filled_count: order.filled_count(),
+                order_id: Some(order.order_id),
+                average_fill_price: None
+            });
+        }
+
+        cursor = match next_cursor {
+            Some(next_cursor) if !next_cursor.is_empty() => Some(next_cursor),
+            _ => return Ok(OrderStatusMessage::unknown(client_order_id))
+        };
+    }
+}
+
+/// Translate an exchange order's status into the OMS's order states, if it is one Kalshi documents.
+pub fn exchange_state(order: &ExchangeOrder) -> Option<OrderState> {
+    match order.status.as_str() {
+        "pending" => Some(OrderState::PendingNew),
+        "resting" if order.filled_count() > 0 => Some(OrderState::PartiallyFilled),
+        "resting" => Some(OrderState::Open),
+        "canceled" => Some(OrderState::Cancelled),
+        "executed" => Some(OrderState::Filled),
+        _ => None
+    }
+}
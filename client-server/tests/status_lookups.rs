@@ -0,0 +1,148 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use kalshi::{Action, OrderType, Side};
+use kalshi_rest::{Credentials, Environment, EnvironmentConfig, KalshiRestClient};
+use mock_kalshi::{MockConfig, Script, API_PREFIX};
+use oms_config::OmsConfig;
+use protocol::messages::{OrderStatusEndMessage, OrderStatusMessage};
+use queue_client::queue_data::orders::{CreateOrderMessage, OrderState};
+use queue_client::queues::Queues;
+
+// message types from CLIENT_PROTOCOL.md
+const LOGIN: u8 = 0;
+const STATUS_REQUEST: u8 = 7;
+const ORDER_STATUS: u8 = 8;
+const ORDER_STATUS_END: u8 = 9;
+
+const CLIENT_NAME: &str = "client0001";
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A fresh working directory for the client-server, whose journal is written relative to it.
+struct TestDir(PathBuf);
+
+impl TestDir {
+    fn enter() -> TestDir {
+        let dir = std::env::temp_dir().join(format!("client-server-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+        TestDir(dir)
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Start the mock exchange on any free port and return the base URL of its REST API.
+async fn start_mock_kalshi() -> String {
+    let rest_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let ws_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let api_url = format!("http://{}{}", rest_listener.local_addr().unwrap(), API_PREFIX);
+    tokio::spawn(mock_kalshi::serve(rest_listener, ws_listener, MockConfig::default(), Arc::new(Script::new(Vec::new()))));
+    api_url
+}
+
+/// Place an order straight on the mock exchange, as if the OMS had placed it for the client.
+async fn place(exchange_client: &KalshiRestClient, client_order_id: &str) {
+    exchange_client.create_order(&CreateOrderMessage {
+        action: Action::Buy,
+        client_order_id: format!("{}{}", CLIENT_NAME, client_order_id),
+        count: 2,
+        side: Side::Yes,
+        ticker: "TICKER".to_string(),
+        input_type: OrderType::Limit,
+        buy_max_cost: None,
+        expiration_ts: None,
+        no_price: None,
+        sell_position_floor: None,
+        yes_price: Some(30)
+    }).await.unwrap();
+}
+
+async fn connect(addr: &str) -> TcpStream {
+    for _ in 0..100 {
+        if let Ok(stream) = TcpStream::connect(addr).await {
+            return stream;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("the client-server did not start listening on {}", addr);
+}
+
+async fn send(stream: &mut TcpStream, header: u8, body: &[u8]) {
+    let mut frame = vec![header, body.len() as u8];
+    frame.extend_from_slice(body);
+    stream.write_all(&frame).await.unwrap();
+}
+
+async fn receive(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).await.unwrap();
+    let mut body = vec![0u8; header[1] as usize];
+    stream.read_exact(&mut body).await.unwrap();
+    (header[0], body)
+}
+
+/// Ask for one order's status, returning the single status the answer holds.
+async fn status_of(stream: &mut TcpStream, client_order_id: &str) -> OrderStatusMessage {
+    send(stream, STATUS_REQUEST, client_order_id.as_bytes()).await;
+    let answer = async {
+        let (header, body) = receive(stream).await;
+        assert_eq!(header, ORDER_STATUS);
+        let status: OrderStatusMessage = bincode::deserialize(&body).unwrap();
+        let (header, body) = receive(stream).await;
+        assert_eq!(header, ORDER_STATUS_END);
+        assert_eq!(bincode::deserialize::<OrderStatusEndMessage>(&body).unwrap().count, 1);
+        status
+    };
+    tokio::time::timeout(ANSWER_TIMEOUT, answer).await.expect("the client-server did not answer")
+}
+
+#[tokio::test]
+async fn the_client_server_reconciles_with_and_looks_orders_up_on_the_configured_exchange() {
+    let _dir = TestDir::enter();
+    std::env::set_var("KALSHI_EMAIL", "trader@example.com");
+    std::env::set_var("KALSHI_PASSWORD", "password");
+
+    let api_url = start_mock_kalshi().await;
+    let environment = EnvironmentConfig::new(Environment::Demo, Some(api_url.clone()), None, None).unwrap();
+    let exchange_client = environment.login(&Credentials::from_env().unwrap()).await.unwrap();
+    place(&exchange_client, "adopted").await;
+
+    let listen_addr = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+    let mut config = OmsConfig::default();
+    config.kalshi.api_url = Some(api_url);
+    config.client_server.listen_addr = listen_addr.clone();
+    let client_server = tokio::spawn(client_server::run(config.clone(), Queues::in_process(config.broker.queues.clone())));
+
+    let mut stream = connect(&listen_addr).await;
+    let mut login = vec![0u8];
+    login.extend_from_slice(CLIENT_NAME.as_bytes());
+    send(&mut stream, LOGIN, &login).await;
+
+    // the order resting on the exchange at startup was adopted into the order store
+    let adopted = status_of(&mut stream, "adopted").await;
+    assert_eq!(adopted.state, Some(OrderState::Open));
+
+    // one placed since is looked up on the exchange when the order store does not know it
+    place(&exchange_client, "since").await;
+    let looked_up = status_of(&mut stream, "since").await;
+    assert_eq!((looked_up.state, looked_up.filled_count), (Some(OrderState::Open), 0));
+    assert!(looked_up.order_id.is_some());
+
+    assert_eq!(status_of(&mut stream, "never").await.state, None);
+
+    assert!(!client_server.is_finished());
+    client_server.abort();
+}
@@ -1,2 +1,9 @@
-pub const USER: &str = "";
-pub const PW: &str = "";
\ No newline at end of file
+use std::time::Duration;
+
+pub const JOURNAL_DIR: &str = "data/journal/exchange-server-1";
+pub const JOURNAL_MAX_FILE_BYTES: u64 = 64 * 1024 * 1024;
+pub const CREATE_ORDER_ENDPOINT: &str = "create_order";
+pub const CANCEL_ORDER_ENDPOINT: &str = "cancel_order";
+pub const RECOVERY_PAGE_SIZE: i32 = 100;
+pub const RECOVERY_LOOKUP_MARGIN_SECS: i64 = 60;
+pub const SESSION_RENEW_INTERVAL: Duration = Duration::from_secs(20 * 60);
\ No newline at end of file
@@ -0,0 +1,181 @@
+use log::{debug, info, error};
+use anyhow::Result;
+use queue_client::{consumer::Consumer, queue_data::orders::OrderConfirmMessage, queue_data::orders::CreateOrderMessage};
+use queue_client::queue_data::orders::OrderRejectMessage;
+use queue_client::queue_data::cancels::{CancelOrderMessage, CancelConfirmMessage, CancelRejectMessage};
+use queue_client::producer::Producer;
+use journal::{FsyncPolicy, Journal, JournalConfig, RecordKind, SharedJournal};
+
+use kalshi_rest::{Credentials, KalshiSession};
+use oms_config::OmsConfig;
+use queue_client::queues::Queues;
+
+mod constants;
+mod recovery;
+
+extern crate kalshi;
+
+/// Run the order gateway: relay orders and cancels from the queues to Kalshi, and its answers back,
+/// with the queues on the broker or in the process it shares with the client-server.
+pub async fn run(config: OmsConfig, queues: Queues) -> Result<()> {
+
+    // 1. Choose the Kalshi environment, refusing to start if its URLs or credentials belong to the other one
+
+    let environment = config.kalshi.environment_config()?;
+    info!("==== Trading in {} ====", environment);
+
+    // 2. Create and login to a new Kalshi REST API Client, with credentials from the environment or a secrets file
+
+    let credentials = Credentials::from_env()?;
+
+    let mut session = KalshiSession::open(&environment, &credentials, constants::SESSION_RENEW_INTERVAL).await?;
+
+    info!("Successful instantiation of kalshi exchange client at {} with {}", session.client().base_url(), credentials);
+
+    // 3. Open the journal every message crossing the gateway is recorded in
+
+    let journal = Journal::open(JournalConfig {
+        dir: constants::JOURNAL_DIR.into(),
+        fsync: FsyncPolicy::Always,
+        max_file_bytes: constants::JOURNAL_MAX_FILE_BYTES
+    })?.shared();
+
+    // 4. Create the producers and consumers of the gateway's queues
+
+    let order_consumer = queues.consumer::<CreateOrderMessage>().await?.with_journal(journal.clone());
+    let order_confirm_producer = queues.producer::<OrderConfirmMessage>().await?.with_journal(journal.clone());
+    let order_reject_producer = queues.producer::<OrderRejectMessage>().await?.with_journal(journal.clone());
+    let cancel_consumer = queues.consumer::<CancelOrderMessage>().await?.with_journal(journal.clone());
+    let cancel_confirm_producer = queues.producer::<CancelConfirmMessage>().await?.with_journal(journal.clone());
+    let cancel_reject_producer = queues.producer::<CancelRejectMessage>().await?.with_journal(journal.clone());
+
+    // 5. Settle every order and cancel left unanswered when the gateway last stopped
+
+    let report = recovery::recover(
+        std::path::Path::new(constants::JOURNAL_DIR),
+        queues.names(),
+        session.client(),
+        &order_confirm_producer,
+        &order_reject_producer,
+        &cancel_confirm_producer,
+        &cancel_reject_producer,
+        &journal
+    ).await?;
+    info!("Recovered from the journal: {:?}", report);
+
+    // 6. Loop
+        
+    run_loop(&mut session, order_consumer, order_confirm_producer, order_reject_producer, cancel_consumer, cancel_confirm_producer, cancel_reject_producer, journal).await?;
+
+    Ok(())
+
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_loop(
+    session: &mut KalshiSession,
+    order_consumer: Consumer<CreateOrderMessage>, 
+    order_confirm_producer: Producer<OrderConfirmMessage>,
+    order_reject_producer: Producer<OrderRejectMessage>,
+    cancel_consumer: Consumer<CancelOrderMessage>,
+    cancel_confirm_producer: Producer<CancelConfirmMessage>,
+    cancel_reject_producer: Producer<CancelRejectMessage>,
+    journal: SharedJournal
+) -> Result<()> {
+
+    loop {
+
+        // renew the session while idle, so the next order does not wait on it
+        session.renew_if_due().await;
+
+        // get all orders in the queue
+        match order_consumer.get_all().await {
+
+            Ok(orders) => {
+                
+                // iteratively place each order & relay response to MQ, holding them while the session renews
+                for order in orders {
+                    session.renew_if_due().await;
+                    info!("Relaying Order from MQ to Exchange: {:?}", order);
+                    let client_order_id = order.client_order_id.clone();
+                    journal::append(&journal, RecordKind::ExchangeRequest, constants::CREATE_ORDER_ENDPOINT, &order)?;
+
+                    // an unauthorized order never reached the book, so it is sent again once logged back in
+                    let mut response = session.client().create_order(&order).await;
+                    if response.as_ref().is_err_and(kalshi_rest::is_unauthorized) {
+                        session.on_unauthorized().await;
+                        response = session.client().create_order(&order).await;
+                    }
+
+                    match response {
+                        Ok(order_response) => {
+                            debug!("Exchange Order Response: {:?}", order_response);
+                            // send the order confirmation to the "order_confirm" queue using the producer
+                            let order_confirm = OrderConfirmMessage::new(order_response.order_id, Some(order_response.client_order_id));
+                            journal::append(&journal, RecordKind::ExchangeResponse, constants::CREATE_ORDER_ENDPOINT, &order_confirm)?;
+                            debug!("Relaying Order Confirmation to MQ: {:?}", order_confirm);
+                            order_confirm_producer.publish(order_confirm).await?;
+                        },
+                        Err(e) => {
+                            error!("Error placing order: {:?}", e);
+                            // send the rejection to the "order_reject" queue so the client learns its order is dead
+                            let order_reject = OrderRejectMessage::new(client_order_id, format!("{:?}", e));
+                            journal::append(&journal, RecordKind::ExchangeResponse, constants::CREATE_ORDER_ENDPOINT, &order_reject)?;
+                            debug!("Relaying Order Rejection to MQ: {:?}", order_reject);
+                            order_reject_producer.publish(order_reject).await?;
+                        }
+                    }
+                }
+            },
+            Err(e) => error!("Error getting orders from queue: {:?}", e)
+        }
+
+        // get all cancels in the queue
+        match cancel_consumer.get_all().await {
+
+            Ok(cancels) => {
+
+                // iteratively cancel each order & relay response to MQ
+                for cancel in cancels {
+                    session.renew_if_due().await;
+                    info!("Relaying Cancel from MQ to Exchange: {:?}", cancel);
+                    journal::append(&journal, RecordKind::ExchangeRequest, constants::CANCEL_ORDER_ENDPOINT, &cancel)?;
+
+                    let mut response = session.client().cancel_order(&cancel.order_id).await;
+                    if response.as_ref().is_err_and(kalshi_rest::is_unauthorized) {
+                        session.on_unauthorized().await;
+                        response = session.client().cancel_order(&cancel.order_id).await;
+                    }
+
+                    match response {
+                        Ok((order_response, _reduced_by)) => {
+                            debug!("Exchange Cancel Response: {:?}", order_response);
+                            // send the cancel confirmation to the "cancel_confirm" queue using the producer
+                            let cancel_confirm = CancelConfirmMessage {
+                                order_id: order_response.order_id,
+                                client_order_id: cancel.client_order_id
+                            };
+                            journal::append(&journal, RecordKind::ExchangeResponse, constants::CANCEL_ORDER_ENDPOINT, &cancel_confirm)?;
+                            debug!("Relaying Cancel Confirmation to MQ: {:?}", cancel_confirm);
+                            cancel_confirm_producer.publish(cancel_confirm).await?;
+                        },
+                        Err(e) => {
+                            error!("Error cancelling order: {:?}", e);
+                            // send the rejection to the "cancel_reject" queue so the order is no longer left pending cancel
+                            let cancel_reject = CancelRejectMessage {
+                                order_id: cancel.order_id,
+                                client_order_id: cancel.client_order_id,
+                                reason: e.to_string()
+                            };
+                            journal::append(&journal, RecordKind::ExchangeResponse, constants::CANCEL_ORDER_ENDPOINT, &cancel_reject)?;
+                            debug!("Relaying Cancel Rejection to MQ: {:?}", cancel_reject);
+                            cancel_reject_producer.publish(cancel_reject).await?;
+                        }
+                    }
+                }
+            },
+            Err(e) => error!("Error getting cancels from queue: {:?}", e)
+        }
+    }
+
+}
\ No newline at end of file
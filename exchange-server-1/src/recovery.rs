@@ -0,0 +1,256 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
+use log::{info, warn};
+
+use journal::{Record, RecordKind, SharedJournal};
+use kalshi_rest::{KalshiRestClient, OrdersQuery};
+use queue_client::producer::Producer;
+use queue_client::queue_data::cancels::{CancelConfirmMessage, CancelOrderMessage, CancelRejectMessage};
+use queue_client::queue_data::data_core::QueueData;
+use queue_client::queue_data::orders::{CreateOrderMessage, OrderConfirmMessage, OrderRejectMessage};
+use queue_client::queue_names::QueueNames;
+
+use crate::constants::{CANCEL_ORDER_ENDPOINT, RECOVERY_LOOKUP_MARGIN_SECS, RECOVERY_PAGE_SIZE};
+
+/// An order or cancel the gateway took off its queue, and when it did.
+struct Unanswered<T> {
+    message: T,
+    consumed_ts: i64
+}
+
+/// What the gateway found in its journal after a restart.
+#[derive(Debug, Default)]
+pub struct RecoveryReport {
+    pub orders_found_on_exchange: usize,
+    pub orders_rejected: usize,
+    pub cancels_resent: usize
+}
+
+/// Find every order and cancel the gateway took off its queues before it last stopped but never
+/// answered on the confirm or reject queues, and settle each one so that none is silently lost.
+///
+/// Messages are acknowledged to the queue as soon as they are journaled, so these are exactly the
+/// ones the gateway died holding. An unanswered order is looked up on Kalshi by its client order id:
+/// if Kalshi has it the order is confirmed, and otherwise it never reached the exchange and is
+/// rejected, since placing it late could surprise the client that sent it. Unanswered cancels are
+/// sent again, and confirmed or rejected as Kalshi answers.
+#[allow(clippy::too_many_arguments)]
+pub async fn recover(
+    journal_dir: &Path,
+    queue_names: &QueueNames,
+    exchange_client: &KalshiRestClient,
+    order_confirm_producer: &Producer<OrderConfirmMessage>,
+    order_reject_producer: &Producer<OrderRejectMessage>,
+    cancel_confirm_producer: &Producer<CancelConfirmMessage>,
+    cancel_reject_producer: &Producer<CancelRejectMessage>,
+    journal: &SharedJournal
+) -> Result<RecoveryReport> {
+
+    let records = journal::reader::read_all(journal_dir)?;
+    info!("Reading {} journal records for unanswered orders and cancels", records.len());
+    let mut report = RecoveryReport::default();
+    let (orders, cancels) = find_unanswered(&records, queue_names)?;
+
+    // 1. Orders: confirm those Kalshi has, reject the rest
+
+    if let Some(min_ts) = orders.values().map(|order| order.consumed_ts).min() {
+        let exchange_order_ids = find_exchange_orders(exchange_client, min_ts - RECOVERY_LOOKUP_MARGIN_SECS).await?;
+        for (client_order_id, order) in orders {
+            match exchange_order_ids.get(&client_order_id) {
+                Some(order_id) => {
+                    info!("Order {:?} reached the exchange before the restart. Confirming it.", client_order_id);
+                    order_confirm_producer.publish(OrderConfirmMessage::new(order_id.clone(), Some(client_order_id))).await?;
+                    report.orders_found_on_exchange += 1;
+                },
+                None => {
+                    warn!("Order {:?} for {} never reached the exchange before the restart. Rejecting it.", client_order_id, order.message.ticker);
+                    let reason = "The order was not placed before the exchange gateway restarted".to_string();
+                    order_reject_producer.publish(OrderRejectMessage::new(client_order_id, reason)).await?;
+                    report.orders_rejected += 1;
+                }
+            }
+        }
+    }
+
+    // 2. Cancels: send them again, as cancelling twice is harmless
+
+    for (_, cancel) in cancels {
+        let cancel = cancel.message;
+        info!("Resending cancel for order {:?} left unanswered before the restart.", cancel.client_order_id);
+        journal::append(journal, RecordKind::ExchangeRequest, CANCEL_ORDER_ENDPOINT, &cancel)?;
+        match exchange_client.cancel_order(&cancel.order_id).await {
+            Ok((order_response, _reduced_by)) => {
+                let cancel_confirm = CancelConfirmMessage {
+                    order_id: order_response.order_id,
+                    client_order_id: cancel.client_order_id
+                };
+                journal::append(journal, RecordKind::ExchangeResponse, CANCEL_ORDER_ENDPOINT, &cancel_confirm)?;
+                cancel_confirm_producer.publish(cancel_confirm).await?;
+            },
+            Err(e) => {
+                warn!("Resent cancel for order {:?} failed: {:?}", cancel.client_order_id, e);
+                let cancel_reject = CancelRejectMessage {
+                    order_id: cancel.order_id,
+                    client_order_id: cancel.client_order_id,
+                    reason: e.to_string()
+                };
+                journal::append(journal, RecordKind::ExchangeResponse, CANCEL_ORDER_ENDPOINT, &cancel_reject)?;
+                cancel_reject_producer.publish(cancel_reject).await?;
+            }
+        }
+        report.cancels_resent += 1;
+    }
+
+    Ok(report)
+}
+
+/// Walk the journal in order, pairing each order consumed with the confirm or reject published
+/// for it and each cancel consumed with the exchange's response to it, and return those left
+/// without one, keyed by client order id.
+#[allow(clippy::type_complexity)]
+fn find_unanswered(records: &[Record], queue_names: &QueueNames) -> Result<(HashMap<String, Unanswered<CreateOrderMessage>>, HashMap<String, Unanswered<CancelOrderMessage>>)> {
+    let (order_queue, cancel_queue) = (queue_names.name(&CreateOrderMessage::class()), queue_names.name(&CancelOrderMessage::class()));
+    let (confirm_queue, reject_queue) = (queue_names.name(&OrderConfirmMessage::class()), queue_names.name(&OrderRejectMessage::class()));
+
+    let mut orders = HashMap::new();
+    let mut cancels = HashMap::new();
+    let mut last_cancel_sent = None;
+    for record in records {
+        let consumed_ts = record.ts / 1_000_000;
+        match record.kind {
+            RecordKind::QueueConsume if record.source == order_queue => {
+                let order: CreateOrderMessage = record.decode()?;
+                orders.insert(order.client_order_id.clone(), Unanswered { message: order, consumed_ts });
+            },
+            RecordKind::QueueConsume if record.source == cancel_queue => {
+                let cancel: CancelOrderMessage = record.decode()?;
+                cancels.insert(cancel.client_order_id.clone(), Unanswered { message: cancel, consumed_ts });
+            },
+            RecordKind::QueuePublish if record.source == confirm_queue => {
+                let confirm: OrderConfirmMessage = record.decode()?;
+                if let Some(client_order_id) = confirm.client_order_id {
+                    orders.remove(&client_order_id);
+                }
+            },
+            RecordKind::QueuePublish if record.source == reject_queue => {
+                let reject: OrderRejectMessage = record.decode()?;
+                orders.remove(&reject.client_order_id);
+            },
+            RecordKind::ExchangeRequest if record.source == CANCEL_ORDER_ENDPOINT => {
+                let cancel: CancelOrderMessage = record.decode()?;
+                last_cancel_sent = Some(cancel.client_order_id);
+            },
+            // the exchange's response answers a cancel whether it succeeded or failed;
+            // the gateway sends one request at a time, so it answers the last cancel sent
+            RecordKind::ExchangeResponse if record.source == CANCEL_ORDER_ENDPOINT => {
+                if let Some(client_order_id) = last_cancel_sent.take() {
+                    cancels.remove(&client_order_id);
+                }
+            },
+            _ => {}
+        }
+    }
+    Ok((orders, cancels))
+}
+
+/// Page through the account's orders placed since `min_ts`, mapping client order id to order id.
+async fn find_exchange_orders(exchange_client: &KalshiRestClient, min_ts: i64) -> Result<HashMap<String, String>> {
+    let mut order_ids = HashMap::new();
+    let mut cursor = None;
+    loop {
+        let (next_cursor, orders) = exchange_client
+            .get_orders(&OrdersQuery { min_ts: Some(min_ts), limit: Some(RECOVERY_PAGE_SIZE), cursor, ..Default::default() })
+            .await
+            .map_err(|e| anyhow::anyhow!("Kalshi order lookup failed: {:?}", e))?;
+        for order in orders {
+            order_ids.insert(order.client_order_id, order.order_id);
+        }
+
+        cursor = match next_cursor {
+            Some(next_cursor) if !next_cursor.is_empty() => Some(next_cursor),
+            _ => return Ok(order_ids)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kalshi::{Action, OrderType, Side};
+    use serde::Serialize;
+
+    fn record<T: Serialize>(seq: u64, kind: RecordKind, source: &str, message: &T) -> Record {
+        Record { seq, ts: seq as i64 * 1_000_000, kind, source: source.to_string(), payload: bincode::serialize(message).unwrap() }
+    }
+
+    fn order(client_order_id: &str) -> CreateOrderMessage {
+        CreateOrderMessage {
+            action: Action::Buy,
+            client_order_id: client_order_id.to_string(),
+            count: 1,
+            side: Side::Yes,
+            ticker: "TICKER".to_string(),
+            input_type: OrderType::Limit,
+            buy_max_cost: None,
+            expiration_ts: None,
+            no_price: None,
+            sell_position_floor: None,
+            yes_price: Some(50)
+        }
+    }
+
+    fn cancel(client_order_id: &str) -> CancelOrderMessage {
+        CancelOrderMessage { order_id: format!("order-{}", client_order_id), client_order_id: client_order_id.to_string() }
+    }
+
+    fn queue<T: QueueData>() -> String {
+        QueueNames::default().name(&T::class())
+    }
+
+    #[test]
+    fn orders_answered_by_a_confirm_or_reject_are_settled() {
+        let records = vec![
+            record(1, RecordKind::QueueConsume, &queue::<CreateOrderMessage>(), &order("confirmed")),
+            record(2, RecordKind::QueueConsume, &queue::<CreateOrderMessage>(), &order("rejected")),
+            record(3, RecordKind::QueueConsume, &queue::<CreateOrderMessage>(), &order("unanswered")),
+            record(4, RecordKind::QueuePublish, &queue::<OrderConfirmMessage>(), &OrderConfirmMessage::new("order-1".to_string(), Some("confirmed".to_string()))),
+            record(5, RecordKind::QueuePublish, &queue::<OrderRejectMessage>(), &OrderRejectMessage::new("rejected".to_string(), "no".to_string()))
+        ];
+
+        let (orders, cancels) = find_unanswered(&records, &QueueNames::default()).unwrap();
+        assert_eq!(orders.keys().collect::<Vec<_>>(), vec!["unanswered"]);
+        assert_eq!(orders["unanswered"].consumed_ts, 3);
+        assert!(cancels.is_empty());
+    }
+
+    #[test]
+    fn cancels_are_answered_by_the_exchange_response_to_the_last_cancel_sent() {
+        let records = vec![
+            record(1, RecordKind::QueueConsume, &queue::<CancelOrderMessage>(), &cancel("answered")),
+            record(2, RecordKind::QueueConsume, &queue::<CancelOrderMessage>(), &cancel("sent")),
+            record(3, RecordKind::QueueConsume, &queue::<CancelOrderMessage>(), &cancel("unsent")),
+            record(4, RecordKind::ExchangeRequest, CANCEL_ORDER_ENDPOINT, &cancel("answered")),
+            record(5, RecordKind::ExchangeResponse, CANCEL_ORDER_ENDPOINT, &"failed".to_string()),
+            record(6, RecordKind::ExchangeRequest, CANCEL_ORDER_ENDPOINT, &cancel("sent"))
+        ];
+
+        let (orders, cancels) = find_unanswered(&records, &QueueNames::default()).unwrap();
+        assert!(orders.is_empty());
+        let mut unanswered: Vec<_> = cancels.keys().cloned().collect();
+        unanswered.sort();
+        assert_eq!(unanswered, vec!["sent", "unsent"]);
+    }
+
+    #[test]
+    fn records_on_other_queues_are_ignored() {
+        let names = QueueNames { prefix: "other-".to_string(), ..Default::default() };
+        let records = vec![
+            record(1, RecordKind::QueueConsume, &queue::<CreateOrderMessage>(), &order("elsewhere")),
+            record(2, RecordKind::QueueConsume, &names.name(&CreateOrderMessage::class()), &order("here"))
+        ];
+
+        let (orders, _) = find_unanswered(&records, &names).unwrap();
+        assert_eq!(orders.keys().collect::<Vec<_>>(), vec!["here"]);
+    }
+}
@@ -0,0 +1,113 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+use kalshi::{Action, OrderType, Side};
+use mock_kalshi::{MockConfig, Script, API_PREFIX};
+use oms_config::OmsConfig;
+use queue_client::consumer::Consumer;
+use queue_client::queue_data::cancels::{CancelConfirmMessage, CancelOrderMessage, CancelRejectMessage};
+use queue_client::queue_data::data_core::QueueData;
+use queue_client::queue_data::orders::{CreateOrderMessage, OrderConfirmMessage, OrderRejectMessage};
+use queue_client::queues::Queues;
+
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A fresh working directory for the gateway, whose journal is written relative to it.
+struct TestDir(PathBuf);
+
+impl TestDir {
+    fn enter() -> TestDir {
+        let dir = std::env::temp_dir().join(format!("exchange-server-1-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+        TestDir(dir)
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Start the mock exchange on any free port and return the base URL of its REST API.
+async fn start_mock_kalshi() -> String {
+    let rest_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let ws_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let api_url = format!("http://{}{}", rest_listener.local_addr().unwrap(), API_PREFIX);
+    tokio::spawn(mock_kalshi::serve(rest_listener, ws_listener, MockConfig::default(), Arc::new(Script::new(Vec::new()))));
+    api_url
+}
+
+async fn next<T: QueueData>(consumer: &Consumer<T>) -> T {
+    let wait = async {
+        loop {
+            if let Some(message) = consumer.get_next().await.unwrap() {
+                return message;
+            }
+        }
+    };
+    tokio::time::timeout(ANSWER_TIMEOUT, wait).await.expect("the gateway did not answer")
+}
+
+fn sell_yes(client_order_id: &str, count: i32, yes_price: i64) -> CreateOrderMessage {
+    CreateOrderMessage {
+        action: Action::Sell,
+        client_order_id: client_order_id.to_string(),
+        count,
+        side: Side::Yes,
+        ticker: "TICKER".to_string(),
+        input_type: OrderType::Limit,
+        buy_max_cost: None,
+        expiration_ts: None,
+        no_price: None,
+        sell_position_floor: None,
+        yes_price: Some(yes_price)
+    }
+}
+
+#[tokio::test]
+async fn the_gateway_relays_orders_and_cancels_to_kalshi_and_its_answers_back() {
+    let _dir = TestDir::enter();
+    std::env::set_var("KALSHI_EMAIL", "trader@example.com");
+    std::env::set_var("KALSHI_PASSWORD", "password");
+
+    let mut config = OmsConfig::default();
+    config.kalshi.api_url = Some(start_mock_kalshi().await);
+    let queues = Queues::in_process(config.broker.queues.clone());
+    let gateway = tokio::spawn(kalshi_exchange_server_1::run(config, queues.clone()));
+
+    let orders = queues.producer::<CreateOrderMessage>().await.unwrap();
+    let cancels = queues.producer::<CancelOrderMessage>().await.unwrap();
+    let order_confirms = queues.consumer::<OrderConfirmMessage>().await.unwrap();
+    let order_rejects = queues.consumer::<OrderRejectMessage>().await.unwrap();
+    let cancel_confirms = queues.consumer::<CancelConfirmMessage>().await.unwrap();
+    let cancel_rejects = queues.consumer::<CancelRejectMessage>().await.unwrap();
+
+    // an order Kalshi accepts is confirmed with the order id it was given
+    orders.publish(sell_yes("order-1", 3, 40)).await.unwrap();
+    let confirm = next(&order_confirms).await;
+    assert_eq!(confirm.client_order_id.as_deref(), Some("order-1"));
+
+    // one it refuses, here for reusing a client order id, is rejected
+    orders.publish(sell_yes("order-1", 1, 45)).await.unwrap();
+    let reject = next(&order_rejects).await;
+    assert_eq!(reject.client_order_id, "order-1");
+
+    // and a cancel is confirmed once Kalshi has cancelled the order
+    cancels.publish(CancelOrderMessage { order_id: confirm.order_id.clone(), client_order_id: "order-1".to_string() }).await.unwrap();
+    let cancel_confirm = next(&cancel_confirms).await;
+    assert_eq!((cancel_confirm.order_id, cancel_confirm.client_order_id), (confirm.order_id.clone(), "order-1".to_string()));
+
+    // while cancelling it again, which Kalshi refuses, is rejected
+    cancels.publish(CancelOrderMessage { order_id: confirm.order_id.clone(), client_order_id: "order-1".to_string() }).await.unwrap();
+    let cancel_reject = next(&cancel_rejects).await;
+    assert_eq!((cancel_reject.order_id, cancel_reject.client_order_id), (confirm.order_id, "order-1".to_string()));
+
+    assert!(!gateway.is_finished());
+    gateway.abort();
+}
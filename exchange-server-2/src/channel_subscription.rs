@@ -0,0 +1,165 @@
+use std::collections::BTreeSet;
+use anyhow::Result;
+
+use kalshi_rest::{KalshiRestClient, OrdersQuery};
+
+use crate::constants::TICKER_LOOKUP_PAGE_SIZE;
+use crate::kalshi_wss::{KalshiClientSubMessage as SubMessage, SubscribeSubMessage, UnsubscribeSubMessage, UpdateSubscriptionSubMessage};
+
+/// Tracks the markets the feed should cover on one channel and the state of the single
+/// subscription to that channel which covers them. Methods return the commands to send.
+pub struct ChannelSubscription {
+    channel: &'static str,
+    tickers: BTreeSet<String>,
+    subscribed_tickers: BTreeSet<String>,
+    sid: Option<u32>,
+    request_id: Option<u32>
+}
+
+impl ChannelSubscription {
+
+    pub fn new(channel: &'static str) -> ChannelSubscription {
+        ChannelSubscription {
+            channel,
+            tickers: BTreeSet::new(),
+            subscribed_tickers: BTreeSet::new(),
+            sid: None,
+            request_id: None
+        }
+    }
+
+    pub fn channel(&self) -> &'static str {
+        self.channel
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tickers.is_empty()
+    }
+
+    /// The id of the subscription once the server has confirmed it.
+    pub fn sid(&self) -> Option<u32> {
+        self.sid
+    }
+
+    /// Whether `id` is the subscribe command awaiting the server's response.
+    pub fn is_request(&self, id: u32) -> bool {
+        self.request_id == Some(id)
+    }
+
+    /// Forget the subscription, which does not outlive its connection. The tickers are kept.
+    pub fn reset(&mut self) {
+        self.subscribed_tickers.clear();
+        self.sid = None;
+        self.request_id = None;
+    }
+
+    /// Add markets without subscribing to them, as before a connection's first subscribe.
+    pub fn extend(&mut self, tickers: impl IntoIterator<Item = String>) {
+        self.tickers.extend(tickers);
+    }
+
+    /// Add a market, returning the command that extends the subscription to it, if one is needed now.
+    pub fn add_ticker(&mut self, ticker: String) -> Option<SubMessage> {
+        if !self.tickers.insert(ticker.clone()) {
+            return None;
+        }
+        match self.sid {
+            Some(sid) => {
+                self.subscribed_tickers.insert(ticker.clone());
+                Some(SubMessage::UpdateSubscriptionSubMessage(UpdateSubscriptionSubMessage::add_markets(sid, vec![ticker])))
+            },
+            None if self.request_id.is_none() => Some(self.subscribe()),
+            None => None // picked up once the pending subscription is confirmed
+        }
+    }
+
+    /// Drop a market, returning the command that removes it from the subscription, if one is needed now.
+    pub fn remove_ticker(&mut self, ticker: &str) -> Option<SubMessage> {
+        if !self.tickers.remove(ticker) {
+            return None;
+        }
+        let subscribed = self.subscribed_tickers.remove(ticker);
+        match self.sid {
+            Some(sid) if subscribed => Some(SubMessage::UpdateSubscriptionSubMessage(UpdateSubscriptionSubMessage::delete_markets(sid, vec![ticker.to_string()]))),
+            _ => None // a pending subscription keeps it until the next resubscribe
+        }
+    }
+
+    /// The command subscribing to the channel for every market. Its id must be handed to `set_request_id`.
+    pub fn subscribe(&mut self) -> SubMessage {
+        self.subscribed_tickers = self.tickers.clone();
+        let sub_msg = SubscribeSubMessage::new(vec![self.channel.into()])
+            .market_tickers(self.tickers.iter().cloned().collect());
+        SubMessage::SubscribeSubMessage(sub_msg)
+    }
+
+    pub fn set_request_id(&mut self, request_id: Option<u32>) {
+        self.request_id = request_id;
+    }
+
+    /// Record the server's confirmation of the subscription, returning the command
+    /// adding any markets requested while it was pending.
+    pub fn on_subscribed(&mut self, sid: u32) -> Option<SubMessage> {
+        self.sid = Some(sid);
+        self.request_id = None;
+        let missing: Vec<String> = self.tickers.difference(&self.subscribed_tickers).cloned().collect();
+        if missing.is_empty() {
+            return None;
+        }
+        self.subscribed_tickers.extend(missing.iter().cloned());
+        Some(SubMessage::UpdateSubscriptionSubMessage(UpdateSubscriptionSubMessage::add_markets(sid, missing)))
+    }
+
+    /// Record the server's refusal of the subscription, so the next market added tries again.
+    pub fn on_rejected(&mut self) {
+        self.request_id = None;
+    }
+
+    /// The commands replacing the subscription with a new one, as when an orderbook
+    /// subscription's books must be rebuilt from fresh snapshots.
+    pub fn resubscribe(&mut self) -> Vec<SubMessage> {
+        let mut commands = Vec::new();
+        if let Some(sid) = self.sid.take() {
+            commands.push(SubMessage::UnsubscribeSubMessage(UnsubscribeSubMessage::new(vec![sid])));
+        }
+        commands.push(self.subscribe());
+        commands
+    }
+}
+
+/// The markets the account has resting orders or open positions in, from Kalshi's REST API.
+pub async fn traded_tickers(exchange_client: &KalshiRestClient) -> Result<BTreeSet<String>> {
+    let mut tickers = BTreeSet::new();
+
+    let mut cursor = None;
+    loop {
+        let (next_cursor, orders) = exchange_client
+            .get_orders(&OrdersQuery { status: Some("resting".to_string()), limit: Some(TICKER_LOOKUP_PAGE_SIZE), cursor, ..Default::default() })
+            .await
+            .map_err(|e| anyhow::anyhow!("Kalshi order lookup failed: {:?}", e))?;
+        tickers.extend(orders.into_iter().map(|order| order.ticker));
+
+        cursor = match next_cursor {
+            Some(next_cursor) if !next_cursor.is_empty() => Some(next_cursor),
+            _ => break
+        };
+    }
+
+    let mut cursor = None;
+    loop {
+        let (next_cursor, market_positions) = exchange_client
+            .get_positions(Some(TICKER_LOOKUP_PAGE_SIZE), cursor)
+            .await
+            .map_err(|e| anyhow::anyhow!("Kalshi position lookup failed: {:?}", e))?;
+        tickers.extend(market_positions.into_iter()
+            .filter(|market_position| market_position.position != 0)
+            .map(|market_position| market_position.ticker));
+
+        cursor = match next_cursor {
+            Some(next_cursor) if !next_cursor.is_empty() => Some(next_cursor),
+            _ => break
+        };
+    }
+
+    Ok(tickers)
+}
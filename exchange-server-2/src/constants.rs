@@ -1,3 +1,19 @@
-pub const PROD_WSS: &str = "wss://trading-api.kalshi.com/trade-api/ws/v2";
-pub const USER: &str = "";
-pub const PW: &str = "";
\ No newline at end of file
+use std::time::Duration;
+
+pub const RECONNECT_BACKOFF_INITIAL: Duration = Duration::from_secs(1);
+pub const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(60);
+pub const SESSION_RENEW_INTERVAL: Duration = Duration::from_secs(20 * 60);
+pub const SESSION_RENEW_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+pub const FEED_STALE_AFTER: Duration = Duration::from_secs(30);
+pub const LIVENESS_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+pub const BACKFILL_PAGE_SIZE: i32 = 100;
+pub const DEDUP_TRADE_ID_CAPACITY: usize = 100_000;
+pub const BOOK_DEPTH_LEVELS: usize = 10;
+pub const TICKER_LOOKUP_PAGE_SIZE: i32 = 100;
+pub const ORDERBOOK_CHANNEL: &str = "orderbook_delta";
+pub const JOURNAL_DIR: &str = "data/journal/exchange-server-2";
+pub const JOURNAL_FSYNC_INTERVAL: Duration = Duration::from_millis(100);
+pub const JOURNAL_MAX_FILE_BYTES: u64 = 64 * 1024 * 1024;
+pub const WEBSOCKET_FILL_SOURCE: &str = "websocket";
+pub const BACKFILL_FILL_SOURCE: &str = "backfill";
+pub const MARKET_DATA_CHANNELS: [&str; 3] = [ORDERBOOK_CHANNEL, "trade", "ticker"];
@@ -0,0 +1,402 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::Result;
+use log::{debug, error, info, trace, warn};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_tungstenite::tungstenite::Message;
+
+use journal::{RecordKind, SharedJournal};
+use kalshi_rest::KalshiRestClient;
+use queue_client::producer::Producer;
+use queue_client::queue_data::fills::{Fill, FillMessage};
+use queue_client::queue_data::market_data::{self, BookMessage, MarketLifecycleMessage, MarketStatus, TickerMessage, TradeMessage};
+use queue_client::queue_data::orders::OrderUpdateMessage;
+
+use crate::channel_subscription::{self, ChannelSubscription};
+use crate::constants::{BACKFILL_FILL_SOURCE, BOOK_DEPTH_LEVELS, MARKET_DATA_CHANNELS, ORDERBOOK_CHANNEL, WEBSOCKET_FILL_SOURCE};
+use crate::fill_tracker::{self, FillTracker, SequenceCheck};
+use crate::kalshi_wss::{
+    KalshiClientMessageBuilder, KalshiClientSubMessage as SubMessage, KalshiServerMessage, ListSubscriptionsSubMessage,
+    MarketLifecycle, OrderUpdate, OrderbookDelta, OrderbookSnapshot, TickerUpdate, TradeUpdate
+};
+use crate::order_book::{BookError, OrderBook, OrderBooks};
+
+/// Dispatches each message from the Kalshi websocket to the handler for its type.
+/// Lives across websocket sessions, so fills already published are never published again
+/// and the market data of every requested market is resubscribed after a reconnect.
+pub struct FeedHandler {
+    msg_builder: KalshiClientMessageBuilder,
+    sender: Option<UnboundedSender<Message>>,
+    fill_producer: Producer<FillMessage>,
+    fill_tracker: FillTracker,
+    order_update_producer: Producer<OrderUpdateMessage>,
+    lifecycle_producer: Producer<MarketLifecycleMessage>,
+    book_producer: Producer<BookMessage>,
+    trade_producer: Producer<TradeMessage>,
+    ticker_producer: Producer<TickerMessage>,
+    market_subscriptions: Vec<ChannelSubscription>,
+    order_books: OrderBooks,
+    unknown_types: HashMap<String, u64>,
+    journal: SharedJournal
+}
+
+impl FeedHandler {
+
+    pub fn new(
+        fill_producer: Producer<FillMessage>,
+        order_update_producer: Producer<OrderUpdateMessage>,
+        lifecycle_producer: Producer<MarketLifecycleMessage>,
+        book_producer: Producer<BookMessage>,
+        trade_producer: Producer<TradeMessage>,
+        ticker_producer: Producer<TickerMessage>,
+        journal: SharedJournal
+    ) -> FeedHandler {
+        FeedHandler {
+            msg_builder: KalshiClientMessageBuilder::new(),
+            sender: None,
+            fill_producer,
+            fill_tracker: FillTracker::new(),
+            order_update_producer,
+            lifecycle_producer,
+            book_producer,
+            trade_producer,
+            ticker_producer,
+            market_subscriptions: MARKET_DATA_CHANNELS.into_iter().map(ChannelSubscription::new).collect(),
+            order_books: OrderBooks::new(),
+            unknown_types: HashMap::new(),
+            journal
+        }
+    }
+
+    /// The builder for commands to send to the server, whose responses this handler matches up.
+    pub fn msg_builder(&mut self) -> &mut KalshiClientMessageBuilder {
+        &mut self.msg_builder
+    }
+
+    /// Prepare for a new websocket session, whose commands are sent through `sender`.
+    /// Recovers any fills missed while disconnected and resubscribes to market data.
+    /// Fails if the missed fills could not all be published, so that the next session tries again.
+    pub async fn on_connect(&mut self, exchange_client: &KalshiRestClient, sender: UnboundedSender<Message>) -> Result<()> {
+        self.sender = Some(sender);
+        self.fill_tracker.reset_sequences();
+
+        // a reconnect backfills from the last fill, or from the first session if there has been none
+        if let Some(min_ts) = self.fill_tracker.backfill_from() {
+            self.backfill_and_publish(exchange_client, min_ts, None).await
+                .map_err(|e| e.context(format!("Failed to backfill fills since {min_ts} after reconnecting")))?;
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs() as i64);
+        self.fill_tracker.record_connect(now);
+
+        self.order_books.clear();
+        self.market_subscriptions.iter_mut().for_each(ChannelSubscription::reset);
+        if self.market_subscriptions.iter().all(ChannelSubscription::is_empty) {
+            // nothing has asked for market data yet, so start from the markets the account is active in
+            match channel_subscription::traded_tickers(exchange_client).await {
+                Ok(tickers) => self.market_subscriptions.iter_mut().for_each(|subscription| subscription.extend(tickers.iter().cloned())),
+                Err(e) => error!("Could not look up the markets the account trades: {e:?}")
+            }
+        }
+        let commands: Vec<_> = self.market_subscriptions.iter_mut().enumerate()
+            .filter(|(_, subscription)| !subscription.is_empty())
+            .map(|(index, subscription)| (index, subscription.subscribe()))
+            .collect();
+        for (index, sub_msg) in commands {
+            self.send_subscription_command(index, sub_msg);
+        }
+        Ok(())
+    }
+
+    /// Build and publish the book, trades and ticker updates of a market from now on.
+    pub fn add_market(&mut self, ticker: String) {
+        info!("Adding {} to the market data subscriptions", ticker);
+        let commands: Vec<_> = self.market_subscriptions.iter_mut().enumerate()
+            .filter_map(|(index, subscription)| subscription.add_ticker(ticker.clone()).map(|sub_msg| (index, sub_msg)))
+            .collect();
+        for (index, sub_msg) in commands {
+            self.send_subscription_command(index, sub_msg);
+        }
+    }
+
+    /// Stop building and publishing the book, trades and ticker updates of a market.
+    pub fn remove_market(&mut self, ticker: &str) {
+        info!("Removing {} from the market data subscriptions", ticker);
+        let commands: Vec<_> = self.market_subscriptions.iter_mut().enumerate()
+            .filter_map(|(index, subscription)| subscription.remove_ticker(ticker).map(|sub_msg| (index, sub_msg)))
+            .collect();
+        for (index, sub_msg) in commands {
+            self.send_subscription_command(index, sub_msg);
+        }
+    }
+
+    /// Parse a text frame and hand it to the handler for its type.
+    pub async fn handle_text(&mut self, text: &str, exchange_client: &KalshiRestClient) -> Result<()> {
+
+        let message = match serde_json::from_str::<KalshiServerMessage>(text) {
+            Ok(message) => message,
+            Err(e) => {
+                error!("Could not parse websocket message {text} with error {e:?}");
+                return Ok(())
+            }
+        };
+
+        match message {
+            KalshiServerMessage::Fill { sid, seq, msg } => self.handle_fill(sid, seq, msg, exchange_client).await?,
+            KalshiServerMessage::OrderbookSnapshot { sid, seq, msg } => self.handle_orderbook_snapshot(sid, seq, msg).await,
+            KalshiServerMessage::OrderbookDelta { sid, seq, msg } => self.handle_orderbook_delta(sid, seq, msg).await,
+            KalshiServerMessage::Ticker { sid, msg } => self.handle_ticker(sid, msg).await,
+            KalshiServerMessage::Trade { sid, msg } => self.handle_trade(sid, msg).await,
+            KalshiServerMessage::MarketLifecycle { sid, msg } => self.handle_market_lifecycle(sid, msg).await,
+            KalshiServerMessage::UserOrder { sid, msg } => self.handle_order_update(sid, msg).await,
+            KalshiServerMessage::Unknown => self.handle_unknown(text),
+            response => self.handle_response(response)
+        }
+        Ok(())
+    }
+
+    /// Publish a fill, first backfilling from the REST API if the subscription skipped any.
+    async fn handle_fill(&mut self, sid: u32, seq: u32, fill: Fill, exchange_client: &KalshiRestClient) -> Result<()> {
+        journal::append(&self.journal, RecordKind::Fill, WEBSOCKET_FILL_SOURCE, &fill)?;
+        match self.fill_tracker.check_sequence(sid, seq) {
+            SequenceCheck::InOrder => {},
+            SequenceCheck::Gap { expected, received } => {
+                warn!("Fill subscription {} skipped from seq {} to {}. Backfilling the gap.", sid, expected, received);
+                let min_ts = self.fill_tracker.backfill_from().unwrap_or(fill.ts);
+                self.backfill_and_publish(exchange_client, min_ts, Some(fill.ts)).await?;
+            },
+            SequenceCheck::Stale => warn!("Fill subscription {} repeated seq {}.", sid, seq)
+        }
+
+        self.publish_fill(FillMessage::new(sid, seq, fill)).await
+    }
+
+    async fn handle_orderbook_snapshot(&mut self, sid: u32, seq: u32, snapshot: OrderbookSnapshot) {
+        if self.book_sid() != Some(sid) {
+            trace!("Ignoring orderbook snapshot from stale subscription {}", sid);
+            return;
+        }
+        match self.order_books.apply_snapshot(sid, seq, &snapshot) {
+            Ok(book) => publish_book(&self.book_producer, &snapshot.market_ticker, book).await,
+            Err(e) => self.resync_books(e)
+        }
+    }
+
+    async fn handle_orderbook_delta(&mut self, sid: u32, seq: u32, delta: OrderbookDelta) {
+        if self.book_sid() != Some(sid) {
+            trace!("Ignoring orderbook delta from stale subscription {}", sid);
+            return;
+        }
+        match self.order_books.apply_delta(sid, seq, &delta) {
+            Ok(book) => publish_book(&self.book_producer, &delta.market_ticker, book).await,
+            Err(e) => self.resync_books(e)
+        }
+    }
+
+    /// Throw away every book and resubscribe, since Kalshi only resends snapshots for new subscriptions.
+    fn resync_books(&mut self, error: BookError) {
+        warn!("Order books are out of sync ({}). Resubscribing for fresh snapshots.", error);
+        self.order_books.clear();
+        if let Some(index) = self.subscription_index(ORDERBOOK_CHANNEL) {
+            for sub_msg in self.market_subscriptions[index].resubscribe() {
+                self.send_subscription_command(index, sub_msg);
+            }
+        }
+        // log what the server holds afterwards, in case the books keep falling out of sync
+        self.send_command(SubMessage::ListSubscriptionsSubMessage(ListSubscriptionsSubMessage::new()));
+    }
+
+    /// The id of the current orderbook subscription, once the server has confirmed it.
+    fn book_sid(&self) -> Option<u32> {
+        self.subscription_index(ORDERBOOK_CHANNEL).and_then(|index| self.market_subscriptions[index].sid())
+    }
+
+    async fn handle_ticker(&mut self, sid: u32, ticker: TickerUpdate) {
+        trace!("Received ticker update for {} on sid {}", ticker.market_ticker, sid);
+        let message = TickerMessage {
+            market_ticker: ticker.market_ticker,
+            price: ticker.price,
+            yes_bid: ticker.yes_bid,
+            yes_ask: ticker.yes_ask,
+            volume: ticker.volume,
+            open_interest: ticker.open_interest,
+            ts: ticker.ts
+        };
+        if let Err(e) = self.ticker_producer.publish(message).await {
+            error!("Failed to publish ticker update with error {e:?}");
+        }
+    }
+
+    async fn handle_trade(&mut self, sid: u32, trade: TradeUpdate) {
+        trace!("Received trade {} in {} on sid {}", trade.trade_id, trade.market_ticker, sid);
+        let message = TradeMessage {
+            trade_id: trade.trade_id,
+            market_ticker: trade.market_ticker,
+            yes_price: trade.yes_price,
+            no_price: trade.no_price,
+            count: trade.count,
+            taker_side: trade.taker_side,
+            ts: trade.ts
+        };
+        if let Err(e) = self.trade_producer.publish(message).await {
+            error!("Failed to publish trade with error {e:?}");
+        }
+    }
+
+    /// Publish a market opening, closing, being determined or settling, so orders and positions on it can follow.
+    async fn handle_market_lifecycle(&mut self, sid: u32, lifecycle: MarketLifecycle) {
+        debug!("Received lifecycle update for {} on sid {}", lifecycle.market_ticker, sid);
+        let ts = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs() as i64);
+        let status = lifecycle.status(ts);
+        if status == MarketStatus::Settled {
+            // a settled market never trades again, so its market data is no longer needed
+            self.remove_market(&lifecycle.market_ticker);
+        }
+        let message = MarketLifecycleMessage {
+            status,
+            result: lifecycle.result.as_deref().and_then(market_data::market_result),
+            market_ticker: lifecycle.market_ticker,
+            close_ts: Some(lifecycle.close_ts),
+            ts
+        };
+        if let Err(e) = self.lifecycle_producer.publish(message).await {
+            error!("Failed to publish market lifecycle update with error {e:?}");
+        }
+    }
+
+    /// Publish a change to one of the account's orders for the order store.
+    async fn handle_order_update(&mut self, sid: u32, update: OrderUpdate) {
+        debug!("Received order update for {} ({}) on sid {}", update.order_id, update.status, sid);
+        let state = match update.state() {
+            Some(state) => state,
+            None => {
+                warn!("Ignoring order update for {} with unknown status {:?}", update.order_id, update.status);
+                return;
+            }
+        };
+        let message = OrderUpdateMessage {
+            order_id: update.order_id,
+            client_order_id: update.client_order_id,
+            ticker: update.ticker,
+            state,
+            yes_price: update.yes_price,
+            filled_count: update.fill_count,
+            remaining_count: update.remaining_count,
+            ts: update.ts
+        };
+        if let Err(e) = self.order_update_producer.publish(message).await {
+            error!("Failed to publish order update with error {e:?}");
+        }
+    }
+
+    /// Report the server's answer to one of our commands, matched to the command by its id.
+    fn handle_response(&mut self, response: KalshiServerMessage) {
+        let cmd = response.id().and_then(|id| self.msg_builder.resolve(id)).unwrap_or("unknown");
+        let index = response.id().and_then(|id| self.market_subscriptions.iter().position(|subscription| subscription.is_request(id)));
+        if let Some(index) = index {
+            match response {
+                KalshiServerMessage::Subscribed { ref msg, .. } => {
+                    if let Some(sub_msg) = self.market_subscriptions[index].on_subscribed(msg.sid) {
+                        self.send_subscription_command(index, sub_msg);
+                    }
+                },
+                KalshiServerMessage::Error { .. } => self.market_subscriptions[index].on_rejected(),
+                _ => warn!("Unexpected response to {} subscription request", self.market_subscriptions[index].channel())
+            }
+        }
+        match response {
+            KalshiServerMessage::Subscribed { id, msg } => info!("Subscribed to {} with sid {} ({} request {})", msg.channel, msg.sid, cmd, id),
+            KalshiServerMessage::Unsubscribed { id, sid } => info!("Unsubscribed from sid {} ({} request {})", sid, cmd, id),
+            KalshiServerMessage::Ok { id, sid, msg } => info!("Kalshi accepted {} request {} for sid {:?}: {:?}", cmd, id, sid, msg),
+            KalshiServerMessage::Error { id, msg } => error!("Kalshi rejected {} request {:?} with code {}: {}", cmd, id, msg.code, msg.msg),
+            other => trace!("Ignoring non-response message {:?}", other)
+        }
+    }
+
+    /// Send a command on the current session, returning its id if it was sent.
+    fn send_command(&mut self, sub_msg: SubMessage) -> Option<u32> {
+        let sender = match self.sender {
+            Some(ref sender) => sender,
+            None => {
+                error!("Cannot send {} command without a websocket session", sub_msg.cmd());
+                return None;
+            }
+        };
+        let message = match self.msg_builder.content(sub_msg).build() {
+            Ok(message) => message,
+            Err(e) => {
+                error!("Failed to build websocket command with error {e:?}");
+                return None;
+            }
+        };
+        match sender.send(message.to_websocket_message()) {
+            Ok(()) => Some(message.id()),
+            Err(e) => {
+                error!("Failed to queue websocket command with error {e:?}");
+                None
+            }
+        }
+    }
+
+    /// Send a command for one of the market data subscriptions, remembering the id of a subscribe to match its response.
+    fn send_subscription_command(&mut self, index: usize, sub_msg: SubMessage) {
+        let is_subscribe = matches!(sub_msg, SubMessage::SubscribeSubMessage(_));
+        let id = self.send_command(sub_msg);
+        if is_subscribe {
+            self.market_subscriptions[index].set_request_id(id);
+        }
+    }
+
+    fn subscription_index(&self, channel: &str) -> Option<usize> {
+        self.market_subscriptions.iter().position(|subscription| subscription.channel() == channel)
+    }
+
+    /// Count messages of types this server does not know, warning the first time each is seen.
+    fn handle_unknown(&mut self, text: &str) {
+        let msg_type = serde_json::from_str::<serde_json::Value>(text).ok()
+            .and_then(|value| value.get("type").and_then(|t| t.as_str()).map(String::from))
+            .unwrap_or_default();
+        let count = self.unknown_types.entry(msg_type.clone()).or_insert(0);
+        *count += 1;
+        if *count == 1 {
+            warn!("Received websocket message of unknown type {:?}", msg_type);
+        } else {
+            debug!("Received {} websocket messages of unknown type {:?}", count, msg_type);
+        }
+    }
+
+    /// Fetch the fills in a time window from Kalshi's REST API and publish those not yet published.
+    async fn backfill_and_publish(&mut self, exchange_client: &KalshiRestClient, min_ts: i64, max_ts: Option<i64>) -> Result<()> {
+        let fills = fill_tracker::backfill(exchange_client, min_ts, max_ts).await?;
+        info!("Fetched {} fills since {} to backfill", fills.len(), min_ts);
+        for fill in fills {
+            journal::append(&self.journal, RecordKind::Fill, BACKFILL_FILL_SOURCE, &fill)?;
+            self.publish_fill(FillMessage::backfilled(fill)).await?;
+        }
+        Ok(())
+    }
+
+    /// Publish a fill to the queue unless a fill with the same trade id has already been published.
+    /// A fill that fails to publish is not remembered, so the backfill after the reconnect the error causes publishes it.
+    async fn publish_fill(&mut self, message: FillMessage) -> Result<()> {
+
+        if self.fill_tracker.is_published(&message.msg) {
+            debug!("Dropping duplicate fill for trade {}", message.msg.trade_id);
+            return Ok(())
+        }
+
+        let fill = message.msg.clone();
+        self.fill_producer.publish(message).await
+            .map_err(|e| e.context(format!("Failed to publish fill for trade {}", fill.trade_id)))?;
+        trace!("Successfully published message to queue");
+        self.fill_tracker.record_fill(&fill);
+        Ok(())
+    }
+}
+
+/// Publish the top levels of a market's book.
+async fn publish_book(producer: &Producer<BookMessage>, market_ticker: &str, book: &OrderBook) {
+    let ts = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs() as i64);
+    if let Err(e) = producer.publish(book.to_message(market_ticker, BOOK_DEPTH_LEVELS, ts)).await {
+        error!("Failed to publish book for {} with error {e:?}", market_ticker);
+    }
+}
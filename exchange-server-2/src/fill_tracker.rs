@@ -0,0 +1,193 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use anyhow::Result;
+
+use kalshi_rest::{FillsQuery, KalshiRestClient};
+use queue_client::queue_data::fills::Fill;
+
+use crate::constants::{BACKFILL_PAGE_SIZE, DEDUP_TRADE_ID_CAPACITY};
+
+/// The result of checking a message's sequence number against its subscription's.
+#[derive(Debug, PartialEq)]
+pub enum SequenceCheck {
+    InOrder,
+    Gap { expected: u32, received: u32 },
+    Stale
+}
+
+/// Tracks the expected sequence number of each fill subscription, the most recent
+/// fill time, the start of the first websocket session, and the trade ids already
+/// published, so that gaps can be backfilled and no fill is published twice.
+pub struct FillTracker {
+    next_seqs: HashMap<u32, u32>,
+    trade_ids: HashSet<String>,
+    trade_id_order: VecDeque<String>,
+    last_fill_ts: Option<i64>,
+    first_connect_ts: Option<i64>
+}
+
+impl FillTracker {
+
+    pub fn new() -> FillTracker {
+        FillTracker {
+            next_seqs: HashMap::new(),
+            trade_ids: HashSet::new(),
+            trade_id_order: VecDeque::new(),
+            last_fill_ts: None,
+            first_connect_ts: None
+        }
+    }
+
+    /// Forget every subscription's sequence, as a new websocket session starts new subscriptions.
+    pub fn reset_sequences(&mut self) {
+        self.next_seqs.clear();
+    }
+
+    /// Check a message's sequence number and advance the subscription past it.
+    /// The first message seen on a subscription is always in order.
+    pub fn check_sequence(&mut self, sid: u32, seq: u32) -> SequenceCheck {
+        let check = match self.next_seqs.get(&sid) {
+            None => SequenceCheck::InOrder,
+            Some(&expected) if seq == expected => SequenceCheck::InOrder,
+            Some(&expected) if seq > expected => SequenceCheck::Gap { expected, received: seq },
+            Some(_) => return SequenceCheck::Stale
+        };
+        self.next_seqs.insert(sid, seq + 1);
+        check
+    }
+
+    /// Whether a fill with the same trade id has already been published.
+    pub fn is_published(&self, fill: &Fill) -> bool {
+        self.trade_ids.contains(&fill.trade_id)
+    }
+
+    /// Record a fill once it has been published, returning false if its trade id already had been.
+    /// A fill that failed to publish is never recorded, so a later backfill publishes it.
+    pub fn record_fill(&mut self, fill: &Fill) -> bool {
+        if !self.trade_ids.insert(fill.trade_id.clone()) {
+            return false;
+        }
+        self.trade_id_order.push_back(fill.trade_id.clone());
+        if self.trade_id_order.len() > DEDUP_TRADE_ID_CAPACITY {
+            if let Some(oldest) = self.trade_id_order.pop_front() {
+                self.trade_ids.remove(&oldest);
+            }
+        }
+        self.last_fill_ts = Some(self.last_fill_ts.map_or(fill.ts, |last| last.max(fill.ts)));
+        true
+    }
+
+    /// Note that a websocket session started at `ts`, in seconds.
+    pub fn record_connect(&mut self, ts: i64) {
+        self.first_connect_ts.get_or_insert(ts);
+    }
+
+    /// Where a backfill has to start to cover every fill not yet published: the time of the
+    /// most recent fill published, or the start of the first session if none has been.
+    pub fn backfill_from(&self) -> Option<i64> {
+        self.last_fill_ts.or(self.first_connect_ts)
+    }
+}
+
+/// Fetch every fill between `min_ts` and `max_ts` (inclusive, in seconds) from
+/// Kalshi's REST API, oldest first.
+pub async fn backfill(exchange_client: &KalshiRestClient, min_ts: i64, max_ts: Option<i64>) -> Result<Vec<Fill>> {
+    let mut fills = Vec::new();
+    let mut cursor = None;
+    loop {
+        let (next_cursor, page) = exchange_client
+            .get_fills(&FillsQuery { min_ts: Some(min_ts), max_ts, limit: Some(BACKFILL_PAGE_SIZE), cursor })
+            .await
+            .map_err(|e| anyhow::anyhow!("Kalshi fill lookup failed: {:?}", e))?;
+        for fill in page {
+            fills.push(Fill::try_from(fill)?);
+        }
+
+        cursor = match next_cursor {
+            Some(next_cursor) if !next_cursor.is_empty() => Some(next_cursor),
+            _ => break
+        };
+    }
+    fills.sort_by_key(|fill| fill.ts);
+    Ok(fills)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kalshi::{Action, Side};
+
+    fn fill(trade_id: &str, ts: i64) -> Fill {
+        Fill {
+            trade_id: trade_id.to_string(),
+            order_id: "order".to_string(),
+            market_ticker: "TICKER".to_string(),
+            is_taker: true,
+            side: Side::Yes,
+            yes_price: 50,
+            no_price: 50,
+            count: 1,
+            action: Action::Buy,
+            ts
+        }
+    }
+
+    #[test]
+    fn sequence_gaps_and_repeats_are_reported_per_subscription() {
+        let mut tracker = FillTracker::new();
+        assert_eq!(tracker.check_sequence(1, 5), SequenceCheck::InOrder);
+        assert_eq!(tracker.check_sequence(1, 6), SequenceCheck::InOrder);
+        assert_eq!(tracker.check_sequence(2, 1), SequenceCheck::InOrder);
+        assert_eq!(tracker.check_sequence(1, 9), SequenceCheck::Gap { expected: 7, received: 9 });
+        assert_eq!(tracker.check_sequence(1, 10), SequenceCheck::InOrder);
+        assert_eq!(tracker.check_sequence(1, 4), SequenceCheck::Stale);
+        assert_eq!(tracker.check_sequence(1, 11), SequenceCheck::InOrder, "a stale message does not move the sequence");
+
+        // a new session's subscriptions start their own sequences
+        tracker.reset_sequences();
+        assert_eq!(tracker.check_sequence(1, 1), SequenceCheck::InOrder);
+    }
+
+    #[test]
+    fn a_trade_id_is_published_once() {
+        let mut tracker = FillTracker::new();
+        assert!(tracker.record_fill(&fill("t1", 10)));
+        assert!(!tracker.record_fill(&fill("t1", 10)));
+        assert!(tracker.record_fill(&fill("t2", 10)));
+    }
+
+    #[test]
+    fn a_fill_counts_as_published_once_recorded() {
+        let mut tracker = FillTracker::new();
+        tracker.record_connect(100);
+        assert!(!tracker.is_published(&fill("t1", 150)));
+        assert_eq!(tracker.backfill_from(), Some(100), "a fill not yet recorded is still backfilled");
+
+        tracker.record_fill(&fill("t1", 150));
+        assert!(tracker.is_published(&fill("t1", 150)));
+        assert_eq!(tracker.backfill_from(), Some(150));
+    }
+
+    #[test]
+    fn only_the_most_recent_trade_ids_are_remembered() {
+        let mut tracker = FillTracker::new();
+        for n in 0..=DEDUP_TRADE_ID_CAPACITY {
+            assert!(tracker.record_fill(&fill(&n.to_string(), 10)));
+        }
+        assert!(tracker.record_fill(&fill("0", 10)), "the oldest trade id has been forgotten");
+        assert!(!tracker.record_fill(&fill(&DEDUP_TRADE_ID_CAPACITY.to_string(), 10)));
+    }
+
+    #[test]
+    fn backfills_start_from_the_last_fill_or_the_first_session() {
+        let mut tracker = FillTracker::new();
+        assert_eq!(tracker.backfill_from(), None);
+
+        tracker.record_connect(100);
+        tracker.record_connect(200);
+        assert_eq!(tracker.backfill_from(), Some(100));
+
+        tracker.record_fill(&fill("t1", 150));
+        tracker.record_fill(&fill("t2", 120));
+        assert_eq!(tracker.backfill_from(), Some(150));
+    }
+}
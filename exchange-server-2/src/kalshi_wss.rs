@@ -1,6 +1,12 @@
-use serde_json;
+use std::collections::HashMap;
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use websocket::Message;
+use tokio_tungstenite::tungstenite::Message;
+
+use kalshi::Side;
+use queue_client::queue_data::fills::Fill;
+use queue_client::queue_data::market_data::MarketStatus;
+use queue_client::queue_data::orders::OrderState;
 
 /// Represents a message to send to the Kalshi websocket server.
 #[derive(Serialize, Deserialize)]
@@ -16,14 +22,19 @@ impl KalshiClientMessage {
     pub fn to_websocket_message(&self) -> Message {
         Message::text(serde_json::to_string(&self).unwrap())
     }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
 }
 
 /// A builder for KalshiClientMessages that keeps track of the 
-/// id to use for each subsequent message.
+/// id to use for each subsequent message, and of the commands
+/// sent that the server has not yet answered.
 pub struct KalshiClientMessageBuilder {
     next_id: u32,
-    cmd: Option<String>,
-    params: Option<KalshiClientSubMessage>
+    params: Option<KalshiClientSubMessage>,
+    pending: HashMap<u32, &'static str>
 }
 
 impl KalshiClientMessageBuilder {
@@ -32,32 +43,41 @@ impl KalshiClientMessageBuilder {
     pub fn new() -> KalshiClientMessageBuilder {
         KalshiClientMessageBuilder {
             next_id: 1, 
-            cmd: None,
-            params: None
+            params: None,
+            pending: HashMap::new()
         }
     }
 
     /// Set the SubMessage for the next message to build.
     pub fn content(&mut self, submsg: KalshiClientSubMessage) -> &mut Self {
-        match submsg {
-            KalshiClientSubMessage::SubscribeSubMessage(ref _msg) => {
-                self.cmd = Some("subscribe".into());
-                self.params = Some(submsg);
-            },
-            _ => {}
-        }
+        self.params = Some(submsg);
         self
     }
 
-    /// Construct a KalshiClientMessage from self's current state
-    pub fn build(&mut self) -> KalshiClientMessage {
+    /// Construct a KalshiClientMessage from self's current state, which must have content set
+    pub fn build(&mut self) -> Result<KalshiClientMessage> {
+        let params = match self.params.take() {
+            Some(params) => params,
+            None => return Err(anyhow::anyhow!("Content must be set before building a message"))
+        };
         let message = KalshiClientMessage {
             id: self.next_id,
-            cmd: self.cmd.clone().unwrap_or_default(),
-            params: self.params.take().unwrap(),
+            cmd: params.cmd().into(),
+            params,
         };
+        self.pending.insert(message.id, message.params.cmd());
         self.next_id += 1;
-        message
+        Ok(message)
+    }
+
+    /// Match a server response to the command it answers, returning that command's name.
+    pub fn resolve(&mut self, id: u32) -> Option<&'static str> {
+        self.pending.remove(&id)
+    }
+
+    /// Forget the commands awaiting a response, which will never arrive once their connection is gone.
+    pub fn clear_pending(&mut self) {
+        self.pending.clear();
     }
 }
 
@@ -68,23 +88,320 @@ impl KalshiClientMessageBuilder {
 /// 
 #[derive(Serialize, Deserialize)]
 #[serde(untagged)]
+#[allow(clippy::enum_variant_names)]
 pub enum KalshiClientSubMessage {
-    SubscribeSubMessage(SubscribeSubMessage)
+    SubscribeSubMessage(SubscribeSubMessage),
+    UnsubscribeSubMessage(UnsubscribeSubMessage),
+    UpdateSubscriptionSubMessage(UpdateSubscriptionSubMessage),
+    ListSubscriptionsSubMessage(ListSubscriptionsSubMessage)
+}
+
+impl KalshiClientSubMessage {
+
+    /// The command under which this sub-message is sent
+    pub fn cmd(&self) -> &'static str {
+        match self {
+            KalshiClientSubMessage::SubscribeSubMessage(_) => "subscribe",
+            KalshiClientSubMessage::UnsubscribeSubMessage(_) => "unsubscribe",
+            KalshiClientSubMessage::UpdateSubscriptionSubMessage(_) => "update_subscription",
+            KalshiClientSubMessage::ListSubscriptionsSubMessage(_) => "list_subscriptions"
+        }
+    }
 }
 
 /// A sub-message representing a subscription request.
 #[derive(Serialize, Deserialize)]
 pub struct SubscribeSubMessage {
-    channels: Vec<String>
+    channels: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    market_tickers: Option<Vec<String>>
 }
 
 impl SubscribeSubMessage {
 
-    /// Construct a new subscription message with the default 'fill' channel
+    /// Construct a new subscription message with the default 'fill' and 'user_orders' channels
     pub fn default() -> SubscribeSubMessage {
-        SubscribeSubMessage { 
-            channels: vec!["fill".into()]
+        SubscribeSubMessage::new(vec!["fill".into(), "user_orders".into(), "market_lifecycle".into()])
+    }
+
+    /// Construct a new subscription message for the given channels across all markets
+    pub fn new(channels: Vec<String>) -> SubscribeSubMessage {
+        SubscribeSubMessage {
+            channels,
+            market_tickers: None
+        }
+    }
+
+    /// Restrict the subscription to the given markets
+    pub fn market_tickers(mut self, market_tickers: Vec<String>) -> SubscribeSubMessage {
+        self.market_tickers = Some(market_tickers);
+        self
+    }
+}
+
+/// A sub-message cancelling the subscriptions with the given ids.
+#[derive(Serialize, Deserialize)]
+pub struct UnsubscribeSubMessage {
+    sids: Vec<u32>
+}
+
+impl UnsubscribeSubMessage {
+
+    pub fn new(sids: Vec<u32>) -> UnsubscribeSubMessage {
+        UnsubscribeSubMessage { sids }
+    }
+}
+
+/// Whether an update adds markets to a subscription or removes them from it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateSubscriptionAction {
+    AddMarkets,
+    DeleteMarkets
+}
+
+/// A sub-message changing the markets an existing subscription covers.
+#[derive(Serialize, Deserialize)]
+pub struct UpdateSubscriptionSubMessage {
+    sids: Vec<u32>,
+    market_tickers: Vec<String>,
+    action: UpdateSubscriptionAction
+}
+
+impl UpdateSubscriptionSubMessage {
+
+    /// Add the given markets to the subscription with id `sid`
+    pub fn add_markets(sid: u32, market_tickers: Vec<String>) -> UpdateSubscriptionSubMessage {
+        UpdateSubscriptionSubMessage {
+            sids: vec![sid],
+            market_tickers,
+            action: UpdateSubscriptionAction::AddMarkets
+        }
+    }
+
+    /// Remove the given markets from the subscription with id `sid`
+    pub fn delete_markets(sid: u32, market_tickers: Vec<String>) -> UpdateSubscriptionSubMessage {
+        UpdateSubscriptionSubMessage {
+            sids: vec![sid],
+            market_tickers,
+            action: UpdateSubscriptionAction::DeleteMarkets
+        }
+    }
+}
+
+/// A sub-message asking the server for every subscription on this connection.
+#[derive(Serialize, Deserialize)]
+pub struct ListSubscriptionsSubMessage {}
+
+impl ListSubscriptionsSubMessage {
+
+    pub fn new() -> ListSubscriptionsSubMessage {
+        ListSubscriptionsSubMessage {}
+    }
+}
+
+/// Every message the Kalshi websocket server sends, tagged by its 'type' field.
+/// Types this server does not know are kept as `Unknown` so they can be counted.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KalshiServerMessage {
+    Fill {
+        sid: u32,
+        seq: u32,
+        msg: Fill
+    },
+    OrderbookSnapshot {
+        sid: u32,
+        seq: u32,
+        msg: OrderbookSnapshot
+    },
+    OrderbookDelta {
+        sid: u32,
+        seq: u32,
+        msg: OrderbookDelta
+    },
+    Ticker {
+        sid: u32,
+        msg: TickerUpdate
+    },
+    Trade {
+        sid: u32,
+        msg: TradeUpdate
+    },
+    MarketLifecycle {
+        sid: u32,
+        msg: MarketLifecycle
+    },
+    UserOrder {
+        sid: u32,
+        msg: OrderUpdate
+    },
+    Subscribed {
+        id: u32,
+        msg: SubscriptionInfo
+    },
+    Unsubscribed {
+        id: u32,
+        sid: u32
+    },
+    Ok {
+        id: u32,
+        #[serde(default)]
+        sid: Option<u32>,
+        #[serde(default)]
+        msg: Option<OkInfo>
+    },
+    Error {
+        #[serde(default)]
+        id: Option<u32>,
+        msg: ErrorInfo
+    },
+    #[serde(other)]
+    Unknown
+}
+
+impl KalshiServerMessage {
+
+    /// The id of the command this message answers, if it is a response the server gave one for
+    pub fn id(&self) -> Option<u32> {
+        match self {
+            KalshiServerMessage::Subscribed { id, .. } => Some(*id),
+            KalshiServerMessage::Unsubscribed { id, .. } => Some(*id),
+            KalshiServerMessage::Ok { id, .. } => Some(*id),
+            KalshiServerMessage::Error { id, .. } => *id,
+            _ => None
+        }
+    }
+}
+
+/// The full book of a market, as lists of [price, quantity] levels for each side.
+#[derive(Deserialize, Debug)]
+pub struct OrderbookSnapshot {
+    pub market_ticker: String,
+    #[serde(default)]
+    pub yes: Vec<(i32, i32)>,
+    #[serde(default)]
+    pub no: Vec<(i32, i32)>
+}
+
+/// A change in the quantity resting at one price level of a market's book.
+#[derive(Deserialize, Debug)]
+pub struct OrderbookDelta {
+    pub market_ticker: String,
+    pub price: i32,
+    pub delta: i32,
+    pub side: Side
+}
+
+/// A summary of a market's price, top of book and activity.
+#[derive(Deserialize, Debug)]
+pub struct TickerUpdate {
+    pub market_ticker: String,
+    pub price: i32,
+    pub yes_bid: i32,
+    pub yes_ask: i32,
+    pub volume: i64,
+    pub open_interest: i64,
+    pub ts: i64
+}
+
+/// A trade between any two participants in a market.
+#[derive(Deserialize, Debug)]
+pub struct TradeUpdate {
+    pub trade_id: String,
+    pub market_ticker: String,
+    pub yes_price: i32,
+    pub no_price: i32,
+    pub count: i32,
+    pub taker_side: Side,
+    pub ts: i64
+}
+
+/// A change in a market's schedule or status, including its determination and settlement.
+#[derive(Deserialize, Debug)]
+pub struct MarketLifecycle {
+    pub market_ticker: String,
+    pub close_ts: i64,
+    #[serde(default)]
+    pub determination_ts: Option<i64>,
+    #[serde(default)]
+    pub settled_ts: Option<i64>,
+    #[serde(default)]
+    pub result: Option<String>,
+    #[serde(default)]
+    pub is_deactivated: bool
+}
+
+impl MarketLifecycle {
+
+    /// The market's status as of `now`, in seconds. Each lifecycle event carries every
+    /// timestamp known so far, so the latest one present is the market's current stage.
+    pub fn status(&self, now: i64) -> MarketStatus {
+        if self.settled_ts.is_some() {
+            MarketStatus::Settled
+        } else if self.determination_ts.is_some() {
+            MarketStatus::Determined
+        } else if self.is_deactivated || now >= self.close_ts {
+            MarketStatus::Closed
+        } else {
+            MarketStatus::Open
+        }
+    }
+}
+
+/// A change to one of the account's orders.
+#[derive(Deserialize, Debug)]
+pub struct OrderUpdate {
+    pub order_id: String,
+    pub client_order_id: String,
+    pub ticker: String,
+    pub status: String,
+    pub yes_price: i32,
+    pub remaining_count: i32,
+    #[serde(default)]
+    pub fill_count: i32,
+    #[serde(default)]
+    pub expiration_ts: Option<i64>,
+    pub ts: i64
+}
+
+impl OrderUpdate {
+
+    /// Translate the order's Kalshi status into the OMS's order states. Kalshi reports
+    /// expired orders as canceled, so a cancel at or after the expiration time is an expiry.
+    pub fn state(&self) -> Option<OrderState> {
+        match self.status.as_str() {
+            "pending" => Some(OrderState::PendingNew),
+            "resting" if self.fill_count > 0 => Some(OrderState::PartiallyFilled),
+            "resting" => Some(OrderState::Open),
+            "canceled" if self.expiration_ts.is_some_and(|expiration_ts| self.ts >= expiration_ts) => Some(OrderState::Expired),
+            "canceled" => Some(OrderState::Cancelled),
+            "executed" => Some(OrderState::Filled),
+            _ => None
         }
     }
 }
 
+/// A subscription on the connection: the channel it covers and its id.
+#[derive(Deserialize, Debug, Clone)]
+pub struct SubscriptionInfo {
+    pub channel: String,
+    pub sid: u32
+}
+
+/// The body of an 'ok' response, which depends on the command it answers. It is only logged.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+#[allow(dead_code)]
+pub enum OkInfo {
+    Subscriptions(Vec<SubscriptionInfo>),
+    MarketTickers { market_tickers: Vec<String> },
+    Other(serde_json::Value)
+}
+
+/// The reason the server gave for refusing a command.
+#[derive(Deserialize, Debug)]
+pub struct ErrorInfo {
+    pub code: u32,
+    pub msg: String
+}
@@ -0,0 +1,255 @@
+use futures::stream::{SplitSink, SplitStream};
+use futures::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::header::{HeaderName, HeaderValue};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::Error as WsError;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use std::sync::Arc;
+use log::{debug, error, info, trace};
+use anyhow::Result;
+use journal::{FsyncPolicy, Journal, JournalConfig};
+use queue_client::consumer::Consumer;
+use queue_client::queues::Queues;
+use queue_client::queue_data::fills::{FeedStatusMessage, FillMessage};
+use queue_client::queue_data::market_data::{BookMessage, MarketLifecycleMessage, MarketSubscriptionMessage, TickerMessage, TradeMessage};
+use queue_client::queue_data::orders::OrderUpdateMessage;
+
+use crate::feed_handler::FeedHandler;
+use crate::kalshi_wss::SubscribeSubMessage;
+use crate::kalshi_wss::KalshiClientMessageBuilder;
+use crate::kalshi_wss::KalshiClientSubMessage as SubMessage;
+use crate::liveness::FeedLiveness;
+
+use kalshi_rest::{Credentials, EnvironmentConfig, KalshiSession};
+use oms_config::OmsConfig;
+
+mod channel_subscription;
+mod constants;
+mod feed_handler;
+mod fill_tracker;
+mod kalshi_wss;
+mod liveness;
+mod order_book;
+
+extern crate kalshi;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// A connected websocket session: the read half, and a channel into the task that
+/// owns the write half.
+struct Session {
+    reader: SplitStream<WsStream>,
+    sender: UnboundedSender<Message>,
+    writer: JoinHandle<()>
+}
+
+/// Run the fill feed: relay fills, order updates and market data from Kalshi's websocket to the queues,
+/// reconnecting whenever it drops, with the queues on the broker or in the process it shares with the client-server.
+pub async fn run(config: OmsConfig, queues: Queues) -> Result<()> {
+
+    // 1. Choose the Kalshi environment, refusing to start if its URLs or credentials belong to the other one
+
+    let environment = config.kalshi.environment_config()?;
+    info!("==== Trading in {} ====", environment);
+    let credentials = Credentials::from_env()?;
+    let mut kalshi = KalshiSession::open(&environment, &credentials, constants::SESSION_RENEW_INTERVAL).await?;
+    info!("Successful instantiation of kalshi exchange client at {} with {}", kalshi.client().base_url(), credentials);
+
+    // 2. Open the journal fills and order updates are recorded in, and create the producers of the feed's queues
+
+    let journal = Journal::open(JournalConfig {
+        dir: constants::JOURNAL_DIR.into(),
+        fsync: FsyncPolicy::Interval(constants::JOURNAL_FSYNC_INTERVAL),
+        max_file_bytes: constants::JOURNAL_MAX_FILE_BYTES
+    })?.shared();
+
+    let fill_producer = queues.producer::<FillMessage>().await?.with_journal(journal.clone());
+    let order_update_producer = queues.producer::<OrderUpdateMessage>().await?.with_journal(journal.clone());
+    let lifecycle_producer = queues.producer::<MarketLifecycleMessage>().await?;
+    let book_producer = queues.producer::<BookMessage>().await?;
+    let trade_producer = queues.producer::<TradeMessage>().await?;
+    let ticker_producer = queues.producer::<TickerMessage>().await?;
+    let feed_status_producer = queues.producer::<FeedStatusMessage>().await?;
+    let market_subscription_consumer = queues.consumer::<MarketSubscriptionMessage>().await?;
+
+    // hand the markets other components want market data for to the websocket session
+    let (market_sender, mut market_receiver) = mpsc::unbounded_channel();
+    tokio::spawn(wait_for_market_subscriptions(market_subscription_consumer, market_sender));
+
+    // 3. Watch the feed, publishing its status so that clients and operators know when fills are stale
+
+    let liveness = Arc::new(FeedLiveness::new());
+    tokio::spawn(liveness::watch(liveness.clone(), feed_status_producer));
+
+    // 4. Loop, reconnecting with backoff whenever the websocket drops
+
+    let mut feed_handler = FeedHandler::new(fill_producer, order_update_producer, lifecycle_producer, book_producer, trade_producer, ticker_producer, journal);
+    let mut backoff = constants::RECONNECT_BACKOFF_INITIAL;
+
+    loop {
+        let result = match connect_and_subscribe(&environment, &mut kalshi, feed_handler.msg_builder()).await {
+            Ok(Session { reader, sender, writer }) => {
+                backoff = constants::RECONNECT_BACKOFF_INITIAL;
+                liveness.set_connected(true);
+                let result = match feed_handler.on_connect(kalshi.client(), sender.clone()).await {
+                    Ok(()) => run_loop(reader, &sender, &mut kalshi, &mut feed_handler, &mut market_receiver, &liveness).await,
+                    Err(e) => Err(e)
+                };
+                match &result {
+                    Ok(()) => info!("Websocket session ended."),
+                    Err(e) => error!("Websocket session failed with error {e:?}")
+                }
+                writer.abort();
+                liveness.set_connected(false);
+                result
+            },
+            Err(e) => {
+                error!("Could not connect to the Kalshi websocket: {e:?}");
+                Err(e)
+            }
+        };
+
+        // an expired token fails every reconnect until the session logs in again
+        if result.as_ref().is_err_and(is_unauthorized) {
+            kalshi.on_unauthorized().await;
+        }
+
+        info!("Reconnecting to the Kalshi websocket in {:?}", backoff);
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(constants::RECONNECT_BACKOFF_MAX);
+    }
+
+}
+
+/// Open a websocket authenticated as the Kalshi session, renewing its token first if it is due, and subscribe to fills,
+/// order updates and market lifecycle events. The write half of the websocket is handed to its own task, fed through the session's sender.
+async fn connect_and_subscribe(environment: &EnvironmentConfig, kalshi: &mut KalshiSession, msg_builder: &mut KalshiClientMessageBuilder) -> Result<Session> {
+
+    // 1. Make sure the session's token will last
+
+    kalshi.renew_if_due().await;
+
+    // 2. Create a new websocket client, authenticated as the REST client, and subscribe to fills
+
+    let mut request = environment.ws_url.as_str().into_client_request()?;
+    for (name, value) in kalshi.client().websocket_headers(&environment.ws_url)? {
+        request.headers_mut().insert(HeaderName::from_static(name), HeaderValue::from_str(&value)?);
+    }
+
+    let (ws_stream, _response) = connect_async(request).await?; // Connects with TLS for wss urls
+    let (ws_sink, reader) = ws_stream.split();
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let writer = tokio::spawn(write_loop(ws_sink, receiver));
+
+    // responses to commands sent on an earlier connection will never arrive
+    msg_builder.clear_pending();
+
+    let account_sub_msg = SubscribeSubMessage::default();
+    let init_sub_msg = msg_builder.content(SubMessage::SubscribeSubMessage(account_sub_msg))
+        .build()?;
+    info!("Sending initial account subscription message: {:?}", serde_json::to_string(&init_sub_msg).unwrap());
+    sender.send(init_sub_msg.to_websocket_message())?;
+
+    Ok(Session { reader, sender, writer })
+}
+
+/// Own the write half of the websocket, sending whatever the rest of the session queues up.
+async fn write_loop(mut ws_sink: SplitSink<WsStream, Message>, mut receiver: UnboundedReceiver<Message>) {
+    while let Some(message) = receiver.recv().await {
+        if let Err(e) = ws_sink.send(message).await {
+            error!("Failed to write to the websocket with error {e:?}");
+            return;
+        }
+    }
+}
+
+/// Relay messages from the websocket until the server closes it or the socket fails.
+async fn run_loop(
+    mut reader: SplitStream<WsStream>,
+    sender: &UnboundedSender<Message>,
+    kalshi: &mut KalshiSession,
+    feed_handler: &mut FeedHandler,
+    markets: &mut UnboundedReceiver<String>,
+    liveness: &FeedLiveness
+) -> Result<()> {
+
+    // the websocket stays open on the token it connected with, but backfills use the REST API's
+    let mut renew_check = tokio::time::interval(constants::SESSION_RENEW_CHECK_INTERVAL);
+    // only traffic on the socket moves the deadline, so the other branches cannot keep a dead one alive
+    let mut last_message_at = Instant::now();
+
+    loop {
+        // 2. Dispatch each message to the handler for its type
+
+        // a silent socket is a dead one, since Kalshi pings connected clients regularly
+        let next_message = tokio::select! {
+            next_message = reader.next() => next_message,
+            _ = tokio::time::sleep_until(last_message_at + constants::FEED_STALE_AFTER) => {
+                return Err(anyhow::anyhow!("No message from the websocket in {:?}", constants::FEED_STALE_AFTER));
+            },
+            Some(ticker) = markets.recv() => {
+                feed_handler.add_market(ticker);
+                continue;
+            },
+            _ = renew_check.tick() => {
+                kalshi.renew_if_due().await;
+                continue;
+            }
+        };
+        let message = match next_message {
+            Some(message) => message?,
+            None => {
+                info!("Websocket stream ended.");
+                return Ok(());
+            }
+        };
+        last_message_at = Instant::now();
+        liveness.record_message();
+
+        match message {
+            Message::Text(s) => {
+                trace!("Handling incoming text");
+                trace!("{s}");
+                feed_handler.handle_text(&s, kalshi.client()).await?;
+            },
+            Message::Binary(_b) => debug!("Received and ignored binary data."),
+            Message::Close(close_frame) => {
+                match close_frame {
+                    Some(close_frame) => info!("Websocket closed by server for reason: {}", close_frame.reason),
+                    None => info!("Websocket closed by server.")
+                }
+                return Ok(());
+            },
+            Message::Ping(data) => {
+                sender.send(Message::Pong(data))?;
+                trace!("Queued pong in response to ping");
+            },
+            Message::Pong(_data) => {}, // as a client, we do not expect to receive pongs
+            Message::Frame(_frame) => {} // raw frames are only produced when writing
+        }
+    }
+}
+
+/// Whether Kalshi refused the session's credentials, on the REST API or the websocket handshake.
+fn is_unauthorized(error: &anyhow::Error) -> bool {
+    kalshi_rest::is_unauthorized(error)
+        || matches!(error.downcast_ref::<WsError>(), Some(WsError::Http(response)) if response.status() == StatusCode::UNAUTHORIZED)
+}
+
+/// Listen to the queues for markets other components want market data for and pass them to the feed.
+async fn wait_for_market_subscriptions(consumer: Consumer<MarketSubscriptionMessage>, markets: UnboundedSender<String>) -> Result<()> {
+    loop {
+        let next_subscription = match consumer.get_next().await? {
+            None => continue,
+            Some(subscription) => subscription
+        };
+        markets.send(next_subscription.market_ticker)?;
+    }
+}
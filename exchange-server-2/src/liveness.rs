@@ -0,0 +1,77 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::{info, warn};
+
+use queue_client::producer::Producer;
+use queue_client::queue_data::fills::FeedStatusMessage;
+
+use crate::constants::{FEED_STALE_AFTER, LIVENESS_CHECK_INTERVAL};
+
+/// Shared record of whether the websocket feed is connected and when it last heard from Kalshi.
+pub struct FeedLiveness {
+    connected: AtomicBool,
+    last_message_ms: AtomicU64
+}
+
+impl FeedLiveness {
+
+    pub fn new() -> FeedLiveness {
+        FeedLiveness {
+            connected: AtomicBool::new(false),
+            last_message_ms: AtomicU64::new(now_ms())
+        }
+    }
+
+    pub fn set_connected(&self, connected: bool) {
+        self.connected.store(connected, Ordering::Relaxed);
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Record that a frame of any kind arrived from Kalshi.
+    pub fn record_message(&self) {
+        self.last_message_ms.store(now_ms(), Ordering::Relaxed);
+    }
+
+    /// Milliseconds since the last frame arrived from Kalshi.
+    pub fn silent_for_ms(&self) -> u64 {
+        now_ms().saturating_sub(self.last_message_ms.load(Ordering::Relaxed))
+    }
+
+    /// Fills are stale when the feed is down or has been silent for too long,
+    /// since Kalshi pings connected clients well within that window.
+    pub fn is_stale(&self) -> bool {
+        !self.is_connected() || self.silent_for_ms() > FEED_STALE_AFTER.as_millis() as u64
+    }
+
+    pub fn status(&self) -> FeedStatusMessage {
+        FeedStatusMessage { connected: self.is_connected(), stale: self.is_stale(), silent_for_ms: self.silent_for_ms() }
+    }
+}
+
+/// Periodically check the feed's liveness and publish it, so that the client-server can tell its
+/// clients when fills are stale, warning operators for as long as they are.
+pub async fn watch(liveness: Arc<FeedLiveness>, status_producer: Producer<FeedStatusMessage>) {
+    let mut was_stale = false;
+    loop {
+        tokio::time::sleep(LIVENESS_CHECK_INTERVAL).await;
+        let status = liveness.status();
+        if let Err(e) = status_producer.publish(status.clone()).await {
+            warn!("Failed to publish the fill feed's status: {:?}", e);
+        }
+        let stale = status.stale;
+        if stale {
+            warn!("Fill feed is stale: connected={}, last message {}ms ago.", liveness.is_connected(), liveness.silent_for_ms());
+        } else if was_stale {
+            info!("Fill feed has recovered.");
+        }
+        was_stale = stale;
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_millis() as u64)
+}
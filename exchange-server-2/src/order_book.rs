@@ -0,0 +1,123 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use kalshi::Side;
+use queue_client::queue_data::market_data::{BookMessage, PriceLevel};
+
+use crate::kalshi_wss::{OrderbookDelta, OrderbookSnapshot};
+
+/// Why a book update could not be applied. Any of these means the local books
+/// no longer match Kalshi's and must be rebuilt from fresh snapshots.
+#[derive(Debug)]
+pub enum BookError {
+    Gap { expected: u32, received: u32 },
+    UnknownMarket(String),
+    NegativeQuantity { market_ticker: String, price: i32 }
+}
+
+impl fmt::Display for BookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BookError::Gap { expected, received } => write!(f, "expected seq {}, received {}", expected, received),
+            BookError::UnknownMarket(market_ticker) => write!(f, "delta for {} before its snapshot", market_ticker),
+            BookError::NegativeQuantity { market_ticker, price } => write!(f, "negative quantity in {} at {}", market_ticker, price)
+        }
+    }
+}
+
+/// One market's resting quantity at each price, for YES bids and NO bids.
+#[derive(Default)]
+pub struct OrderBook {
+    yes: BTreeMap<i32, i32>,
+    no: BTreeMap<i32, i32>
+}
+
+impl OrderBook {
+
+    fn from_snapshot(snapshot: &OrderbookSnapshot) -> OrderBook {
+        OrderBook {
+            yes: snapshot.yes.iter().filter(|(_, quantity)| *quantity > 0).copied().collect(),
+            no: snapshot.no.iter().filter(|(_, quantity)| *quantity > 0).copied().collect()
+        }
+    }
+
+    fn apply_delta(&mut self, delta: &OrderbookDelta) -> Result<(), BookError> {
+        let side = match delta.side {
+            Side::Yes => &mut self.yes,
+            Side::No => &mut self.no
+        };
+        let quantity = side.get(&delta.price).copied().unwrap_or(0) + delta.delta;
+        if quantity < 0 {
+            return Err(BookError::NegativeQuantity { market_ticker: delta.market_ticker.clone(), price: delta.price });
+        }
+        if quantity == 0 {
+            side.remove(&delta.price);
+        } else {
+            side.insert(delta.price, quantity);
+        }
+        Ok(())
+    }
+
+    /// The best `depth` levels of each side, highest bid first.
+    pub fn to_message(&self, market_ticker: &str, depth: usize, ts: i64) -> BookMessage {
+        let levels = |side: &BTreeMap<i32, i32>| side.iter().rev()
+            .take(depth)
+            .map(|(&price, &quantity)| PriceLevel { price, quantity })
+            .collect();
+        BookMessage {
+            market_ticker: market_ticker.to_string(),
+            yes_bids: levels(&self.yes),
+            no_bids: levels(&self.no),
+            ts
+        }
+    }
+}
+
+/// The books of every market on an orderbook subscription. Snapshots and deltas share
+/// the subscription's sequence numbers, so any skipped or repeated number invalidates them all.
+pub struct OrderBooks {
+    books: HashMap<String, OrderBook>,
+    next_seqs: HashMap<u32, u32>
+}
+
+impl OrderBooks {
+
+    pub fn new() -> OrderBooks {
+        OrderBooks {
+            books: HashMap::new(),
+            next_seqs: HashMap::new()
+        }
+    }
+
+    /// Forget every book and sequence, as when the subscription is replaced.
+    pub fn clear(&mut self) {
+        self.books.clear();
+        self.next_seqs.clear();
+    }
+
+    /// Replace a market's book with a snapshot.
+    pub fn apply_snapshot(&mut self, sid: u32, seq: u32, snapshot: &OrderbookSnapshot) -> Result<&OrderBook, BookError> {
+        self.check_sequence(sid, seq)?;
+        self.books.insert(snapshot.market_ticker.clone(), OrderBook::from_snapshot(snapshot));
+        Ok(&self.books[&snapshot.market_ticker])
+    }
+
+    /// Apply a change in quantity at one price level to a market's book.
+    pub fn apply_delta(&mut self, sid: u32, seq: u32, delta: &OrderbookDelta) -> Result<&OrderBook, BookError> {
+        self.check_sequence(sid, seq)?;
+        let book = self.books.get_mut(&delta.market_ticker)
+            .ok_or_else(|| BookError::UnknownMarket(delta.market_ticker.clone()))?;
+        book.apply_delta(delta)?;
+        Ok(book)
+    }
+
+    fn check_sequence(&mut self, sid: u32, seq: u32) -> Result<(), BookError> {
+        match self.next_seqs.get(&sid) {
+            Some(&expected) if seq != expected => Err(BookError::Gap { expected, received: seq }),
+            _ => {
+                self.next_seqs.insert(sid, seq + 1);
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+use mock_kalshi::{MockConfig, Script, ScriptStep, API_PREFIX};
+use oms_config::OmsConfig;
+use queue_client::queue_data::fills::{FeedStatusMessage, FillMessage};
+use queue_client::queues::Queues;
+
+const FEED_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// A fresh working directory for the feed, whose journal is written relative to it.
+struct TestDir(PathBuf);
+
+impl TestDir {
+    fn enter() -> TestDir {
+        let dir = std::env::temp_dir().join(format!("exchange-server-2-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+        TestDir(dir)
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Start the mock exchange on any free ports, playing `script` to the feed, and return the
+/// base URLs of its REST API and websocket.
+async fn start_mock_kalshi(script: Vec<ScriptStep>) -> (String, String) {
+    let rest_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let ws_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let api_url = format!("http://{}{}", rest_listener.local_addr().unwrap(), API_PREFIX);
+    let ws_url = format!("ws://{}/trade-api/ws/v2", ws_listener.local_addr().unwrap());
+    tokio::spawn(mock_kalshi::serve(rest_listener, ws_listener, MockConfig::default(), Arc::new(Script::new(script))));
+    (api_url, ws_url)
+}
+
+fn send_fill(trade_id: &str, ts: i64, drop: bool) -> serde_json::Value {
+    json!({
+        "step": "send",
+        "type": "fill",
+        "drop": drop,
+        "msg": {
+            "trade_id": trade_id,
+            "order_id": "order-1",
+            "market_ticker": "TICKER",
+            "is_taker": true,
+            "side": "yes",
+            "yes_price": 40,
+            "no_price": 60,
+            "count": 1,
+            "action": "buy",
+            "ts": ts
+        }
+    })
+}
+
+#[tokio::test]
+async fn the_feed_publishes_every_fill_once_backfilling_gaps_and_reconnects() {
+    let _dir = TestDir::enter();
+    std::env::set_var("KALSHI_EMAIL", "trader@example.com");
+    std::env::set_var("KALSHI_PASSWORD", "password");
+
+    // t2 is lost to a sequence gap the next fill shows up, and t4 to a disconnect
+    let script = serde_json::from_value(json!([
+        {"step": "wait", "ms": 200},
+        send_fill("t1", 1_700_000_000, false),
+        send_fill("t2", 1_700_000_001, true),
+        send_fill("t3", 1_700_000_002, false),
+        send_fill("t4", 1_700_000_003, true),
+        {"step": "disconnect"}
+    ])).unwrap();
+    let (api_url, ws_url) = start_mock_kalshi(script).await;
+
+    let mut config = OmsConfig::default();
+    config.kalshi.api_url = Some(api_url);
+    config.kalshi.ws_url = Some(ws_url);
+    let queues = Queues::in_process(config.broker.queues.clone());
+    let feed = tokio::spawn(kalshi_exchange_server_2::run(config, queues.clone()));
+    let fills = queues.consumer::<FillMessage>().await.unwrap();
+    let feed_status = queues.consumer::<FeedStatusMessage>().await.unwrap();
+
+    let mut published: HashMap<String, usize> = HashMap::new();
+    let collect = async {
+        while published.len() < 4 {
+            if let Some(fill) = fills.get_next().await.unwrap() {
+                *published.entry(fill.msg.trade_id).or_default() += 1;
+            }
+        }
+    };
+    tokio::time::timeout(FEED_TIMEOUT, collect).await.expect("the feed did not publish every fill");
+
+    // backfills list fills already published too, which must not be published again
+    let repeats = tokio::time::timeout(Duration::from_millis(500), fills.get_next()).await;
+    assert!(!matches!(repeats, Ok(Ok(Some(_)))));
+    assert_eq!(published, HashMap::from([("t1", 1), ("t2", 1), ("t3", 1), ("t4", 1)].map(|(id, n)| (id.to_string(), n))));
+
+    // the feed publishes its status for the client-server, live again once it has reconnected
+    let live = async {
+        loop {
+            if let Some(status) = feed_status.get_next().await.unwrap() {
+                if status.connected && !status.stale {
+                    break;
+                }
+            }
+        }
+    };
+    tokio::time::timeout(FEED_TIMEOUT, live).await.expect("the feed did not publish that it is live");
+
+    assert!(!feed.is_finished());
+    feed.abort();
+}
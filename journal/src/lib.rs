@@ -0,0 +1,6 @@
+pub mod record;
+pub mod writer;
+pub mod reader;
+
+pub use record::{Record, RecordKind};
+pub use writer::{append, append_bytes, FsyncPolicy, Journal, JournalConfig, SharedJournal};
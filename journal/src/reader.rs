@@ -0,0 +1,68 @@
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::record::Record;
+
+const FILE_PREFIX: &str = "journal-";
+const FILE_SUFFIX: &str = ".log";
+pub(crate) const LENGTH_PREFIX_SIZE: usize = 4;
+
+/// Read every record in the journal directory, oldest first. A record cut short by a crash
+/// mid-write ends its file; everything written before it is returned.
+pub fn read_all(dir: &Path) -> Result<Vec<Record>> {
+    let mut records = Vec::new();
+    for path in journal_files(dir)? {
+        records.extend(read_file(&path)?.0);
+    }
+    Ok(records)
+}
+
+/// Read the records written after `seq`, oldest first.
+pub fn read_after(dir: &Path, seq: u64) -> Result<Vec<Record>> {
+    Ok(read_all(dir)?.into_iter().filter(|record| record.seq > seq).collect())
+}
+
+/// The name of the journal file whose first record is `first_seq`. Sequence numbers are
+/// zero-padded so the files sort in the order they were written.
+pub(crate) fn file_name(first_seq: u64) -> String {
+    format!("{FILE_PREFIX}{first_seq:020}{FILE_SUFFIX}")
+}
+
+/// The journal files in a directory, oldest first. A missing directory holds no files.
+pub(crate) fn journal_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(FILE_PREFIX) && name.ends_with(FILE_SUFFIX)))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Read the complete records in one journal file, along with the length in bytes they take
+/// up, which is where the next record belongs.
+pub(crate) fn read_file(path: &Path) -> Result<(Vec<Record>, u64)> {
+    let bytes = fs::read(path)?;
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while offset + LENGTH_PREFIX_SIZE <= bytes.len() {
+        let mut length = [0u8; LENGTH_PREFIX_SIZE];
+        length.copy_from_slice(&bytes[offset..offset + LENGTH_PREFIX_SIZE]);
+        let start = offset + LENGTH_PREFIX_SIZE;
+        let end = start + u32::from_le_bytes(length) as usize;
+        if end > bytes.len() {
+            break;
+        }
+        match bincode::deserialize::<Record>(&bytes[start..end]) {
+            Ok(record) => records.push(record),
+            Err(_) => break
+        }
+        offset = end;
+    }
+    Ok((records, offset as u64))
+}
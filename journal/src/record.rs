@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+/// What crossed the OMS boundary to produce a journal record
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    /// A message read from a trading client's connection
+    ClientFrame,
+    /// A message published to a queue
+    QueuePublish,
+    /// A message taken off a queue, recorded before it is acknowledged
+    QueueConsume,
+    /// A request about to be sent to the exchange
+    ExchangeRequest,
+    /// The exchange's answer to a request
+    ExchangeResponse,
+    /// A fill reported by the exchange
+    Fill
+}
+
+/// One entry in the journal. `seq` increases by one with every record a journal writes,
+/// across file rotations, and `ts` is in microseconds since the epoch. `source` names where
+/// the message came from or went to (a client, a queue or an exchange endpoint), and
+/// `payload` is the message itself, bincode-encoded.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Record {
+    pub seq: u64,
+    pub ts: i64,
+    pub kind: RecordKind,
+    pub source: String,
+    pub payload: Vec<u8>
+}
+
+impl Record {
+
+    /// Decode the payload as the message type it was written from
+    pub fn decode<T: serde::de::DeserializeOwned>(&self) -> anyhow::Result<T> {
+        Ok(bincode::deserialize(&self.payload)?)
+    }
+}
@@ -0,0 +1,291 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::reader::{self, LENGTH_PREFIX_SIZE};
+use crate::record::{Record, RecordKind};
+
+/// A journal shared by every task in a process that writes to it
+pub type SharedJournal = Arc<Mutex<Journal>>;
+
+/// When the journal forces what it has written to disk. Every record is handed to the
+/// operating system as soon as it is appended, so only a machine crash can lose records
+/// that have not been synced.
+#[derive(Debug, Clone, Copy)]
+pub enum FsyncPolicy {
+    /// Sync after every record. Nothing acknowledged is ever lost, at the cost of a disk flush per record.
+    Always,
+    /// Sync when a record is appended at least this long after the last sync.
+    Interval(Duration),
+    /// Leave syncing to the operating system.
+    Never
+}
+
+/// Where a journal lives and how it is written
+#[derive(Debug, Clone)]
+pub struct JournalConfig {
+    pub dir: PathBuf,
+    pub fsync: FsyncPolicy,
+    /// A new file is started once the current one grows past this size
+    pub max_file_bytes: u64
+}
+
+/// An append-only journal of length-prefixed, bincode-encoded records, split across
+/// files that are rotated by size and named by the sequence number of their first record.
+pub struct Journal {
+    config: JournalConfig,
+    file: File,
+    file_bytes: u64,
+    next_seq: u64,
+    last_sync: Instant
+}
+
+impl Journal {
+
+    /// Open the journal in the configured directory, creating it if needed, and continue
+    /// the sequence from its last record. A record left half-written by a crash is cut off.
+    pub fn open(config: JournalConfig) -> Result<Journal> {
+        fs::create_dir_all(&config.dir)?;
+
+        let (path, file_bytes, next_seq) = match reader::journal_files(&config.dir)?.pop() {
+            None => (config.dir.join(reader::file_name(1)), 0, 1),
+            Some(path) => {
+                let (records, valid_bytes) = reader::read_file(&path)?;
+                let next_seq = match records.last() {
+                    Some(record) => record.seq + 1,
+                    None => first_seq_of(&path).unwrap_or(1)
+                };
+                (path, valid_bytes, next_seq)
+            }
+        };
+
+        let file = OpenOptions::new().create(true).write(true).truncate(false).open(&path)?;
+        file.set_len(file_bytes)?;
+        let mut journal = Journal {
+            config,
+            file,
+            file_bytes,
+            next_seq,
+            last_sync: Instant::now()
+        };
+        journal.seek_to_end()?;
+        Ok(journal)
+    }
+
+    /// Wrap the journal for sharing between tasks
+    pub fn shared(self) -> SharedJournal {
+        Arc::new(Mutex::new(self))
+    }
+
+    /// The sequence number the next record will be written with
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Append a message to the journal, returning the sequence number it was written with.
+    pub fn append<T: Serialize>(&mut self, kind: RecordKind, source: &str, message: &T) -> Result<u64> {
+        self.append_bytes(kind, source, bincode::serialize(message)?)
+    }
+
+    /// Append an already-encoded message to the journal, returning its sequence number.
+    pub fn append_bytes(&mut self, kind: RecordKind, source: &str, payload: Vec<u8>) -> Result<u64> {
+        let record = Record {
+            seq: self.next_seq,
+            ts: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_micros() as i64),
+            kind,
+            source: source.to_string(),
+            payload
+        };
+        let body = bincode::serialize(&record)?;
+
+        if self.file_bytes > 0 && self.file_bytes + (LENGTH_PREFIX_SIZE + body.len()) as u64 > self.config.max_file_bytes {
+            self.rotate()?;
+        }
+
+        // one write per record, so a crash can only ever tear the last one
+        let mut frame = Vec::with_capacity(LENGTH_PREFIX_SIZE + body.len());
+        frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&body);
+        self.file.write_all(&frame)?;
+        self.file_bytes += frame.len() as u64;
+        self.next_seq += 1;
+
+        match self.config.fsync {
+            FsyncPolicy::Always => self.sync()?,
+            FsyncPolicy::Interval(interval) if self.last_sync.elapsed() >= interval => self.sync()?,
+            FsyncPolicy::Interval(_) | FsyncPolicy::Never => {}
+        }
+        Ok(record.seq)
+    }
+
+    /// Force everything written so far to disk.
+    pub fn sync(&mut self) -> Result<()> {
+        self.file.sync_data()?;
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    /// Close out the current file and start a new one at the next sequence number.
+    fn rotate(&mut self) -> Result<()> {
+        self.sync()?;
+        let path = self.config.dir.join(reader::file_name(self.next_seq));
+        self.file = OpenOptions::new().create(true).append(true).open(path)?;
+        self.file_bytes = 0;
+        // make the new file's directory entry durable too
+        File::open(&self.config.dir)?.sync_all()?;
+        Ok(())
+    }
+
+    fn seek_to_end(&mut self) -> Result<()> {
+        self.file.seek(SeekFrom::Start(self.file_bytes))?;
+        Ok(())
+    }
+}
+
+/// Append a message to a journal shared between tasks, returning its sequence number.
+pub fn append<T: Serialize>(journal: &SharedJournal, kind: RecordKind, source: &str, message: &T) -> Result<u64> {
+    lock(journal)?.append(kind, source, message)
+}
+
+/// Append an already-encoded message to a journal shared between tasks, returning its sequence number.
+pub fn append_bytes(journal: &SharedJournal, kind: RecordKind, source: &str, payload: Vec<u8>) -> Result<u64> {
+    lock(journal)?.append_bytes(kind, source, payload)
+}
+
+fn lock(journal: &SharedJournal) -> Result<MutexGuard<'_, Journal>> {
+    journal.lock().map_err(|_| anyhow::anyhow!("A writer panicked while holding the journal"))
+}
+
+/// The sequence number a journal file was started at, from its name.
+fn first_seq_of(path: &Path) -> Option<u64> {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.rsplit('-').next())
+        .and_then(|seq| seq.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    static NEXT_DIR: AtomicU32 = AtomicU32::new(0);
+
+    /// A fresh journal directory for one test, removed when it is dropped.
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new() -> TestDir {
+            let name = format!("journal-test-{}-{}", std::process::id(), NEXT_DIR.fetch_add(1, Ordering::SeqCst));
+            let dir = std::env::temp_dir().join(name);
+            let _ = fs::remove_dir_all(&dir);
+            TestDir(dir)
+        }
+
+        fn config(&self, max_file_bytes: u64) -> JournalConfig {
+            JournalConfig { dir: self.0.clone(), fsync: FsyncPolicy::Never, max_file_bytes }
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn records_read_back_in_order() {
+        let dir = TestDir::new();
+        let mut journal = Journal::open(dir.config(1024 * 1024)).unwrap();
+        assert_eq!(journal.append(RecordKind::ClientFrame, "client", &"first".to_string()).unwrap(), 1);
+        assert_eq!(journal.append(RecordKind::QueuePublish, "orders", &42u32).unwrap(), 2);
+        assert_eq!(journal.append_bytes(RecordKind::Fill, "fills", vec![1, 2, 3]).unwrap(), 3);
+
+        let records = reader::read_all(&dir.0).unwrap();
+        assert_eq!(records.iter().map(|record| record.seq).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(records[0].kind, RecordKind::ClientFrame);
+        assert_eq!(records[0].source, "client");
+        assert_eq!(records[0].decode::<String>().unwrap(), "first");
+        assert_eq!(records[1].decode::<u32>().unwrap(), 42);
+        assert_eq!(records[2].payload, vec![1, 2, 3]);
+        assert_eq!(reader::read_after(&dir.0, 2).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn reopening_continues_the_sequence() {
+        let dir = TestDir::new();
+        {
+            let mut journal = Journal::open(dir.config(1024 * 1024)).unwrap();
+            journal.append(RecordKind::Fill, "fills", &1u32).unwrap();
+            journal.append(RecordKind::Fill, "fills", &2u32).unwrap();
+        }
+        let mut journal = Journal::open(dir.config(1024 * 1024)).unwrap();
+        assert_eq!(journal.next_seq(), 3);
+        journal.append(RecordKind::Fill, "fills", &3u32).unwrap();
+
+        let records = reader::read_all(&dir.0).unwrap();
+        assert_eq!(records.iter().map(|record| record.decode::<u32>().unwrap()).collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn files_rotate_by_size_and_are_named_by_their_first_record() {
+        let dir = TestDir::new();
+        let mut journal = Journal::open(dir.config(100)).unwrap();
+        for n in 0..10u64 {
+            journal.append(RecordKind::QueueConsume, "orders", &n).unwrap();
+        }
+
+        let files = reader::journal_files(&dir.0).unwrap();
+        assert!(files.len() > 1, "expected the journal to rotate, got {:?}", files);
+        for path in files.iter() {
+            let (records, _) = reader::read_file(path).unwrap();
+            assert_eq!(first_seq_of(path), records.first().map(|record| record.seq));
+            assert!(fs::metadata(path).unwrap().len() <= 100 || records.len() == 1);
+        }
+        let records = reader::read_all(&dir.0).unwrap();
+        assert_eq!(records.iter().map(|record| record.seq).collect::<Vec<_>>(), (1..=10).collect::<Vec<_>>());
+
+        // a reopened journal carries on in the newest file
+        drop(journal);
+        let mut journal = Journal::open(dir.config(100)).unwrap();
+        assert_eq!(journal.append(RecordKind::QueueConsume, "orders", &10u64).unwrap(), 11);
+        assert_eq!(reader::read_all(&dir.0).unwrap().len(), 11);
+    }
+
+    #[test]
+    fn a_torn_last_record_is_ignored_and_then_overwritten() {
+        let dir = TestDir::new();
+        {
+            let mut journal = Journal::open(dir.config(1024 * 1024)).unwrap();
+            journal.append(RecordKind::Fill, "fills", &1u32).unwrap();
+            journal.append(RecordKind::Fill, "fills", &2u32).unwrap();
+        }
+
+        // a crash mid-write leaves a length prefix promising more than was written
+        let path = reader::journal_files(&dir.0).unwrap().pop().unwrap();
+        let valid_bytes = fs::metadata(&path).unwrap().len();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&100u32.to_le_bytes()).unwrap();
+        file.write_all(&[7, 7, 7]).unwrap();
+        drop(file);
+
+        let records = reader::read_all(&dir.0).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(reader::read_file(&path).unwrap().1, valid_bytes);
+
+        let mut journal = Journal::open(dir.config(1024 * 1024)).unwrap();
+        assert_eq!(journal.append(RecordKind::Fill, "fills", &3u32).unwrap(), 3);
+        let records = reader::read_all(&dir.0).unwrap();
+        assert_eq!(records.iter().map(|record| record.decode::<u32>().unwrap()).collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn a_missing_directory_reads_as_empty() {
+        let dir = TestDir::new();
+        assert!(reader::read_all(&dir.0).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,51 @@
+use anyhow::Result;
+use tokio::task::JoinError;
+use tracing::info;
+use tracing_subscriber::EnvFilter;
+
+use oms_config::OmsConfig;
+use queue_client::queues::Queues;
+
+/// The whole OMS in one process: the client-server, the order gateway and the fill feed run
+/// as tasks joined by in-process queues, so an order never leaves the process on its way to
+/// the gateway. For single-host deployments that want the lowest latency; the separate
+/// binaries, joined by RabbitMQ, are still there for deployments spread across hosts.
+#[tokio::main]
+async fn main() -> Result<()> {
+
+    // Load the config every OMS binary shares, and log at its level. The exchange servers log
+    // through the `log` crate, whose records the subscriber picks up along with its own.
+
+    let config = OmsConfig::load()?;
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_new(&config.logging.level)?)
+        .init();
+    info!("Loaded config from {}", config);
+
+    // 1. Join the components by queues in this process; the broker's URL goes unused
+
+    let queues = Queues::in_process(config.broker.queues.clone());
+
+    // 2. Start each component as a task. Messages published before their consumer starts wait on its queue
+
+    let mut gateway = tokio::spawn(kalshi_exchange_server_1::run(config.clone(), queues.clone()));
+    let mut fill_feed = tokio::spawn(kalshi_exchange_server_2::run(config.clone(), queues.clone()));
+    let mut client_server = tokio::spawn(client_server::run(config, queues));
+    info!("Running the client-server, order gateway and fill feed in one process");
+
+    // 3. Stop the OMS as soon as any component stops, since the others cannot trade without it
+
+    tokio::select! {
+        result = &mut client_server => stopped("client-server", result),
+        result = &mut gateway => stopped("order gateway", result),
+        result = &mut fill_feed => stopped("fill feed", result)
+    }
+}
+
+fn stopped(component: &str, result: Result<Result<()>, JoinError>) -> Result<()> {
+    match result {
+        Ok(Ok(())) => Err(anyhow::anyhow!("The {} stopped", component)),
+        Ok(Err(e)) => Err(e.context(format!("The {} failed", component))),
+        Err(e) => Err(anyhow::anyhow!("The {} panicked: {}", component, e))
+    }
+}
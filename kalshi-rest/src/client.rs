@@ -0,0 +1,219 @@
+use anyhow::Result;
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+
+use queue_client::queue_data::orders::CreateOrderMessage;
+
+use crate::credentials::{ApiKey, Credentials};
+use crate::models::{ExchangeFill, ExchangeMarket, ExchangeOrder, FillsQuery, MarketPosition, OrdersQuery};
+
+#[derive(Deserialize)]
+struct LoginResponse {
+    token: String
+}
+
+#[derive(Deserialize)]
+struct OrderResponse {
+    order: ExchangeOrder
+}
+
+#[derive(Deserialize)]
+struct CancelResponse {
+    order: ExchangeOrder,
+    reduced_by: i32
+}
+
+#[derive(Deserialize)]
+struct MarketResponse {
+    market: ExchangeMarket
+}
+
+#[derive(Deserialize)]
+struct OrdersResponse {
+    orders: Vec<ExchangeOrder>,
+    cursor: Option<String>
+}
+
+#[derive(Deserialize)]
+struct FillsResponse {
+    fills: Vec<ExchangeFill>,
+    cursor: Option<String>
+}
+
+#[derive(Deserialize)]
+struct PositionsResponse {
+    market_positions: Vec<MarketPosition>,
+    cursor: Option<String>
+}
+
+#[derive(Serialize)]
+struct PageQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cursor: Option<String>
+}
+
+/// A client for the parts of Kalshi's v2 REST API the OMS uses, against whichever base URL
+/// it is given: production, demo, or the mock exchange for tests.
+pub struct KalshiRestClient {
+    base_url: String,
+    http: Client,
+    token: Option<String>,
+    api_key: Option<ApiKey>
+}
+
+impl KalshiRestClient {
+    pub fn new(base_url: &str) -> KalshiRestClient {
+        KalshiRestClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http: Client::new(),
+            token: None,
+            api_key: None
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// The session token from the last login, which the websocket feed authenticates with too.
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    /// Authenticate with either kind of credentials. An API key is checked with a request
+    /// signed by it, so a wrong key fails here rather than on the first order.
+    pub async fn authenticate(&mut self, credentials: &Credentials) -> Result<()> {
+        match credentials {
+            Credentials::Password { email, password } => self.login(email, password.expose()).await,
+            Credentials::ApiKey(api_key) => {
+                self.api_key = Some(api_key.as_ref().clone());
+                self.get_positions(Some(1), None).await.map(|_| ())
+            }
+        }
+    }
+
+    /// The headers that authenticate a websocket connection to `ws_url` as this client.
+    pub fn websocket_headers(&self, ws_url: &str) -> Result<Vec<(&'static str, String)>> {
+        if let Some(api_key) = &self.api_key {
+            return Ok(api_key.headers(Method::GET.as_str(), Url::parse(ws_url)?.path()));
+        }
+        let token = self.token.as_ref().ok_or_else(|| anyhow::anyhow!("Not logged in to Kalshi"))?;
+        Ok(vec![("authorization", token.clone())])
+    }
+
+    pub async fn login(&mut self, email: &str, password: &str) -> Result<()> {
+        let response = self.http.post(self.url("/login"))
+            .json(&json!({ "email": email, "password": password }))
+            .send()
+            .await?;
+        let login: LoginResponse = parse(response).await?;
+        self.token = Some(login.token);
+        Ok(())
+    }
+
+    pub async fn create_order(&self, order: &CreateOrderMessage) -> Result<ExchangeOrder> {
+        let mut body = json!({
+            "action": order.action,
+            "client_order_id": order.client_order_id,
+            "count": order.count,
+            "side": order.side,
+            "ticker": order.ticker,
+            "type": order.input_type,
+            "buy_max_cost": order.buy_max_cost,
+            "expiration_ts": order.expiration_ts,
+            "no_price": order.no_price,
+            "sell_position_floor": order.sell_position_floor,
+            "yes_price": order.yes_price
+        });
+        // Kalshi wants fields that do not apply left out rather than null
+        if let Value::Object(ref mut fields) = body {
+            fields.retain(|_, value| !value.is_null());
+        }
+        let response = self.request(Method::POST, "/portfolio/orders")?.json(&body).send().await?;
+        Ok(parse::<OrderResponse>(response).await?.order)
+    }
+
+    /// Cancel an order, returning it and how many contracts the cancel took off the book.
+    pub async fn cancel_order(&self, order_id: &str) -> Result<(ExchangeOrder, i32)> {
+        let response = self.request(Method::DELETE, &format!("/portfolio/orders/{}", order_id))?.send().await?;
+        let cancel: CancelResponse = parse(response).await?;
+        Ok((cancel.order, cancel.reduced_by))
+    }
+
+    /// One page of the account's orders, and the cursor for the next page.
+    pub async fn get_orders(&self, query: &OrdersQuery) -> Result<(Option<String>, Vec<ExchangeOrder>)> {
+        let response = self.request(Method::GET, "/portfolio/orders")?.query(query).send().await?;
+        let orders: OrdersResponse = parse(response).await?;
+        Ok((orders.cursor, orders.orders))
+    }
+
+    /// One page of the account's fills, and the cursor for the next page.
+    pub async fn get_fills(&self, query: &FillsQuery) -> Result<(Option<String>, Vec<ExchangeFill>)> {
+        let response = self.request(Method::GET, "/portfolio/fills")?.query(query).send().await?;
+        let fills: FillsResponse = parse(response).await?;
+        Ok((fills.cursor, fills.fills))
+    }
+
+    /// One page of the account's positions by market, and the cursor for the next page.
+    pub async fn get_positions(&self, limit: Option<i32>, cursor: Option<String>) -> Result<(Option<String>, Vec<MarketPosition>)> {
+        let query = PageQuery { limit, cursor };
+        let response = self.request(Method::GET, "/portfolio/positions")?.query(&query).send().await?;
+        let positions: PositionsResponse = parse(response).await?;
+        Ok((positions.cursor, positions.market_positions))
+    }
+
+    /// A market's status and close time.
+    pub async fn get_market(&self, ticker: &str) -> Result<ExchangeMarket> {
+        let response = self.request(Method::GET, &format!("/markets/{}", ticker))?.send().await?;
+        Ok(parse::<MarketResponse>(response).await?.market)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    fn request(&self, method: Method, path: &str) -> Result<RequestBuilder> {
+        let url = self.url(path);
+        if let Some(api_key) = &self.api_key {
+            let headers = api_key.headers(method.as_str(), Url::parse(&url)?.path());
+            return Ok(headers.into_iter().fold(self.http.request(method, url), |request, (name, value)| request.header(name, value)));
+        }
+        let token = self.token.as_ref().ok_or_else(|| anyhow::anyhow!("Not logged in to Kalshi"))?;
+        Ok(self.http.request(method, url).bearer_auth(token))
+    }
+}
+
+/// An error status from Kalshi, with the message it sent.
+#[derive(Debug)]
+pub struct StatusError {
+    pub status: StatusCode,
+    pub body: String
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Kalshi returned {}: {}", self.status, self.body)
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+/// Whether Kalshi refused a request for its authentication, such as with an expired session token.
+pub fn is_unauthorized(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<StatusError>().is_some_and(|e| e.status == StatusCode::UNAUTHORIZED)
+}
+
+/// Read a successful response's body, or turn an error status into an error carrying Kalshi's message.
+async fn parse<T: DeserializeOwned>(response: Response) -> Result<T> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(StatusError { status, body }.into());
+    }
+    Ok(response.json::<T>().await?)
+}
@@ -0,0 +1,196 @@
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::pss::BlindedSigningKey;
+use rsa::sha2::Sha256;
+use rsa::signature::{RandomizedSigner, SignatureEncoding};
+use rsa::RsaPrivateKey;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::path::Path;
+
+/// A JSON file of credentials, read instead of the variables below when set. Its keys are the
+/// variables' names without `KALSHI_`, in lower case, such as `{"email": .., "password": ..}`.
+pub const KALSHI_CREDENTIALS_FILE_VAR: &str = "KALSHI_CREDENTIALS_FILE";
+pub const KALSHI_EMAIL_VAR: &str = "KALSHI_EMAIL";
+pub const KALSHI_PASSWORD_VAR: &str = "KALSHI_PASSWORD";
+pub const KALSHI_API_KEY_ID_VAR: &str = "KALSHI_API_KEY_ID";
+/// The API key's RSA private key, PEM encoded in PKCS#1 or PKCS#8.
+pub const KALSHI_PRIVATE_KEY_VAR: &str = "KALSHI_PRIVATE_KEY";
+/// Any credential can instead name a file holding it, such as a mounted secret, with this
+/// suffix on its name: `KALSHI_PASSWORD_FILE=/run/secrets/kalshi_password`.
+pub const SECRET_FILE_SUFFIX: &str = "_FILE";
+
+const ACCESS_KEY_HEADER: &str = "kalshi-access-key";
+const ACCESS_TIMESTAMP_HEADER: &str = "kalshi-access-timestamp";
+const ACCESS_SIGNATURE_HEADER: &str = "kalshi-access-signature";
+
+/// A value that must never reach a log. It has no `Display`, and its `Debug` hides it.
+#[derive(Clone)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: String) -> Secret {
+        Secret(value)
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret(..)")
+    }
+}
+
+/// A Kalshi API key: requests are signed with its RSA private key instead of carrying a session token.
+#[derive(Clone)]
+pub struct ApiKey {
+    key_id: String,
+    signing_key: BlindedSigningKey<Sha256>
+}
+
+impl ApiKey {
+    pub fn new(key_id: &str, private_key_pem: &str) -> Result<ApiKey> {
+        let private_key = RsaPrivateKey::from_pkcs1_pem(private_key_pem)
+            .or_else(|_| RsaPrivateKey::from_pkcs8_pem(private_key_pem))
+            .map_err(|_| anyhow::anyhow!("The private key for Kalshi API key {} is not a PEM encoded RSA key", key_id))?;
+        Ok(ApiKey { key_id: key_id.to_string(), signing_key: BlindedSigningKey::new(private_key) })
+    }
+
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    /// The headers authenticating one request: the key id, the time in milliseconds, and an
+    /// RSA-PSS SHA-256 signature of the time, the method and the URL's path, without its query.
+    pub fn headers(&self, method: &str, path: &str) -> Vec<(&'static str, String)> {
+        let timestamp = chrono::Utc::now().timestamp_millis().to_string();
+        let message = format!("{}{}{}", timestamp, method, path);
+        let signature = self.signing_key.sign_with_rng(&mut rand::thread_rng(), message.as_bytes());
+        vec![
+            (ACCESS_KEY_HEADER, self.key_id.clone()),
+            (ACCESS_TIMESTAMP_HEADER, timestamp),
+            (ACCESS_SIGNATURE_HEADER, BASE64.encode(signature.to_bytes()))
+        ]
+    }
+}
+
+impl fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiKey").field("key_id", &self.key_id).finish_non_exhaustive()
+    }
+}
+
+/// How to authenticate with Kalshi: an account's email and password, exchanged for a
+/// session token at login, or an API key that signs every request.
+#[derive(Debug, Clone)]
+pub enum Credentials {
+    Password { email: String, password: Secret },
+    ApiKey(Box<ApiKey>)
+}
+
+impl Credentials {
+
+    /// Read credentials from `KALSHI_CREDENTIALS_FILE` if it is set, and otherwise from
+    /// `KALSHI_*` variables or the files their `_FILE` variants name. An API key is used
+    /// when one is given, and an email and password otherwise.
+    pub fn from_env() -> Result<Credentials> {
+        let source = Source::from_env()?;
+        if let Some(key_id) = source.value("api_key_id")? {
+            let private_key = source.value("private_key")?
+                .ok_or_else(|| anyhow::anyhow!("Kalshi API key {} was given without {}", key_id, KALSHI_PRIVATE_KEY_VAR))?;
+            return Ok(Credentials::ApiKey(Box::new(ApiKey::new(&key_id, &private_key)?)));
+        }
+        let (email, password) = Credentials::email_and_password(&source)?;
+        Ok(Credentials::Password { email, password })
+    }
+
+    fn email_and_password(source: &Source) -> Result<(String, Secret)> {
+        match (source.value("email")?, source.value("password")?) {
+            (Some(email), Some(password)) => Ok((email, Secret::new(password))),
+            _ => Err(anyhow::anyhow!(
+                "No Kalshi credentials: set {} and {}, or {} and {}, or {}",
+                KALSHI_EMAIL_VAR, KALSHI_PASSWORD_VAR, KALSHI_API_KEY_ID_VAR, KALSHI_PRIVATE_KEY_VAR, KALSHI_CREDENTIALS_FILE_VAR))
+        }
+    }
+}
+
+/// Who the credentials log in as, without anything secret.
+impl fmt::Display for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credentials::Password { email, .. } => write!(f, "email {}", email),
+            Credentials::ApiKey(api_key) => write!(f, "API key {}", api_key.key_id)
+        }
+    }
+}
+
+/// Where credentials are read from: the credentials file, or the environment.
+enum Source {
+    File(Map<String, Value>),
+    Env
+}
+
+impl Source {
+    fn from_env() -> Result<Source> {
+        match std::env::var(KALSHI_CREDENTIALS_FILE_VAR) {
+            Ok(path) => {
+                let contents = read_secret_file(Path::new(&path))?;
+                match serde_json::from_str(&contents) {
+                    Ok(Value::Object(fields)) => Ok(Source::File(fields)),
+                    _ => Err(anyhow::anyhow!("The Kalshi credentials file {} is not a JSON object", path))
+                }
+            },
+            Err(_) => Ok(Source::Env)
+        }
+    }
+
+    /// A credential given directly, or read from the file its `_file` variant names.
+    fn value(&self, name: &str) -> Result<Option<String>> {
+        let (direct, file) = match self {
+            Source::File(fields) => {
+                let field = |key: &str| fields.get(key).and_then(Value::as_str).map(str::to_string);
+                (field(name), field(&format!("{}{}", name, SECRET_FILE_SUFFIX.to_ascii_lowercase())))
+            },
+            Source::Env => {
+                let var = format!("KALSHI_{}", name.to_ascii_uppercase());
+                (std::env::var(&var).ok(), std::env::var(format!("{}{}", var, SECRET_FILE_SUFFIX)).ok())
+            }
+        };
+        match (direct, file) {
+            (Some(value), _) => Ok(Some(value)),
+            (None, Some(path)) => Ok(Some(read_secret_file(Path::new(&path))?.trim_end().to_string())),
+            (None, None) => Ok(None)
+        }
+    }
+}
+
+/// Read a file holding a secret, refusing one that other users can read or write.
+fn read_secret_file(path: &Path) -> Result<String> {
+    check_permissions(path)?;
+    std::fs::read_to_string(path).with_context(|| format!("Could not read Kalshi credentials from {}", path.display()))
+}
+
+#[cfg(unix)]
+fn check_permissions(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = std::fs::metadata(path)
+        .with_context(|| format!("Could not read Kalshi credentials from {}", path.display()))?
+        .permissions()
+        .mode();
+    if mode & 0o077 != 0 {
+        return Err(anyhow::anyhow!(
+            "Kalshi credentials in {} are open to other users (mode {:o}); restrict them with chmod 600", path.display(), mode & 0o777));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_path: &Path) -> Result<()> {
+    Ok(())
+}
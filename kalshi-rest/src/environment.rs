@@ -0,0 +1,152 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::fmt;
+
+use crate::client::KalshiRestClient;
+use crate::credentials::Credentials;
+
+/// The base URL of Kalshi's production REST API.
+pub const PROD_API_URL: &str = "https://trading-api.kalshi.com/trade-api/v2";
+/// The URL of Kalshi's production websocket feed.
+pub const PROD_WS_URL: &str = "wss://trading-api.kalshi.com/trade-api/ws/v2";
+/// The base URL of Kalshi's demo REST API, which trades with play money.
+pub const DEMO_API_URL: &str = "https://demo-api.kalshi.co/trade-api/v2";
+/// The URL of Kalshi's demo websocket feed.
+pub const DEMO_WS_URL: &str = "wss://demo-api.kalshi.co/trade-api/ws/v2";
+
+const PROD_HOST: &str = "trading-api.kalshi.com";
+const DEMO_HOST: &str = "demo-api.kalshi.co";
+
+/// One of Kalshi's two environments. Accounts, and so credentials, belong to exactly one.
+/// Demo is the default, so that trading real money is only ever asked for explicitly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Environment {
+    #[default]
+    Demo,
+    Production
+}
+
+impl Environment {
+    pub fn parse(name: &str) -> Result<Environment> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "demo" => Ok(Environment::Demo),
+            "prod" | "production" => Ok(Environment::Production),
+            other => Err(anyhow::anyhow!("Unknown Kalshi environment {:?}: expected demo or prod", other))
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Environment::Demo => "demo",
+            Environment::Production => "production"
+        }
+    }
+
+    pub fn api_url(&self) -> &'static str {
+        match self {
+            Environment::Demo => DEMO_API_URL,
+            Environment::Production => PROD_API_URL
+        }
+    }
+
+    pub fn ws_url(&self) -> &'static str {
+        match self {
+            Environment::Demo => DEMO_WS_URL,
+            Environment::Production => PROD_WS_URL
+        }
+    }
+
+    /// Which environment a URL belongs to, if it is one of Kalshi's.
+    fn of_url(url: &str) -> Option<Environment> {
+        if url.contains(PROD_HOST) {
+            Some(Environment::Production)
+        } else if url.contains(DEMO_HOST) {
+            Some(Environment::Demo)
+        } else {
+            None
+        }
+    }
+}
+
+impl TryFrom<String> for Environment {
+    type Error = anyhow::Error;
+
+    fn try_from(name: String) -> Result<Environment> {
+        Environment::parse(&name)
+    }
+}
+
+/// The Kalshi environment a binary trades in and where to reach it.
+#[derive(Debug, Clone)]
+pub struct EnvironmentConfig {
+    pub environment: Environment,
+    pub api_url: String,
+    pub ws_url: String
+}
+
+impl EnvironmentConfig {
+
+    /// The environment's own URLs, unless overridden, such as to point at the mock exchange.
+    /// Refuses a URL that belongs to the other Kalshi environment, credentials said to be for the
+    /// other one, and production unless the credentials are said to be for production too.
+    pub fn new(
+        environment: Environment,
+        api_url: Option<String>,
+        ws_url: Option<String>,
+        credentials_environment: Option<Environment>
+    ) -> Result<EnvironmentConfig> {
+        let config = EnvironmentConfig {
+            environment,
+            api_url: api_url.unwrap_or(environment.api_url().to_string()),
+            ws_url: ws_url.unwrap_or(environment.ws_url().to_string())
+        };
+        config.validate()?;
+        match credentials_environment {
+            Some(credentials_environment) => config.check_credentials(credentials_environment)?,
+            None if environment == Environment::Production => return Err(anyhow::anyhow!(
+                "The Kalshi environment is production but the credentials environment is not given; give it as production to trade real money")),
+            None => {}
+        }
+        Ok(config)
+    }
+
+    /// Refuse URLs that point at the other Kalshi environment.
+    pub fn validate(&self) -> Result<()> {
+        for (what, url) in [("REST API", &self.api_url), ("websocket", &self.ws_url)] {
+            match Environment::of_url(url) {
+                Some(environment) if environment != self.environment => return Err(anyhow::anyhow!(
+                    "The Kalshi environment is {} but the {} URL {} is Kalshi {}", self.environment.name(), what, url, environment.name())),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Refuse credentials that belong to the other environment.
+    pub fn check_credentials(&self, credentials_environment: Environment) -> Result<()> {
+        if credentials_environment != self.environment {
+            return Err(anyhow::anyhow!(
+                "The Kalshi environment is {} but the credentials are for {}", self.environment.name(), credentials_environment.name()));
+        }
+        Ok(())
+    }
+
+    /// Log in to the environment's REST API. A login that fails is most often credentials
+    /// for the other environment, since accounts in one cannot log in to the other.
+    pub async fn login(&self, credentials: &Credentials) -> Result<KalshiRestClient> {
+        let mut client = KalshiRestClient::new(&self.api_url);
+        if let Err(e) = client.authenticate(credentials).await {
+            return Err(anyhow::anyhow!(
+                "Could not log in to Kalshi {} at {} with {}; check the credentials are for the {} environment: {}",
+                self.environment.name(), self.api_url, credentials, self.environment.name(), e));
+        }
+        Ok(client)
+    }
+}
+
+impl fmt::Display for EnvironmentConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Kalshi {} (REST {}, websocket {})", self.environment.name().to_ascii_uppercase(), self.api_url, self.ws_url)
+    }
+}
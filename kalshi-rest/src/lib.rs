@@ -0,0 +1,11 @@
+pub mod client;
+pub mod credentials;
+pub mod environment;
+pub mod models;
+pub mod session;
+
+pub use client::{is_unauthorized, KalshiRestClient, StatusError};
+pub use credentials::{ApiKey, Credentials, Secret};
+pub use environment::{Environment, EnvironmentConfig, DEMO_API_URL, DEMO_WS_URL, PROD_API_URL, PROD_WS_URL};
+pub use models::{ExchangeFill, ExchangeMarket, ExchangeOrder, FillsQuery, MarketPosition, OrdersQuery};
+pub use session::KalshiSession;
@@ -0,0 +1,145 @@
+use chrono::DateTime;
+use kalshi::{Action, Side};
+use serde::{Deserialize, Serialize};
+
+use queue_client::queue_data::fills::Fill;
+use queue_client::queue_data::market_data::{self, MarketLifecycleMessage, MarketStatus};
+
+/// An order as Kalshi's v2 REST API returns it. Only the fields the OMS reads are kept.
+#[derive(Deserialize, Debug)]
+pub struct ExchangeOrder {
+    pub order_id: String,
+    pub client_order_id: String,
+    pub ticker: String,
+    pub status: String,
+    pub side: Side,
+    pub action: Action,
+    #[serde(default)]
+    pub yes_price: i64,
+    #[serde(default)]
+    pub no_price: i64,
+    #[serde(default)]
+    pub remaining_count: i32,
+    #[serde(default)]
+    pub taker_fill_count: i32,
+    #[serde(default)]
+    pub maker_fill_count: i32
+}
+
+impl ExchangeOrder {
+
+    /// The number of contracts of the order that have traded.
+    pub fn filled_count(&self) -> i32 {
+        self.taker_fill_count + self.maker_fill_count
+    }
+
+    /// The order's price on its own side, in cents.
+    pub fn price(&self) -> i64 {
+        match self.side {
+            Side::Yes => self.yes_price,
+            Side::No => self.no_price
+        }
+    }
+}
+
+/// A market as Kalshi's v2 REST API returns it. Only the fields the OMS reads are kept.
+#[derive(Deserialize, Debug)]
+pub struct ExchangeMarket {
+    pub ticker: String,
+    pub status: String,
+    pub close_time: String,
+    /// Empty until the market is determined.
+    #[serde(default)]
+    pub result: String
+}
+
+impl TryFrom<ExchangeMarket> for MarketLifecycleMessage {
+    type Error = anyhow::Error;
+
+    /// Convert a market looked up on Kalshi's REST API into the form the lifecycle feed delivers
+    fn try_from(market: ExchangeMarket) -> Result<Self, Self::Error> {
+        let status = match MarketStatus::from_kalshi(&market.status) {
+            Some(status) => status,
+            None => return Err(anyhow::anyhow!("Unknown status {:?} for market {}", market.status, market.ticker))
+        };
+        Ok(MarketLifecycleMessage {
+            market_ticker: market.ticker,
+            status,
+            close_ts: Some(DateTime::parse_from_rfc3339(&market.close_time)?.timestamp()),
+            result: market_data::market_result(&market.result),
+            ts: chrono::Utc::now().timestamp()
+        })
+    }
+}
+
+/// A fill as Kalshi's v2 REST API returns it.
+#[derive(Deserialize, Debug)]
+pub struct ExchangeFill {
+    pub trade_id: String,
+    pub order_id: String,
+    pub ticker: String,
+    pub is_taker: bool,
+    pub side: Side,
+    pub yes_price: i64,
+    pub no_price: i64,
+    pub count: i32,
+    pub action: Action,
+    pub created_time: String
+}
+
+impl TryFrom<ExchangeFill> for Fill {
+    type Error = anyhow::Error;
+
+    /// Convert a fill from Kalshi's REST API into the form the websocket feed delivers
+    fn try_from(fill: ExchangeFill) -> Result<Self, Self::Error> {
+        Ok(Fill {
+            trade_id: fill.trade_id,
+            order_id: fill.order_id,
+            market_ticker: fill.ticker,
+            is_taker: fill.is_taker,
+            side: fill.side,
+            yes_price: fill.yes_price as i32,
+            no_price: fill.no_price as i32,
+            count: fill.count,
+            action: fill.action,
+            ts: DateTime::parse_from_rfc3339(&fill.created_time)?.timestamp()
+        })
+    }
+}
+
+/// The account's position in one market, in contracts: positive for YES, negative for NO.
+#[derive(Deserialize, Debug)]
+pub struct MarketPosition {
+    pub ticker: String,
+    pub position: i32
+}
+
+/// Filters for listing the account's orders. Unset fields are left out of the query.
+#[derive(Serialize, Debug, Default)]
+pub struct OrdersQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ticker: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_ts: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_ts: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>
+}
+
+/// Filters for listing the account's fills. Unset fields are left out of the query.
+#[derive(Serialize, Debug, Default)]
+pub struct FillsQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_ts: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_ts: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>
+}
@@ -0,0 +1,80 @@
+use anyhow::Result;
+use log::{error, info, warn};
+use std::time::{Duration, Instant};
+
+use crate::client::KalshiRestClient;
+use crate::credentials::Credentials;
+use crate::environment::EnvironmentConfig;
+
+const RENEW_BACKOFF_INITIAL: Duration = Duration::from_secs(1);
+const RENEW_BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+/// A logged in client that stays logged in. Kalshi's session tokens expire, so the session
+/// logs in again once its token is older than `renew_every`, before Kalshi would refuse it,
+/// and again whenever a request comes back unauthorized anyway.
+///
+/// Renewing retries until it succeeds. Callers renew between requests and wait for it, so
+/// the orders they hold, and those still queued behind them, are sent once it is done
+/// rather than rejected.
+pub struct KalshiSession {
+    environment: EnvironmentConfig,
+    credentials: Credentials,
+    client: KalshiRestClient,
+    renewed_at: Instant,
+    renew_every: Duration
+}
+
+impl KalshiSession {
+
+    /// Log in, failing if the credentials are refused, since retrying would not help.
+    pub async fn open(environment: &EnvironmentConfig, credentials: &Credentials, renew_every: Duration) -> Result<KalshiSession> {
+        let client = environment.login(credentials).await?;
+        Ok(KalshiSession {
+            environment: environment.clone(),
+            credentials: credentials.clone(),
+            client,
+            renewed_at: Instant::now(),
+            renew_every
+        })
+    }
+
+    pub fn client(&self) -> &KalshiRestClient {
+        &self.client
+    }
+
+    /// Whether the token is old enough to renew. API keys sign each request and have no token to expire.
+    pub fn is_due(&self) -> bool {
+        matches!(self.credentials, Credentials::Password { .. }) && self.renewed_at.elapsed() >= self.renew_every
+    }
+
+    pub async fn renew_if_due(&mut self) {
+        if self.is_due() {
+            info!("Renewing the Kalshi session after {:?}", self.renewed_at.elapsed());
+            self.renew().await;
+        }
+    }
+
+    /// Log in again for a fresh token, retrying with backoff until Kalshi accepts.
+    pub async fn renew(&mut self) {
+        let mut backoff = RENEW_BACKOFF_INITIAL;
+        loop {
+            match self.environment.login(&self.credentials).await {
+                Ok(client) => {
+                    self.client = client;
+                    self.renewed_at = Instant::now();
+                    info!("Renewed the Kalshi session with {}", self.credentials);
+                    return;
+                },
+                Err(e) => error!("Could not renew the Kalshi session, retrying in {:?}: {:?}", backoff, e)
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(RENEW_BACKOFF_MAX);
+        }
+    }
+
+    /// Renew after a request came back unauthorized, so the caller can send it again.
+    pub async fn on_unauthorized(&mut self) {
+        warn!("Kalshi refused the session's credentials; logging in again");
+        self.renew().await;
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::Result;
+use std::env;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+const REST_ADDR_VAR: &str = "MOCK_KALSHI_REST_ADDR";
+const WS_ADDR_VAR: &str = "MOCK_KALSHI_WS_ADDR";
+const WS_SCRIPT_VAR: &str = "MOCK_KALSHI_WS_SCRIPT";
+const LATENCY_MS_VAR: &str = "MOCK_KALSHI_LATENCY_MS";
+const FAIL_EVERY_VAR: &str = "MOCK_KALSHI_FAIL_EVERY";
+const FAIL_STATUS_VAR: &str = "MOCK_KALSHI_FAIL_STATUS";
+const RATE_LIMIT_VAR: &str = "MOCK_KALSHI_RATE_LIMIT_PER_SEC";
+const EMAIL_VAR: &str = "MOCK_KALSHI_EMAIL";
+const PASSWORD_VAR: &str = "MOCK_KALSHI_PASSWORD";
+const API_KEY_ID_VAR: &str = "MOCK_KALSHI_API_KEY_ID";
+const TOKEN_TTL_SECS_VAR: &str = "MOCK_KALSHI_TOKEN_TTL_SECS";
+
+const DEFAULT_REST_ADDR: &str = "127.0.0.1:8081";
+const DEFAULT_WS_ADDR: &str = "127.0.0.1:8082";
+const DEFAULT_FAIL_STATUS: u16 = 500;
+
+/// How the mock exchange behaves, read from `MOCK_KALSHI_*` environment variables so a CI job
+/// can start it with whatever latency and faults the test calls for.
+#[derive(Debug, Clone)]
+pub struct MockConfig {
+    /// Where the REST API listens. Point the gateway's `KALSHI_API_URL` at `http://<addr>/trade-api/v2`.
+    pub rest_addr: SocketAddr,
+    /// Where the websocket listens. Point the feed's `KALSHI_WS_URL` at `ws://<addr>/trade-api/ws/v2`.
+    pub ws_addr: SocketAddr,
+    /// The JSON file of steps the websocket plays once a client subscribes. See `script::ScriptStep`.
+    pub ws_script: Option<PathBuf>,
+    /// Added to every REST request before it is answered.
+    pub latency: Duration,
+    /// Fail every nth request with `fail_status`, counting from the first. Deterministic, so
+    /// a test knows exactly which request will fail.
+    pub fail_every: Option<u64>,
+    pub fail_status: u16,
+    /// Answer 429 once more than this many requests arrive within one second.
+    pub rate_limit_per_sec: Option<u32>,
+    /// The only credentials login accepts. Any credentials are accepted when unset.
+    pub email: Option<String>,
+    pub password: Option<String>,
+    /// The only API key id signed requests are accepted from. Any are accepted when unset.
+    /// Signatures are not checked, since the mock has no public keys.
+    pub api_key_id: Option<String>,
+    /// How long a login's token is accepted for, so clients' renewal can be tested. Tokens never expire when unset.
+    pub token_ttl: Option<Duration>
+}
+
+/// The default addresses, with no latency, faults or rate limit, accepting any credentials.
+impl Default for MockConfig {
+    fn default() -> Self {
+        MockConfig {
+            rest_addr: DEFAULT_REST_ADDR.parse().unwrap(),
+            ws_addr: DEFAULT_WS_ADDR.parse().unwrap(),
+            ws_script: None,
+            latency: Duration::ZERO,
+            fail_every: None,
+            fail_status: DEFAULT_FAIL_STATUS,
+            rate_limit_per_sec: None,
+            email: None,
+            password: None,
+            api_key_id: None,
+            token_ttl: None
+        }
+    }
+}
+
+impl MockConfig {
+    pub fn from_env() -> Result<MockConfig> {
+        Ok(MockConfig {
+            rest_addr: parse_var(REST_ADDR_VAR)?.unwrap_or(DEFAULT_REST_ADDR.parse()?),
+            ws_addr: parse_var(WS_ADDR_VAR)?.unwrap_or(DEFAULT_WS_ADDR.parse()?),
+            ws_script: env::var(WS_SCRIPT_VAR).ok().map(PathBuf::from),
+            latency: Duration::from_millis(parse_var(LATENCY_MS_VAR)?.unwrap_or(0)),
+            fail_every: parse_var(FAIL_EVERY_VAR)?.filter(|every| *every > 0),
+            fail_status: parse_var(FAIL_STATUS_VAR)?.unwrap_or(DEFAULT_FAIL_STATUS),
+            rate_limit_per_sec: parse_var(RATE_LIMIT_VAR)?.filter(|limit| *limit > 0),
+            email: env::var(EMAIL_VAR).ok(),
+            password: env::var(PASSWORD_VAR).ok(),
+            api_key_id: env::var(API_KEY_ID_VAR).ok(),
+            token_ttl: parse_var(TOKEN_TTL_SECS_VAR)?.filter(|ttl| *ttl > 0).map(Duration::from_secs)
+        })
+    }
+}
+
+fn parse_var<T: FromStr>(name: &str) -> Result<Option<T>> {
+    match env::var(name) {
+        Ok(value) => value.parse()
+            .map(Some)
+            .map_err(|_| anyhow::anyhow!("{} is not a valid value for {}", value, name)),
+        Err(_) => Ok(None)
+    }
+}
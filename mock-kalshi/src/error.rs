@@ -0,0 +1,37 @@
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::json;
+
+/// An error answered in Kalshi's shape: `{"error": {"code": ..., "message": ...}}`.
+#[derive(Debug)]
+pub struct MockError {
+    pub status: StatusCode,
+    pub code: String,
+    pub message: String
+}
+
+impl MockError {
+    pub fn new(status: StatusCode, code: &str, message: &str) -> MockError {
+        MockError { status, code: code.to_string(), message: message.to_string() }
+    }
+
+    pub fn bad_request(code: &str, message: &str) -> MockError {
+        MockError::new(StatusCode::BAD_REQUEST, code, message)
+    }
+
+    pub fn not_found(order_id: &str) -> MockError {
+        MockError::new(StatusCode::NOT_FOUND, "not_found", &format!("no order {}", order_id))
+    }
+
+    pub fn unauthorized() -> MockError {
+        MockError::new(StatusCode::UNAUTHORIZED, "unauthorized", "missing or unknown token")
+    }
+}
+
+impl IntoResponse for MockError {
+    fn into_response(self) -> Response {
+        let body = json!({ "error": { "code": self.code, "message": self.message } });
+        (self.status, Json(body)).into_response()
+    }
+}
@@ -0,0 +1,103 @@
+use chrono::{TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use crate::error::MockError;
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// A fill as the websocket's fill channel delivers it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MockFill {
+    pub trade_id: String,
+    pub order_id: String,
+    pub market_ticker: String,
+    pub is_taker: bool,
+    pub side: String,
+    pub yes_price: i64,
+    pub no_price: i64,
+    pub count: i32,
+    pub action: String,
+    pub ts: i64
+}
+
+impl MockFill {
+
+    /// The fill as the REST API lists it, which names the market `ticker` and gives its time as RFC 3339.
+    fn to_rest(&self) -> Value {
+        json!({
+            "trade_id": self.trade_id,
+            "order_id": self.order_id,
+            "ticker": self.market_ticker,
+            "is_taker": self.is_taker,
+            "side": self.side,
+            "yes_price": self.yes_price,
+            "no_price": self.no_price,
+            "count": self.count,
+            "action": self.action,
+            "created_time": Utc.timestamp_opt(self.ts, 0).single().unwrap_or_default().to_rfc3339()
+        })
+    }
+
+    /// The change in YES contracts held: buying NO is the same as selling YES.
+    fn yes_delta(&self) -> i32 {
+        match (self.action.as_str(), self.side.as_str()) {
+            ("buy", "yes") | ("sell", "no") => self.count,
+            _ => -self.count
+        }
+    }
+}
+
+/// Filters for listing fills, as the query string of `GET /portfolio/fills`.
+#[derive(Deserialize, Debug, Default)]
+pub struct FillFilter {
+    pub min_ts: Option<i64>,
+    pub max_ts: Option<i64>,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>
+}
+
+/// Every fill the mock has produced, oldest first, whether or not it reached the websocket.
+#[derive(Default)]
+pub struct Fills {
+    fills: Vec<MockFill>
+}
+
+impl Fills {
+    pub fn new() -> Fills {
+        Fills::default()
+    }
+
+    pub fn record(&mut self, fill: MockFill) {
+        self.fills.push(fill);
+    }
+
+    /// One page of the fills matching `filter`, newest first, and the cursor for the next page.
+    pub fn list(&self, filter: &FillFilter) -> Result<(Vec<Value>, String), MockError> {
+        let offset = match filter.cursor.as_deref() {
+            None | Some("") => 0,
+            Some(cursor) => cursor.parse().map_err(|_| MockError::bad_request("invalid_parameters", "invalid cursor"))?
+        };
+        let limit = filter.limit.unwrap_or(DEFAULT_PAGE_SIZE).max(1);
+
+        let matching: Vec<&MockFill> = self.fills.iter().rev()
+            .filter(|fill| filter.min_ts.is_none_or(|min_ts| fill.ts >= min_ts))
+            .filter(|fill| filter.max_ts.is_none_or(|max_ts| fill.ts <= max_ts))
+            .collect();
+        let page = matching.iter().skip(offset).take(limit).map(|fill| fill.to_rest()).collect();
+        let cursor = if offset + limit < matching.len() { (offset + limit).to_string() } else { String::new() };
+        Ok((page, cursor))
+    }
+
+    /// The net YES position in every market traded, as the REST API lists market positions.
+    pub fn positions(&self) -> Vec<Value> {
+        let mut positions = BTreeMap::new();
+        for fill in self.fills.iter() {
+            *positions.entry(fill.market_ticker.as_str()).or_insert(0) += fill.yes_delta();
+        }
+        positions.into_iter()
+            .map(|(ticker, position)| json!({ "ticker": ticker, "position": position }))
+            .collect()
+    }
+}
@@ -0,0 +1,29 @@
+use anyhow::Result;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+use crate::state::MockState;
+
+mod config;
+mod error;
+mod fills;
+mod orders;
+mod rest;
+mod script;
+mod state;
+mod ws;
+
+pub use config::MockConfig;
+pub use rest::API_PREFIX;
+pub use script::{Script, ScriptStep};
+
+/// Serve the REST API and the websocket on the given listeners until either fails. They share
+/// logins and fills. Tests start the mock here, on listeners bound to any free port.
+pub async fn serve(rest_listener: TcpListener, ws_listener: TcpListener, config: MockConfig, script: Arc<Script>) -> Result<()> {
+    let state = MockState::new(config);
+    tokio::try_join!(
+        async { axum::serve(rest_listener, rest::router(state.clone())).await.map_err(anyhow::Error::from) },
+        ws::serve(ws_listener, state.clone(), script)
+    )?;
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use anyhow::Result;
+use log::info;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+use mock_kalshi::{MockConfig, Script, API_PREFIX};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+
+    // Initialize logger
+
+    env_logger::Builder::from_default_env().format_timestamp_micros().init();
+
+    // 1. Read how the mock should behave and what its websocket should play
+
+    let config = MockConfig::from_env()?;
+    info!("Starting mock Kalshi with latency {:?}, failing every {:?} request(s) with {}, rate limit {:?}/s",
+        config.latency, config.fail_every, config.fail_status, config.rate_limit_per_sec);
+    let script = Arc::new(Script::load(config.ws_script.as_deref())?);
+    info!("Loaded {} scripted websocket step(s)", script.len());
+
+    // 2. Serve the REST API and the websocket, which share logins and fills
+
+    let rest_listener = TcpListener::bind(config.rest_addr).await?;
+    info!("Mock Kalshi REST API listening on http://{}{}", config.rest_addr, API_PREFIX);
+    let ws_listener = TcpListener::bind(config.ws_addr).await?;
+    info!("Mock Kalshi websocket listening on ws://{}", config.ws_addr);
+
+    mock_kalshi::serve(rest_listener, ws_listener, config, script).await
+}
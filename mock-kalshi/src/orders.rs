@@ -0,0 +1,293 @@
+use axum::http::StatusCode;
+use chrono::{TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sim_exchange::{Action, MatchingEngine, NewOrder, OrderType, Side, SimError, SimFill, SimOrder};
+use std::collections::{HashMap, HashSet};
+
+use crate::error::MockError;
+use crate::fills::MockFill;
+
+pub const USER_ID: &str = "mock-user";
+
+/// An order as Kalshi's v2 REST API renders it.
+#[derive(Serialize, Debug, Clone)]
+pub struct MockOrder {
+    pub order_id: String,
+    pub user_id: String,
+    pub client_order_id: String,
+    pub ticker: String,
+    pub status: String,
+    pub action: String,
+    pub side: String,
+    #[serde(rename = "type")]
+    pub order_type: String,
+    pub yes_price: i64,
+    pub no_price: i64,
+    pub place_count: i32,
+    pub remaining_count: i32,
+    pub taker_fill_count: i32,
+    pub maker_fill_count: i32,
+    pub created_time: String,
+    pub expiration_time: Option<String>,
+    #[serde(skip)]
+    pub created_ts: i64,
+    #[serde(skip)]
+    pub expiration_ts: Option<i64>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateOrderRequest {
+    pub action: String,
+    pub client_order_id: String,
+    pub count: i32,
+    pub side: String,
+    pub ticker: String,
+    #[serde(rename = "type")]
+    pub order_type: String,
+    pub expiration_ts: Option<i64>,
+    pub no_price: Option<i64>,
+    pub yes_price: Option<i64>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AmendOrderRequest {
+    pub action: String,
+    pub client_order_id: String,
+    pub count: i32,
+    pub side: String,
+    pub ticker: String,
+    pub updated_client_order_id: Option<String>,
+    pub no_price: Option<i64>,
+    pub yes_price: Option<i64>
+}
+
+/// Filters for listing orders, as the query string of `GET /portfolio/orders`.
+#[derive(Deserialize, Debug, Default)]
+pub struct OrderFilter {
+    pub ticker: Option<String>,
+    pub status: Option<String>,
+    pub min_ts: Option<i64>,
+    pub max_ts: Option<i64>,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>
+}
+
+/// What a request did beyond its answer: every order whose state it changed, including ones
+/// resting on the other side of a trade, and the fills it produced, for the websocket to push.
+#[derive(Default)]
+pub struct OrderEvents {
+    pub orders: Vec<MockOrder>,
+    pub fills: Vec<MockFill>
+}
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Every order placed on the mock, matched against each other by a simulated exchange, so an
+/// order that crosses one resting in the same market trades with it.
+pub struct Orders {
+    engine: MatchingEngine,
+    order_ids: Vec<String>,
+    /// Client order ids changed by an amend, by order id.
+    renamed: HashMap<String, String>
+}
+
+impl Orders {
+    pub fn new() -> Orders {
+        Orders {
+            engine: MatchingEngine::new("mock"),
+            order_ids: Vec::new(),
+            renamed: HashMap::new()
+        }
+    }
+
+    pub fn create(&mut self, request: CreateOrderRequest, now: i64) -> Result<(MockOrder, OrderEvents), MockError> {
+        let (side, action) = parse_side_action(&request.side, &request.action)?;
+        let order_type = match request.order_type.as_str() {
+            "limit" => OrderType::Limit,
+            "market" => OrderType::Market,
+            _ => return Err(MockError::bad_request("invalid_parameters", "type must be limit or market"))
+        };
+        if self.renamed.values().any(|client_order_id| client_order_id == &request.client_order_id) {
+            return Err(from_sim(SimError::DuplicateClientOrderId(request.client_order_id)));
+        }
+
+        let execution = self.engine.submit(NewOrder {
+            client_order_id: request.client_order_id,
+            ticker: request.ticker,
+            side,
+            action,
+            order_type,
+            count: request.count,
+            yes_price: request.yes_price,
+            no_price: request.no_price,
+            expiration_ts: request.expiration_ts
+        }, now).map_err(from_sim)?;
+        self.order_ids.push(execution.order.order_id.clone());
+
+        let events = self.events_of(&execution.fills, Some(&execution.order));
+        Ok((self.render(&execution.order), events))
+    }
+
+    /// Cancel a resting order, returning it and how many contracts the cancel took off the book.
+    pub fn cancel(&mut self, order_id: &str, now: i64) -> Result<(MockOrder, i32, OrderEvents), MockError> {
+        let (order, reduced_by) = self.engine.cancel(order_id, now).map_err(|e| self.refusal(e))?;
+        let events = self.events_of(&[], Some(&order));
+        Ok((self.render(&order), reduced_by, events))
+    }
+
+    /// Change the price or size of a resting order, returning it before and after. An amend
+    /// to a price that crosses the book trades straight away.
+    pub fn amend(&mut self, order_id: &str, request: AmendOrderRequest, now: i64) -> Result<(MockOrder, MockOrder, OrderEvents), MockError> {
+        let (side, action) = parse_side_action(&request.side, &request.action)?;
+        let current = self.engine.order(order_id).ok_or_else(|| MockError::not_found(order_id))?;
+        if self.client_order_id_of(current) != request.client_order_id || current.ticker != request.ticker
+            || current.side != side || current.action != action {
+            return Err(MockError::bad_request("invalid_parameters", "amend does not match the order"));
+        }
+
+        let (old_order, execution) = self.engine.amend(order_id, request.count, request.yes_price, request.no_price, now)
+            .map_err(|e| self.refusal(e))?;
+        let old_order = self.render(&old_order);
+        if let Some(updated_client_order_id) = request.updated_client_order_id {
+            self.renamed.insert(order_id.to_string(), updated_client_order_id);
+        }
+        let events = self.events_of(&execution.fills, Some(&execution.order));
+        Ok((old_order, self.render(&execution.order), events))
+    }
+
+    pub fn get(&mut self, order_id: &str, now: i64) -> Result<MockOrder, MockError> {
+        self.engine.expire(now);
+        self.engine.order(order_id)
+            .map(|order| self.render(order))
+            .ok_or_else(|| MockError::not_found(order_id))
+    }
+
+    /// One page of the orders matching `filter`, newest first as Kalshi lists them, and the
+    /// cursor for the next page, which is empty on the last one.
+    pub fn list(&mut self, filter: &OrderFilter, now: i64) -> Result<(Vec<MockOrder>, String), MockError> {
+        self.engine.expire(now);
+        let offset = match filter.cursor.as_deref() {
+            None | Some("") => 0,
+            Some(cursor) => cursor.parse().map_err(|_| MockError::bad_request("invalid_parameters", "invalid cursor"))?
+        };
+        let limit = filter.limit.unwrap_or(DEFAULT_PAGE_SIZE).max(1);
+
+        let matching: Vec<MockOrder> = self.order_ids.iter().rev()
+            .filter_map(|order_id| self.engine.order(order_id))
+            .map(|order| self.render(order))
+            .filter(|order| filter.ticker.as_ref().is_none_or(|ticker| &order.ticker == ticker))
+            .filter(|order| filter.status.as_ref().is_none_or(|status| &order.status == status))
+            .filter(|order| filter.min_ts.is_none_or(|min_ts| order.created_ts >= min_ts))
+            .filter(|order| filter.max_ts.is_none_or(|max_ts| order.created_ts <= max_ts))
+            .collect();
+        let cursor = if offset + limit < matching.len() { (offset + limit).to_string() } else { String::new() };
+        let page = matching.into_iter().skip(offset).take(limit).collect();
+        Ok((page, cursor))
+    }
+
+    fn client_order_id_of(&self, order: &SimOrder) -> String {
+        self.renamed.get(&order.order_id).cloned().unwrap_or_else(|| order.client_order_id.clone())
+    }
+
+    fn render(&self, order: &SimOrder) -> MockOrder {
+        MockOrder {
+            order_id: order.order_id.clone(),
+            user_id: USER_ID.to_string(),
+            client_order_id: self.client_order_id_of(order),
+            ticker: order.ticker.clone(),
+            status: order.status.as_str().to_string(),
+            action: action_str(order.action).to_string(),
+            side: side_str(order.side).to_string(),
+            order_type: match order.order_type {
+                OrderType::Limit => "limit",
+                OrderType::Market => "market"
+            }.to_string(),
+            yes_price: order.yes_price,
+            no_price: order.no_price(),
+            place_count: order.place_count,
+            remaining_count: order.remaining_count,
+            taker_fill_count: order.taker_fill_count,
+            maker_fill_count: order.maker_fill_count,
+            created_time: rfc3339(order.created_ts),
+            expiration_time: order.expiration_ts.map(rfc3339),
+            created_ts: order.created_ts,
+            expiration_ts: order.expiration_ts
+        }
+    }
+
+    /// The order a request acted on and every resting order its fills traded with, each once.
+    fn events_of(&self, fills: &[SimFill], order: Option<&SimOrder>) -> OrderEvents {
+        let mut seen = HashSet::new();
+        let orders = order.map(|order| order.order_id.as_str()).into_iter()
+            .chain(fills.iter().map(|fill| fill.order_id.as_str()))
+            .filter(|order_id| seen.insert(*order_id))
+            .filter_map(|order_id| self.engine.order(order_id))
+            .map(|order| self.render(order))
+            .collect();
+        let fills = fills.iter().map(|fill| MockFill {
+            trade_id: fill.trade_id.clone(),
+            order_id: fill.order_id.clone(),
+            market_ticker: fill.ticker.clone(),
+            is_taker: fill.is_taker,
+            side: side_str(fill.side).to_string(),
+            yes_price: fill.yes_price,
+            no_price: fill.no_price(),
+            count: fill.count,
+            action: action_str(fill.action).to_string(),
+            ts: fill.ts
+        }).collect();
+        OrderEvents { orders, fills }
+    }
+
+    /// Refuse a request about an existing order, saying what state it is in if it is not resting.
+    fn refusal(&self, e: SimError) -> MockError {
+        match &e {
+            SimError::NotResting(order_id) => match self.engine.order(order_id) {
+                Some(order) => MockError::bad_request("order_not_resting", &format!("order is {}", order.status.as_str())),
+                None => from_sim(e)
+            },
+            _ => from_sim(e)
+        }
+    }
+}
+
+fn from_sim(e: SimError) -> MockError {
+    match e {
+        SimError::InvalidOrder(reason) => MockError::bad_request("invalid_parameters", &reason),
+        SimError::DuplicateClientOrderId(_) => MockError::new(StatusCode::CONFLICT, "order_already_exists", "client_order_id has already been used"),
+        SimError::UnknownOrder(order_id) => MockError::not_found(&order_id),
+        SimError::NotResting(_) => MockError::bad_request("order_not_resting", "order is not resting")
+    }
+}
+
+fn parse_side_action(side: &str, action: &str) -> Result<(Side, Action), MockError> {
+    let action = match action {
+        "buy" => Action::Buy,
+        "sell" => Action::Sell,
+        _ => return Err(MockError::bad_request("invalid_parameters", "action must be buy or sell"))
+    };
+    let side = match side {
+        "yes" => Side::Yes,
+        "no" => Side::No,
+        _ => return Err(MockError::bad_request("invalid_parameters", "side must be yes or no"))
+    };
+    Ok((side, action))
+}
+
+fn side_str(side: Side) -> &'static str {
+    match side {
+        Side::Yes => "yes",
+        Side::No => "no"
+    }
+}
+
+fn action_str(action: Action) -> &'static str {
+    match action {
+        Action::Buy => "buy",
+        Action::Sell => "sell"
+    }
+}
+
+fn rfc3339(ts: i64) -> String {
+    Utc.timestamp_opt(ts, 0).single().unwrap_or_default().to_rfc3339()
+}
@@ -0,0 +1,163 @@
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{TimeZone, Utc};
+use log::{debug, info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::atomic::Ordering;
+
+use crate::error::MockError;
+use crate::fills::FillFilter;
+use crate::orders::{AmendOrderRequest, CreateOrderRequest, OrderFilter, USER_ID};
+use crate::state::{now, SharedState};
+
+pub const API_PREFIX: &str = "/trade-api/v2";
+const MARKET_OPEN_SECS: i64 = 24 * 60 * 60;
+
+/// The Kalshi v2 REST endpoints the OMS uses, behind the configured faults.
+pub fn router(state: SharedState) -> Router {
+    let api = Router::new()
+        .route("/login", post(login))
+        .route("/portfolio/orders", post(create_order).get(get_orders))
+        .route("/portfolio/orders/:order_id", get(get_order).delete(cancel_order))
+        .route("/portfolio/orders/:order_id/amend", post(amend_order))
+        .route("/portfolio/fills", get(get_fills))
+        .route("/portfolio/positions", get(get_positions))
+        .route("/markets/:ticker", get(get_market));
+
+    Router::new()
+        .nest(API_PREFIX, api)
+        .layer(middleware::from_fn_with_state(state.clone(), inject_faults))
+        .with_state(state)
+}
+
+/// Rate limit, delay and fail requests as configured, before they reach an endpoint.
+async fn inject_faults(State(state): State<SharedState>, request: Request, next: Next) -> Response {
+    debug!("{} {}", request.method(), request.uri());
+    if !state.within_rate_limit() {
+        warn!("Rate limiting {} {}", request.method(), request.uri());
+        return MockError::new(StatusCode::TOO_MANY_REQUESTS, "too_many_requests", "rate limit exceeded").into_response();
+    }
+
+    if !state.config.latency.is_zero() {
+        tokio::time::sleep(state.config.latency).await;
+    }
+
+    let seen = state.requests_seen.fetch_add(1, Ordering::SeqCst) + 1;
+    if let Some(fail_every) = state.config.fail_every {
+        if seen % fail_every == 0 {
+            warn!("Injecting a {} into request {}: {} {}", state.config.fail_status, seen, request.method(), request.uri());
+            let status = StatusCode::from_u16(state.config.fail_status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            return MockError::new(status, "injected_error", "error injected by the mock exchange").into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+#[derive(Deserialize)]
+struct LoginRequest {
+    email: String,
+    password: String
+}
+
+async fn login(State(state): State<SharedState>, Json(request): Json<LoginRequest>) -> Result<Json<Value>, MockError> {
+    let email_matches = state.config.email.as_ref().is_none_or(|email| email == &request.email);
+    let password_matches = state.config.password.as_ref().is_none_or(|password| password == &request.password);
+    if !email_matches || !password_matches {
+        return Err(MockError::new(StatusCode::UNAUTHORIZED, "invalid_credentials", "invalid email or password"));
+    }
+
+    let token = state.issue_token();
+    info!("Logged in {}", request.email);
+    Ok(Json(json!({ "member_id": USER_ID, "token": token })))
+}
+
+async fn create_order(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Json(request): Json<CreateOrderRequest>
+) -> Result<(StatusCode, Json<Value>), MockError> {
+    state.authorize(&headers)?;
+    let now = now();
+    let (order, events) = state.orders.lock().unwrap().create(request, now)?;
+    info!("Created order {} ({}) on {}: {}, {} filled", order.order_id, order.client_order_id, order.ticker, order.status,
+        order.taker_fill_count);
+    state.publish(events, now);
+    Ok((StatusCode::CREATED, Json(json!({ "order": order }))))
+}
+
+async fn cancel_order(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Path(order_id): Path<String>
+) -> Result<Json<Value>, MockError> {
+    state.authorize(&headers)?;
+    let now = now();
+    let (order, reduced_by, events) = state.orders.lock().unwrap().cancel(&order_id, now)?;
+    info!("Cancelled order {} ({})", order.order_id, order.client_order_id);
+    state.publish(events, now);
+    Ok(Json(json!({ "order": order, "reduced_by": reduced_by })))
+}
+
+async fn amend_order(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Path(order_id): Path<String>,
+    Json(request): Json<AmendOrderRequest>
+) -> Result<Json<Value>, MockError> {
+    state.authorize(&headers)?;
+    let now = now();
+    let (old_order, order, events) = state.orders.lock().unwrap().amend(&order_id, request, now)?;
+    info!("Amended order {} to {} at {}", order.order_id, order.place_count, order.yes_price);
+    state.publish(events, now);
+    Ok(Json(json!({ "old_order": old_order, "order": order })))
+}
+
+async fn get_order(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Path(order_id): Path<String>
+) -> Result<Json<Value>, MockError> {
+    state.authorize(&headers)?;
+    let order = state.orders.lock().unwrap().get(&order_id, now())?;
+    Ok(Json(json!({ "order": order })))
+}
+
+async fn get_orders(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(filter): Query<OrderFilter>
+) -> Result<Json<Value>, MockError> {
+    state.authorize(&headers)?;
+    let (orders, cursor) = state.orders.lock().unwrap().list(&filter, now())?;
+    Ok(Json(json!({ "orders": orders, "cursor": cursor })))
+}
+
+async fn get_fills(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(filter): Query<FillFilter>
+) -> Result<Json<Value>, MockError> {
+    state.authorize(&headers)?;
+    let (fills, cursor) = state.fills.lock().unwrap().list(&filter)?;
+    Ok(Json(json!({ "fills": fills, "cursor": cursor })))
+}
+
+async fn get_positions(State(state): State<SharedState>, headers: HeaderMap) -> Result<Json<Value>, MockError> {
+    state.authorize(&headers)?;
+    let market_positions = state.fills.lock().unwrap().positions();
+    Ok(Json(json!({ "market_positions": market_positions, "event_positions": [], "cursor": "" })))
+}
+
+/// Every market is open, and stays open for a day from whenever it is asked about, since the mock
+/// trades any ticker it is sent.
+async fn get_market(State(state): State<SharedState>, headers: HeaderMap, Path(ticker): Path<String>) -> Result<Json<Value>, MockError> {
+    state.authorize(&headers)?;
+    let close_time = Utc.timestamp_opt(now() + MARKET_OPEN_SECS, 0).single().unwrap_or_default().to_rfc3339();
+    Ok(Json(json!({ "market": { "ticker": ticker, "status": "active", "close_time": close_time, "result": "" } })))
+}
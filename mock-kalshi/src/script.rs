@@ -0,0 +1,101 @@
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// One step of what the websocket mock does once a client has subscribed. A script is a JSON
+/// array of steps, each tagged by its `step` field, for example:
+///
+/// ```json
+/// [
+///   {"step": "send", "type": "fill", "msg": {"trade_id": "t1", ...}},
+///   {"step": "send", "type": "fill", "msg": {"trade_id": "t2", ...}, "drop": true},
+///   {"step": "wait", "ms": 500},
+///   {"step": "disconnect"}
+/// ]
+/// ```
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum ScriptStep {
+    /// Pause before the next step.
+    Wait { ms: u64 },
+    /// Send a data message of the given type on the subscription to its channel, numbered with
+    /// the subscription's next sequence number. A dropped message uses up its sequence number
+    /// without being sent, so the client sees a gap; a dropped fill can still be backfilled over REST.
+    Send {
+        #[serde(rename = "type")]
+        msg_type: String,
+        msg: Value,
+        #[serde(default)]
+        drop: bool
+    },
+    /// Send an error that answers no command.
+    Error { code: u32, msg: String },
+    /// Send a text frame exactly as given, to exercise the client's parsing.
+    Raw { text: String },
+    /// End the connection, with a close frame or, if abrupt, by dropping the socket.
+    Disconnect {
+        #[serde(default)]
+        abrupt: bool
+    }
+}
+
+/// The scripted steps, played in order across connections: steps left after a disconnect are
+/// played on the next connection once it subscribes.
+pub struct Script {
+    steps: Vec<ScriptStep>,
+    next: Mutex<usize>
+}
+
+impl Script {
+    pub fn new(steps: Vec<ScriptStep>) -> Script {
+        Script { steps, next: Mutex::new(0) }
+    }
+
+    /// Load a script from a JSON file, or an empty one if no file is given.
+    pub fn load(path: Option<&Path>) -> Result<Script> {
+        let steps = match path {
+            Some(path) => serde_json::from_slice(&std::fs::read(path)?)
+                .map_err(|e| anyhow::anyhow!("Could not parse websocket script {:?}: {}", path, e))?,
+            None => Vec::new()
+        };
+        Ok(Script::new(steps))
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Take the next step to play, if any are left.
+    pub fn next_step(&self) -> Option<ScriptStep> {
+        let mut next = self.next.lock().unwrap();
+        let step = self.steps.get(*next).cloned();
+        if step.is_some() {
+            *next += 1;
+        }
+        step
+    }
+}
+
+/// The channel a data message of the given type is delivered on.
+pub fn channel_of(msg_type: &str) -> Option<&'static str> {
+    match msg_type {
+        "fill" => Some("fill"),
+        "orderbook_snapshot" | "orderbook_delta" => Some("orderbook_delta"),
+        "ticker" => Some("ticker"),
+        "trade" => Some("trade"),
+        "user_order" => Some("user_orders"),
+        "market_lifecycle" => Some("market_lifecycle"),
+        _ => None
+    }
+}
+
+/// Whether messages of the given type carry a sequence number, so that gaps can be detected.
+pub fn is_sequenced(msg_type: &str) -> bool {
+    matches!(msg_type, "fill" | "orderbook_snapshot" | "orderbook_delta")
+}
@@ -0,0 +1,128 @@
+use axum::http::{header, HeaderMap};
+use log::debug;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+use crate::config::MockConfig;
+use crate::error::MockError;
+use crate::fills::Fills;
+use crate::orders::{OrderEvents, Orders};
+
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(1);
+const EVENT_BUFFER: usize = 1024;
+const ACCESS_KEY_HEADER: &str = "kalshi-access-key";
+const SIGNED_HEADERS: [&str; 2] = ["kalshi-access-timestamp", "kalshi-access-signature"];
+
+/// A message for websocket subscribers, as its type and body, before it is framed for a connection.
+pub type Event = (String, Value);
+
+/// Everything the mock exchange holds between requests, shared by its REST API and websocket.
+pub struct MockState {
+    pub config: MockConfig,
+    pub orders: Mutex<Orders>,
+    pub fills: Mutex<Fills>,
+    pub requests_seen: AtomicU64,
+    pub events: broadcast::Sender<Event>,
+    tokens: Mutex<HashMap<String, Instant>>,
+    rate_window: Mutex<(Instant, u32)>
+}
+
+pub type SharedState = Arc<MockState>;
+
+impl MockState {
+    pub fn new(config: MockConfig) -> SharedState {
+        Arc::new(MockState {
+            config,
+            orders: Mutex::new(Orders::new()),
+            fills: Mutex::new(Fills::new()),
+            requests_seen: AtomicU64::new(0),
+            events: broadcast::channel(EVENT_BUFFER).0,
+            tokens: Mutex::new(HashMap::new()),
+            rate_window: Mutex::new((Instant::now(), 0))
+        })
+    }
+
+    /// Issue a new session token, accepted by every endpoint and the websocket until it expires.
+    pub fn issue_token(&self) -> String {
+        let mut tokens = self.tokens.lock().unwrap();
+        let token = format!("mock-token-{}", tokens.len() + 1);
+        tokens.insert(token.clone(), Instant::now());
+        token
+    }
+
+    /// Check the request carries a token issued by login, either bare or after `Bearer`, or
+    /// is signed with an API key.
+    pub fn authorize(&self, headers: &HeaderMap) -> Result<(), MockError> {
+        if let Some(key_id) = headers.get(ACCESS_KEY_HEADER).and_then(|value| value.to_str().ok()) {
+            let signed = SIGNED_HEADERS.iter().all(|header| headers.contains_key(*header));
+            return match signed && self.config.api_key_id.as_ref().is_none_or(|id| id == key_id) {
+                true => Ok(()),
+                false => Err(MockError::unauthorized())
+            };
+        }
+        let token = headers.get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_whitespace().last())
+            .ok_or_else(MockError::unauthorized)?;
+        match self.tokens.lock().unwrap().get(token) {
+            Some(issued) if self.config.token_ttl.is_none_or(|ttl| issued.elapsed() < ttl) => Ok(()),
+            _ => Err(MockError::unauthorized())
+        }
+    }
+
+    /// Record the fills a request produced, so REST can list them, and push them and the
+    /// orders it changed to every websocket connection.
+    pub fn publish(&self, events: OrderEvents, now: i64) {
+        let mut messages = Vec::new();
+        for order in events.orders {
+            let fill_count = order.taker_fill_count + order.maker_fill_count;
+            messages.push(("user_order".to_string(), json!({
+                "order_id": order.order_id,
+                "client_order_id": order.client_order_id,
+                "ticker": order.ticker,
+                "status": order.status,
+                "side": order.side,
+                "action": order.action,
+                "yes_price": order.yes_price,
+                "no_price": order.no_price,
+                "remaining_count": order.remaining_count,
+                "fill_count": fill_count,
+                "expiration_ts": order.expiration_ts,
+                "ts": now
+            })));
+        }
+        let mut fills = self.fills.lock().unwrap();
+        for fill in events.fills {
+            messages.push(("fill".to_string(), serde_json::to_value(&fill).unwrap_or_default()));
+            fills.record(fill);
+        }
+        for message in messages {
+            // with nobody connected there is no one to tell
+            if self.events.send(message).is_err() {
+                debug!("No websocket connections for order events");
+            }
+        }
+    }
+
+    /// Count a request against the rate limit, returning false once the current window is full.
+    pub fn within_rate_limit(&self) -> bool {
+        let limit = match self.config.rate_limit_per_sec {
+            Some(limit) => limit,
+            None => return true
+        };
+        let mut window = self.rate_window.lock().unwrap();
+        if window.0.elapsed() >= RATE_LIMIT_WINDOW {
+            *window = (Instant::now(), 0);
+        }
+        window.1 += 1;
+        window.1 <= limit
+    }
+}
+
+pub fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
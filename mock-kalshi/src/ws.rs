@@ -0,0 +1,293 @@
+use anyhow::Result;
+use futures_util::{SinkExt, StreamExt};
+use log::{debug, info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{accept_hdr_async, WebSocketStream};
+
+use crate::fills::MockFill;
+use crate::script::{self, Script, ScriptStep};
+use crate::state::SharedState;
+
+const PING_INTERVAL: Duration = Duration::from_secs(10);
+const CHANNELS: [&str; 6] = ["fill", "user_orders", "market_lifecycle", "orderbook_delta", "ticker", "trade"];
+
+/// Kalshi's websocket error codes for the commands the mock refuses.
+const UNABLE_TO_PROCESS: u32 = 1;
+const CHANNELS_REQUIRED: u32 = 3;
+const SIDS_REQUIRED: u32 = 4;
+const UNKNOWN_COMMAND: u32 = 5;
+const ALREADY_SUBSCRIBED: u32 = 6;
+const UNKNOWN_SID: u32 = 7;
+const UNKNOWN_CHANNEL: u32 = 8;
+const ONE_SID_REQUIRED: u32 = 12;
+const UNSUPPORTED_ACTION: u32 = 13;
+
+/// A command from the client: `{"id": 1, "cmd": "subscribe", "params": {...}}`.
+#[derive(Deserialize)]
+struct Command {
+    id: u32,
+    cmd: String,
+    #[serde(default)]
+    params: Value
+}
+
+#[derive(Deserialize, Default)]
+struct CommandParams {
+    #[serde(default)]
+    channels: Vec<String>,
+    #[serde(default)]
+    sids: Vec<u32>,
+    market_tickers: Option<Vec<String>>,
+    action: Option<String>
+}
+
+/// A subscription on one connection.
+struct Subscription {
+    channel: String,
+    next_seq: u32,
+    market_tickers: Option<Vec<String>>
+}
+
+/// The subscriptions on one connection, by sid.
+#[derive(Default)]
+struct Subscriptions {
+    next_sid: u32,
+    by_sid: BTreeMap<u32, Subscription>
+}
+
+impl Subscriptions {
+
+    /// Answer a command, returning the messages to send back.
+    fn handle_command(&mut self, text: &str) -> Vec<Value> {
+        let command: Command = match serde_json::from_str(text) {
+            Ok(command) => command,
+            Err(_) => return vec![error(None, UNABLE_TO_PROCESS, "Unable to process message")]
+        };
+        let params: CommandParams = serde_json::from_value(command.params).unwrap_or_default();
+        let id = command.id;
+        match command.cmd.as_str() {
+            "subscribe" => self.subscribe(id, params),
+            "unsubscribe" => self.unsubscribe(id, params),
+            "update_subscription" => vec![self.update_subscription(id, params)],
+            "list_subscriptions" => {
+                let subscriptions: Vec<Value> = self.by_sid.iter()
+                    .map(|(sid, subscription)| json!({ "channel": subscription.channel, "sid": sid }))
+                    .collect();
+                vec![json!({ "id": id, "type": "ok", "msg": subscriptions })]
+            },
+            _ => vec![error(Some(id), UNKNOWN_COMMAND, "Unknown command")]
+        }
+    }
+
+    fn subscribe(&mut self, id: u32, params: CommandParams) -> Vec<Value> {
+        if params.channels.is_empty() {
+            return vec![error(Some(id), CHANNELS_REQUIRED, "Channels required")];
+        }
+        params.channels.into_iter().map(|channel| {
+            if !CHANNELS.contains(&channel.as_str()) {
+                return error(Some(id), UNKNOWN_CHANNEL, "Unknown channel name");
+            }
+            if self.sid_of(&channel).is_some() {
+                return error(Some(id), ALREADY_SUBSCRIBED, "Already subscribed");
+            }
+            self.next_sid += 1;
+            let sid = self.next_sid;
+            self.by_sid.insert(sid, Subscription { channel: channel.clone(), next_seq: 1, market_tickers: params.market_tickers.clone() });
+            json!({ "id": id, "type": "subscribed", "msg": { "channel": channel, "sid": sid } })
+        }).collect()
+    }
+
+    fn unsubscribe(&mut self, id: u32, params: CommandParams) -> Vec<Value> {
+        if params.sids.is_empty() {
+            return vec![error(Some(id), SIDS_REQUIRED, "Subscription IDs required")];
+        }
+        params.sids.into_iter().map(|sid| match self.by_sid.remove(&sid) {
+            Some(_) => json!({ "id": id, "type": "unsubscribed", "sid": sid }),
+            None => error(Some(id), UNKNOWN_SID, "Unknown subscription ID")
+        }).collect()
+    }
+
+    fn update_subscription(&mut self, id: u32, params: CommandParams) -> Value {
+        let sid = match params.sids.as_slice() {
+            [sid] => *sid,
+            _ => return error(Some(id), ONE_SID_REQUIRED, "Exactly one subscription ID is required")
+        };
+        let subscription = match self.by_sid.get_mut(&sid) {
+            Some(subscription) => subscription,
+            None => return error(Some(id), UNKNOWN_SID, "Unknown subscription ID")
+        };
+        let changed = params.market_tickers.unwrap_or_default();
+        let mut market_tickers = subscription.market_tickers.take().unwrap_or_default();
+        match params.action.as_deref() {
+            Some("add_markets") => for ticker in changed {
+                if !market_tickers.contains(&ticker) {
+                    market_tickers.push(ticker);
+                }
+            },
+            Some("delete_markets") => market_tickers.retain(|ticker| !changed.contains(ticker)),
+            _ => {
+                subscription.market_tickers = Some(market_tickers);
+                return error(Some(id), UNSUPPORTED_ACTION, "Unsupported action");
+            }
+        }
+        subscription.market_tickers = Some(market_tickers.clone());
+        json!({ "id": id, "sid": sid, "type": "ok", "msg": { "market_tickers": market_tickers } })
+    }
+
+    fn sid_of(&self, channel: &str) -> Option<u32> {
+        self.by_sid.iter().find(|(_, subscription)| subscription.channel == channel).map(|(sid, _)| *sid)
+    }
+
+    /// Number a data message for the subscription to its channel, unless the client has no
+    /// subscription covering its market.
+    fn frame(&mut self, msg_type: &str, msg: Value) -> Option<Value> {
+        let sid = self.sid_of(script::channel_of(msg_type)?)?;
+        let subscription = self.by_sid.get_mut(&sid)?;
+        if let (Some(market_tickers), Some(ticker)) = (&subscription.market_tickers, msg.get("market_ticker").and_then(Value::as_str)) {
+            if !market_tickers.iter().any(|market_ticker| market_ticker == ticker) {
+                return None;
+            }
+        }
+        if script::is_sequenced(msg_type) {
+            let seq = subscription.next_seq;
+            subscription.next_seq += 1;
+            Some(json!({ "type": msg_type, "sid": sid, "seq": seq, "msg": msg }))
+        } else {
+            Some(json!({ "type": msg_type, "sid": sid, "msg": msg }))
+        }
+    }
+}
+
+fn error(id: Option<u32>, code: u32, msg: &str) -> Value {
+    match id {
+        Some(id) => json!({ "id": id, "type": "error", "msg": { "code": code, "msg": msg } }),
+        None => json!({ "type": "error", "msg": { "code": code, "msg": msg } })
+    }
+}
+
+/// Accept websocket connections, each authenticated with a token from the REST API's login or signed with an API key.
+pub async fn serve(listener: TcpListener, state: SharedState, script: Arc<Script>) -> Result<()> {
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let (state, script) = (state.clone(), script.clone());
+        tokio::spawn(async move {
+            info!("Websocket connection from {}", peer);
+            match handle_connection(stream, state, script).await {
+                Ok(()) => info!("Websocket connection from {} ended", peer),
+                Err(e) => warn!("Websocket connection from {} failed: {:?}", peer, e)
+            }
+        });
+    }
+}
+
+async fn handle_connection(stream: TcpStream, state: SharedState, script: Arc<Script>) -> Result<()> {
+    // the handshake callback's signature is tungstenite's, refusal and all
+    #[allow(clippy::result_large_err)]
+    let authenticate = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+        match state.authorize(request.headers()) {
+            Ok(()) => Ok(response),
+            Err(_) => {
+                let mut refusal = ErrorResponse::new(Some("missing or unknown token".to_string()));
+                *refusal.status_mut() = StatusCode::UNAUTHORIZED;
+                Err(refusal)
+            }
+        }
+    };
+    let mut ws = accept_hdr_async(stream, authenticate).await?;
+    let mut events = state.events.subscribe();
+
+    let mut subscriptions = Subscriptions::default();
+    let (mut script_started, mut script_done) = (false, false);
+    let step_timer = tokio::time::sleep(Duration::ZERO);
+    tokio::pin!(step_timer);
+    let mut ping = tokio::time::interval(PING_INTERVAL);
+
+    loop {
+        tokio::select! {
+            incoming = ws.next() => match incoming {
+                None | Some(Ok(Message::Close(_))) => return Ok(()),
+                Some(Err(e)) => return Err(e.into()),
+                Some(Ok(Message::Text(text))) => {
+                    debug!("Command: {}", text);
+                    for reply in subscriptions.handle_command(&text) {
+                        ws.send(Message::Text(reply.to_string())).await?;
+                    }
+                    if !script_started && !subscriptions.by_sid.is_empty() {
+                        script_started = true;
+                        step_timer.as_mut().reset(Instant::now());
+                    }
+                },
+                Some(Ok(_)) => {}
+            },
+            () = &mut step_timer, if script_started && !script_done => {
+                match script.next_step() {
+                    None => script_done = true,
+                    Some(ScriptStep::Wait { ms }) => step_timer.as_mut().reset(Instant::now() + Duration::from_millis(ms)),
+                    Some(ScriptStep::Disconnect { abrupt }) => {
+                        info!("Script disconnecting{}", if abrupt { " abruptly" } else { "" });
+                        if !abrupt {
+                            ws.close(None).await?;
+                        }
+                        return Ok(());
+                    },
+                    Some(step) => {
+                        play(step, &mut ws, &mut subscriptions, &state).await?;
+                        step_timer.as_mut().reset(Instant::now());
+                    }
+                }
+            },
+            event = events.recv() => match event {
+                Ok((msg_type, msg)) => if let Some(frame) = subscriptions.frame(&msg_type, msg) {
+                    debug!("Sending {}", frame);
+                    ws.send(Message::Text(frame.to_string())).await?;
+                },
+                Err(RecvError::Lagged(missed)) => warn!("Websocket connection fell {} order event(s) behind", missed),
+                Err(RecvError::Closed) => return Ok(())
+            },
+            _ = ping.tick() => ws.send(Message::Ping(Vec::new())).await?
+        }
+    }
+}
+
+/// Play a step that sends something on the connection.
+async fn play(step: ScriptStep, ws: &mut WebSocketStream<TcpStream>, subscriptions: &mut Subscriptions, state: &SharedState) -> Result<()> {
+    let text = match step {
+        ScriptStep::Send { msg_type, msg, drop } => {
+            // fills happen whether or not anyone is subscribed, so REST backfill can find them
+            if msg_type == "fill" {
+                match serde_json::from_value::<MockFill>(msg.clone()) {
+                    Ok(fill) => state.fills.lock().unwrap().record(fill),
+                    Err(e) => warn!("Scripted fill is not a valid fill, so it will not be backfilled: {}", e)
+                }
+            }
+            let frame = match subscriptions.frame(&msg_type, msg) {
+                Some(frame) => frame,
+                None => {
+                    debug!("Not sending scripted {} message, as nothing subscribed covers it", msg_type);
+                    return Ok(());
+                }
+            };
+            if drop {
+                debug!("Dropping scripted message {}", frame);
+                return Ok(());
+            }
+            frame.to_string()
+        },
+        ScriptStep::Error { code, msg } => error(None, code, &msg).to_string(),
+        ScriptStep::Raw { text } => text,
+        ScriptStep::Wait { .. } | ScriptStep::Disconnect { .. } => return Ok(())
+    };
+    debug!("Sending {}", text);
+    ws.send(Message::Text(text)).await?;
+    Ok(())
+}
@@ -0,0 +1,237 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::Duration;
+use std::path::{Path, PathBuf};
+
+use kalshi_rest::{Environment, EnvironmentConfig};
+use queue_client::queue_names::QueueNames;
+
+/// The config file to load. `oms.toml` in the working directory is read if it exists, and
+/// the defaults are used otherwise; a file named here must exist.
+pub const CONFIG_PATH_VAR: &str = "OMS_CONFIG";
+pub const DEFAULT_CONFIG_PATH: &str = "oms.toml";
+
+/// Environment variables that override single settings of the file, for deployments
+/// that differ from it in one or two places.
+pub const BROKER_URL_VAR: &str = "OMS_BROKER_URL";
+pub const QUEUE_PREFIX_VAR: &str = "OMS_QUEUE_PREFIX";
+pub const LISTEN_ADDR_VAR: &str = "OMS_LISTEN_ADDR";
+pub const PAPER_CLIENTS_VAR: &str = "OMS_PAPER_CLIENTS";
+pub const CANCEL_ON_DISCONNECT_GRACE_SECS_VAR: &str = "OMS_CANCEL_ON_DISCONNECT_GRACE_SECS";
+pub const KALSHI_ENV_VAR: &str = "KALSHI_ENV";
+pub const KALSHI_API_URL_VAR: &str = "KALSHI_API_URL";
+pub const KALSHI_WS_URL_VAR: &str = "KALSHI_WS_URL";
+pub const KALSHI_CREDENTIALS_ENV_VAR: &str = "KALSHI_CREDENTIALS_ENV";
+pub const MAX_POSITION_PER_TICKER_VAR: &str = "OMS_MAX_POSITION_PER_TICKER";
+pub const LOG_LEVEL_VAR: &str = "RUST_LOG";
+
+const LOG_LEVELS: [&str; 6] = ["trace", "debug", "info", "warn", "error", "off"];
+
+/// Everything about how the OMS is deployed, shared by all of its binaries: where the
+/// broker is and what its queues are called, where the client-server listens, which Kalshi
+/// environment to trade in, the risk limits and how much to log. Credentials are not part
+/// of it; see `kalshi_rest::Credentials`. `oms.example.toml` documents every setting.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OmsConfig {
+    pub broker: BrokerConfig,
+    pub client_server: ClientServerConfig,
+    pub kalshi: KalshiConfig,
+    pub risk: RiskConfig,
+    pub logging: LoggingConfig,
+    /// The file the config was read from, if any.
+    #[serde(skip)]
+    pub source: Option<PathBuf>
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BrokerConfig {
+    /// The RabbitMQ broker every component's queues are on.
+    pub url: String,
+    pub queues: QueueNames
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientServerConfig {
+    /// Where trading clients connect.
+    pub listen_addr: String,
+    /// Clients that always paper trade, whatever their login asks for.
+    pub paper_clients: Vec<String>,
+    /// How long a client that logged in with cancel on disconnect has to log back in
+    /// before its open orders are cancelled.
+    pub cancel_on_disconnect_grace_secs: u64
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KalshiConfig {
+    pub environment: Environment,
+    /// Overrides the environment's REST base URL, such as to point at the mock exchange.
+    pub api_url: Option<String>,
+    /// Overrides the environment's websocket URL.
+    pub ws_url: Option<String>,
+    /// The environment the credentials belong to, so that the binaries refuse to start against the other one.
+    pub credentials_environment: Option<Environment>
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskConfig {
+    /// The most contracts a client's position in one ticker may reach, long or short, counting its working orders.
+    pub max_position_per_ticker: i64
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// A level, or comma-separated `target=level` directives, as `RUST_LOG` takes them.
+    pub level: String
+}
+
+impl Default for BrokerConfig {
+    fn default() -> Self {
+        BrokerConfig { url: "amqp://localhost:5672".to_string(), queues: QueueNames::default() }
+    }
+}
+
+impl Default for ClientServerConfig {
+    fn default() -> Self {
+        ClientServerConfig { listen_addr: "127.0.0.1:8080".to_string(), paper_clients: Vec::new(), cancel_on_disconnect_grace_secs: 5 }
+    }
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        RiskConfig { max_position_per_ticker: 1000 }
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig { level: "info".to_string() }
+    }
+}
+
+impl OmsConfig {
+
+    /// Read the config file, apply the environment's overrides and validate the result.
+    pub fn load() -> Result<OmsConfig> {
+        let mut config = match std::env::var(CONFIG_PATH_VAR) {
+            Ok(path) => OmsConfig::read(Path::new(&path))?,
+            Err(_) if Path::new(DEFAULT_CONFIG_PATH).exists() => OmsConfig::read(Path::new(DEFAULT_CONFIG_PATH))?,
+            Err(_) => OmsConfig::default()
+        };
+        config.apply_overrides()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn read(path: &Path) -> Result<OmsConfig> {
+        let contents = std::fs::read_to_string(path).with_context(|| format!("Could not read the config file {}", path.display()))?;
+        let mut config: OmsConfig = toml::from_str(&contents).with_context(|| format!("Invalid config file {}", path.display()))?;
+        config.source = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    fn apply_overrides(&mut self) -> Result<()> {
+        if let Ok(url) = std::env::var(BROKER_URL_VAR) {
+            self.broker.url = url;
+        }
+        if let Ok(prefix) = std::env::var(QUEUE_PREFIX_VAR) {
+            self.broker.queues.prefix = prefix;
+        }
+        if let Ok(addr) = std::env::var(LISTEN_ADDR_VAR) {
+            self.client_server.listen_addr = addr;
+        }
+        if let Ok(clients) = std::env::var(PAPER_CLIENTS_VAR) {
+            self.client_server.paper_clients = clients.split(',')
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .collect();
+        }
+        if let Ok(secs) = std::env::var(CANCEL_ON_DISCONNECT_GRACE_SECS_VAR) {
+            self.client_server.cancel_on_disconnect_grace_secs = secs.parse()
+                .map_err(|_| anyhow::anyhow!("{} is not a valid value for {}", secs, CANCEL_ON_DISCONNECT_GRACE_SECS_VAR))?;
+        }
+        if let Ok(name) = std::env::var(KALSHI_ENV_VAR) {
+            self.kalshi.environment = Environment::parse(&name)?;
+        }
+        if let Ok(url) = std::env::var(KALSHI_API_URL_VAR) {
+            self.kalshi.api_url = Some(url);
+        }
+        if let Ok(url) = std::env::var(KALSHI_WS_URL_VAR) {
+            self.kalshi.ws_url = Some(url);
+        }
+        if let Ok(name) = std::env::var(KALSHI_CREDENTIALS_ENV_VAR) {
+            self.kalshi.credentials_environment = Some(Environment::parse(&name)?);
+        }
+        if let Ok(limit) = std::env::var(MAX_POSITION_PER_TICKER_VAR) {
+            self.risk.max_position_per_ticker = limit.parse()
+                .map_err(|_| anyhow::anyhow!("{} is not a valid value for {}", limit, MAX_POSITION_PER_TICKER_VAR))?;
+        }
+        if let Ok(level) = std::env::var(LOG_LEVEL_VAR) {
+            self.logging.level = level;
+        }
+        Ok(())
+    }
+
+    /// Refuse a config the binaries could not run with, so that it fails at startup rather than on first use.
+    pub fn validate(&self) -> Result<()> {
+        if !self.broker.url.starts_with("amqp://") && !self.broker.url.starts_with("amqps://") {
+            return Err(anyhow::anyhow!("broker.url {:?} is not an amqp:// or amqps:// URL", self.broker.url));
+        }
+        self.broker.queues.validate().context("Invalid broker.queues")?;
+        self.listen_addr()?;
+        self.kalshi.environment_config().context("Invalid kalshi settings")?;
+        if self.risk.max_position_per_ticker <= 0 {
+            return Err(anyhow::anyhow!("risk.max_position_per_ticker must be positive, not {}", self.risk.max_position_per_ticker));
+        }
+        self.logging.validate()?;
+        Ok(())
+    }
+
+    pub fn listen_addr(&self) -> Result<SocketAddr> {
+        self.client_server.listen_addr.parse()
+            .map_err(|_| anyhow::anyhow!("client_server.listen_addr {:?} is not an address and port", self.client_server.listen_addr))
+    }
+}
+
+/// Where the config came from, for the binaries to log as they start.
+impl fmt::Display for OmsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            Some(path) => write!(f, "{}", path.display())?,
+            None => write!(f, "defaults")?
+        }
+        write!(f, " (broker {}, queue prefix {:?}, log level {})", self.broker.url, self.broker.queues.prefix, self.logging.level)
+    }
+}
+
+impl ClientServerConfig {
+    pub fn cancel_on_disconnect_grace_period(&self) -> Duration {
+        Duration::from_secs(self.cancel_on_disconnect_grace_secs)
+    }
+}
+
+impl KalshiConfig {
+    pub fn environment_config(&self) -> Result<EnvironmentConfig> {
+        EnvironmentConfig::new(self.environment, self.api_url.clone(), self.ws_url.clone(), self.credentials_environment)
+    }
+}
+
+impl LoggingConfig {
+    fn validate(&self) -> Result<()> {
+        for directive in self.level.split(',').map(str::trim).filter(|directive| !directive.is_empty()) {
+            let level = directive.rsplit('=').next().unwrap_or(directive).to_ascii_lowercase();
+            // a bare target, such as `lapin`, logs everything from it
+            if !LOG_LEVELS.contains(&level.as_str()) && directive.contains('=') {
+                return Err(anyhow::anyhow!("logging.level {:?} has an unknown level {:?}; levels are {}", self.level, level, LOG_LEVELS.join(", ")));
+            }
+        }
+        Ok(())
+    }
+}
@@ -2,3 +2,25 @@ pub const MESSAGE_LENGTH_SIZE: usize = 1;
 pub const LOGIN_HEADER: u8 = 0;
 pub const ORDER_HEADER: u8 = 1;
 pub const CANCEL_HEADER: u8 = 2;
+pub const ORDER_CONFIRM_HEADER: u8 = 3;
+pub const CANCEL_CONFIRM_HEADER: u8 = 4;
+pub const FILL_HEADER: u8 = 5;
+pub const ORDER_REJECT_HEADER: u8 = 6;
+pub const STATUS_REQUEST_HEADER: u8 = 7;
+pub const ORDER_STATUS_HEADER: u8 = 8;
+pub const ORDER_STATUS_END_HEADER: u8 = 9;
+pub const POSITION_REQUEST_HEADER: u8 = 10;
+pub const POSITION_HEADER: u8 = 11;
+pub const POSITION_END_HEADER: u8 = 12;
+pub const MARKET_DATA_SUBSCRIBE_HEADER: u8 = 13;
+pub const MARKET_DATA_UNSUBSCRIBE_HEADER: u8 = 14;
+pub const TOP_OF_BOOK_HEADER: u8 = 15;
+pub const TRADE_HEADER: u8 = 16;
+pub const TICKER_HEADER: u8 = 17;
+pub const CANCEL_REJECT_HEADER: u8 = 18;
+pub const FEED_STATUS_HEADER: u8 = 19;
+pub const CANCEL_ON_DISCONNECT_FLAG: u8 = 0b0000_0001;
+pub const PAPER_TRADING_FLAG: u8 = 0b0000_0010;
+pub const TOP_OF_BOOK_FLAG: u8 = 0b0000_0001;
+pub const TRADES_FLAG: u8 = 0b0000_0010;
+pub const TICKER_UPDATES_FLAG: u8 = 0b0000_0100;
@@ -1,2 +1,4 @@
 pub mod read;
+pub mod write;
+pub mod messages;
 mod constants;
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use queue_client::queue_data::orders::OrderState;
+use queue_client::queue_data::market_data::{BookMessage, PriceLevel};
+
+use crate::constants::{ORDER_STATUS_HEADER, ORDER_STATUS_END_HEADER, POSITION_HEADER, POSITION_END_HEADER, TOP_OF_BOOK_HEADER};
+use crate::write::ClientMessage;
+
+/// What the OMS knows about a single order, sent in answer to a status request.
+/// A state of None means neither the OMS nor the exchange knows the order.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OrderStatusMessage {
+    pub client_order_id: String,
+    pub order_id: Option<String>,
+    pub state: Option<OrderState>,
+    pub filled_count: i32,
+    pub average_fill_price: Option<f64>
+}
+
+impl ClientMessage for OrderStatusMessage {
+    fn header() -> u8 {
+        ORDER_STATUS_HEADER
+    }
+}
+
+impl OrderStatusMessage {
+    pub fn unknown(client_order_id: String) -> Self {
+        OrderStatusMessage {
+            client_order_id,
+            order_id: None,
+            state: None,
+            filled_count: 0,
+            average_fill_price: None
+        }
+    }
+}
+
+/// Terminates the answer to a status request, carrying the number of statuses sent.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OrderStatusEndMessage {
+    pub count: u32
+}
+
+impl ClientMessage for OrderStatusEndMessage {
+    fn header() -> u8 {
+        ORDER_STATUS_END_HEADER
+    }
+}
+
+/// A client's position in one ticker, in YES-equivalent contracts with prices and P&L in cents.
+/// Unrealized P&L is absent when the OMS has no price to mark the position to.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PositionMessage {
+    pub ticker: String,
+    pub net_contracts: i64,
+    pub average_price: f64,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: Option<f64>
+}
+
+impl ClientMessage for PositionMessage {
+    fn header() -> u8 {
+        POSITION_HEADER
+    }
+}
+
+/// Terminates the answer to a position request, carrying the number of positions sent.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PositionEndMessage {
+    pub count: u32
+}
+
+impl ClientMessage for PositionEndMessage {
+    fn header() -> u8 {
+        POSITION_END_HEADER
+    }
+}
+
+/// The best YES bid and ask in a market, with prices in cents. A side is absent when nothing rests on it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TopOfBookMessage {
+    pub ticker: String,
+    pub yes_bid: Option<PriceLevel>,
+    pub yes_ask: Option<PriceLevel>,
+    pub ts: i64
+}
+
+impl ClientMessage for TopOfBookMessage {
+    fn header() -> u8 {
+        TOP_OF_BOOK_HEADER
+    }
+}
+
+impl From<&BookMessage> for TopOfBookMessage {
+    fn from(book: &BookMessage) -> Self {
+        TopOfBookMessage {
+            ticker: book.market_ticker.clone(),
+            yes_bid: book.best_yes_bid(),
+            yes_ask: book.best_yes_ask(),
+            ts: book.ts
+        }
+    }
+}
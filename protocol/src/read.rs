@@ -1,74 +1,286 @@
 use anyhow::Result;
-use tokio::net::TcpStream;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use queue_client::queue_data::data_core::QueueData;
 use queue_client::queue_data::orders::CreateOrderMessage;
 use queue_client::queue_data::cancels::CancelOrderMessage;
 
-use crate::constants::{LOGIN_HEADER, MESSAGE_LENGTH_SIZE, ORDER_HEADER, CANCEL_HEADER};
+use crate::constants::{LOGIN_HEADER, MESSAGE_LENGTH_SIZE, ORDER_HEADER, CANCEL_HEADER, STATUS_REQUEST_HEADER, POSITION_REQUEST_HEADER, CANCEL_ON_DISCONNECT_FLAG, PAPER_TRADING_FLAG};
+use crate::constants::{MARKET_DATA_SUBSCRIBE_HEADER, MARKET_DATA_UNSUBSCRIBE_HEADER, TOP_OF_BOOK_FLAG, TRADES_FLAG, TICKER_UPDATES_FLAG};
 
 enum IncomingMessageType {
     Order,
     Cancel,
     Login,
+    StatusRequest,
+    PositionRequest,
+    MarketDataSubscribe,
+    MarketDataUnsubscribe,
 }
 
-enum IncomingMessage {
+#[derive(Serialize, Deserialize)]
+pub enum IncomingMessage {
     Order(CreateOrderMessage),
     Cancel(CancelOrderMessage),
-    Login(String)
+    Login(Login),
+    StatusRequest(StatusRequest),
+    PositionRequest(PositionRequest),
+    MarketDataSubscribe(MarketDataRequest),
+    MarketDataUnsubscribe(String)
+}
+
+/// A client's login request: its name and the session options it opted into.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Login {
+    pub name: String,
+    pub cancel_on_disconnect: bool,
+    /// Route the client's orders to the simulated exchange rather than Kalshi, where they
+    /// trade against other paper orders and the live book. Paper state is not journaled and
+    /// starts empty whenever the OMS restarts.
+    #[serde(default)]
+    pub paper_trading: bool
+}
+
+/// A client's request for what the OMS knows about its orders.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum StatusRequest {
+    Order(String),
+    AllOpenOrders
+}
+
+/// A client's request for its positions.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum PositionRequest {
+    Ticker(String),
+    AllTickers
+}
+
+/// A client's request for market data on a ticker, and which kinds of it to send.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketDataRequest {
+    pub ticker: String,
+    pub top_of_book: bool,
+    pub trades: bool,
+    pub ticker_updates: bool
 }
 
 /*
-Reads an order from the TcpStream.
+Reads an order from the client's connection.
 Only call when the message header has already been read and the next message is known to be an order.
 */
-async fn read_order(input: &TcpStream) -> Result<CreateOrderMessage> {
-    todo!()
+async fn read_order<R: AsyncRead + Unpin>(input: &mut R) -> Result<CreateOrderMessage> {
+    let order_length = read_message_length(input).await?;
+    CreateOrderMessage::from_bytes(&read_n_bytes(input, order_length).await?)
 }
 
 /*
-Reads a cancel from the TcpStream.
+Reads a cancel from the client's connection.
 Only call when the message header has already been read and the next message is known to be an cancel.
 */
-async fn read_cancel(input: &TcpStream) -> Result<CancelOrderMessage> {
-    todo!()
+async fn read_cancel<R: AsyncRead + Unpin>(input: &mut R) -> Result<CancelOrderMessage> {
+    let cancel_length = read_message_length(input).await?;
+    CancelOrderMessage::from_bytes(&read_n_bytes(input, cancel_length).await?)
+}
+
+/*
+Reads a status request from the client's connection.
+An empty body asks for all open orders; otherwise the body is the client order id to look up.
+*/
+async fn read_status_request<R: AsyncRead + Unpin>(input: &mut R) -> Result<StatusRequest> {
+    match read_message_length(input).await? {
+        0 => Ok(StatusRequest::AllOpenOrders),
+        id_length => Ok(StatusRequest::Order(String::from_utf8(read_n_bytes(input, id_length).await?)?))
+    }
+}
+
+/*
+Reads a position request from the client's connection.
+An empty body asks for positions in all tickers; otherwise the body is the ticker to look up.
+*/
+async fn read_position_request<R: AsyncRead + Unpin>(input: &mut R) -> Result<PositionRequest> {
+    match read_message_length(input).await? {
+        0 => Ok(PositionRequest::AllTickers),
+        ticker_length => Ok(PositionRequest::Ticker(String::from_utf8(read_n_bytes(input, ticker_length).await?)?))
+    }
+}
+
+/*
+Reads a market data subscription from the client's connection.
+The body is a flags byte choosing the kinds of market data, followed by the ticker.
+*/
+async fn read_market_data_subscribe<R: AsyncRead + Unpin>(input: &mut R) -> Result<MarketDataRequest> {
+    let subscribe_length = read_message_length(input).await?;
+    let body = read_n_bytes(input, subscribe_length).await?;
+    let (flags, ticker) = match body.split_first() {
+        Some(parts) => parts,
+        None => return Err(anyhow::anyhow!("Empty market data subscription")),
+    };
+    Ok(MarketDataRequest {
+        ticker: String::from_utf8(ticker.to_vec())?,
+        top_of_book: flags & TOP_OF_BOOK_FLAG != 0,
+        trades: flags & TRADES_FLAG != 0,
+        ticker_updates: flags & TICKER_UPDATES_FLAG != 0
+    })
+}
+
+/*
+Reads a market data unsubscription from the client's connection. The body is the ticker.
+*/
+async fn read_market_data_unsubscribe<R: AsyncRead + Unpin>(input: &mut R) -> Result<String> {
+    let ticker_length = read_message_length(input).await?;
+    Ok(String::from_utf8(read_n_bytes(input, ticker_length).await?)?)
 }
 
 /*
-Returns the next IncomingMessage from the TcpStream
+Returns the next IncomingMessage from the client's connection
 */
-pub async fn read_next(input: &TcpStream) -> Result<IncomingMessage> {
+pub async fn read_next<R: AsyncRead + Unpin>(input: &mut R) -> Result<IncomingMessage> {
     match read_header(input).await? {
         IncomingMessageType::Order => Ok(IncomingMessage::Order(read_order(input).await?)),
         IncomingMessageType::Cancel => Ok(IncomingMessage::Cancel(read_cancel(input).await?)),
-        IncomingMessageType::Login => Ok(IncomingMessage::Login(read_login(input).await?)),
+        IncomingMessageType::Login => Ok(IncomingMessage::Login(read_login_body(input).await?)),
+        IncomingMessageType::StatusRequest => Ok(IncomingMessage::StatusRequest(read_status_request(input).await?)),
+        IncomingMessageType::PositionRequest => Ok(IncomingMessage::PositionRequest(read_position_request(input).await?)),
+        IncomingMessageType::MarketDataSubscribe => Ok(IncomingMessage::MarketDataSubscribe(read_market_data_subscribe(input).await?)),
+        IncomingMessageType::MarketDataUnsubscribe => Ok(IncomingMessage::MarketDataUnsubscribe(read_market_data_unsubscribe(input).await?)),
+    }
+}
+
+/*
+Reads a complete login message, header included, from the client's connection.
+Call on a freshly accepted connection, before any other message is read.
+*/
+pub async fn read_login<R: AsyncRead + Unpin>(input: &mut R) -> Result<Login> {
+    match read_header(input).await? {
+        IncomingMessageType::Login => read_login_body(input).await,
+        _ => Err(anyhow::anyhow!("Expected a login message")),
     }
 }
 
-pub async fn read_login(input: &TcpStream) -> Result<String> {
-    let name_length = read_message_length(input).await?;
-    Ok(String::from_utf8(read_n_bytes(input, name_length).await?)?)
+async fn read_login_body<R: AsyncRead + Unpin>(input: &mut R) -> Result<Login> {
+    let login_length = read_message_length(input).await?;
+    let body = read_n_bytes(input, login_length).await?;
+    let (flags, name) = match body.split_first() {
+        Some(parts) => parts,
+        None => return Err(anyhow::anyhow!("Empty login message")),
+    };
+    Ok(Login {
+        name: String::from_utf8(name.to_vec())?,
+        cancel_on_disconnect: flags & CANCEL_ON_DISCONNECT_FLAG != 0,
+        paper_trading: flags & PAPER_TRADING_FLAG != 0
+    })
 }
 
-async fn read_header(input: &TcpStream) -> Result<IncomingMessageType> {
+async fn read_header<R: AsyncRead + Unpin>(input: &mut R) -> Result<IncomingMessageType> {
     match read_header_byte(input).await? {
         LOGIN_HEADER => Ok(IncomingMessageType::Login),
         ORDER_HEADER => Ok(IncomingMessageType::Order),
         CANCEL_HEADER => Ok(IncomingMessageType::Cancel),
+        STATUS_REQUEST_HEADER => Ok(IncomingMessageType::StatusRequest),
+        POSITION_REQUEST_HEADER => Ok(IncomingMessageType::PositionRequest),
+        MARKET_DATA_SUBSCRIBE_HEADER => Ok(IncomingMessageType::MarketDataSubscribe),
+        MARKET_DATA_UNSUBSCRIBE_HEADER => Ok(IncomingMessageType::MarketDataUnsubscribe),
         _ => Err(anyhow::anyhow!("Invalid header byte")),
     }
 }
 
-async fn read_header_byte(input: &TcpStream) -> Result<u8> {
-    todo!()
+async fn read_header_byte<R: AsyncRead + Unpin>(input: &mut R) -> Result<u8> {
+    Ok(read_n_bytes(input, 1).await?[0])
 }
 
-async fn read_message_length(input: &TcpStream) -> Result<usize> {
+async fn read_message_length<R: AsyncRead + Unpin>(input: &mut R) -> Result<usize> {
     match read_n_bytes(input, MESSAGE_LENGTH_SIZE).await {
         Ok(bytes) => Ok(bytes[0] as usize),
         Err(e) => Err(e)
     }
 }
 
-async fn read_n_bytes(input: &TcpStream, n: usize) -> Result<Vec<u8>> {
-    todo!()
-}
\ No newline at end of file
+async fn read_n_bytes<R: AsyncRead + Unpin>(input: &mut R, n: usize) -> Result<Vec<u8>> {
+    let mut buffer = vec![0u8; n];
+    let mut filled = 0;
+    while filled < n {
+        match input.read(&mut buffer[filled..]).await? {
+            0 => return Err(anyhow::anyhow!("Connection closed by peer")),
+            read => filled += read
+        }
+    }
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(header: u8, body: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![header, body.len() as u8];
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    #[tokio::test]
+    async fn a_login_carries_its_name_and_session_flags() {
+        let bytes = frame(LOGIN_HEADER, &[[CANCEL_ON_DISCONNECT_FLAG | PAPER_TRADING_FLAG].as_slice(), b"alice"].concat());
+        let login = read_login(&mut bytes.as_slice()).await.unwrap();
+        assert_eq!(login.name, "alice");
+        assert!(login.cancel_on_disconnect);
+        assert!(login.paper_trading);
+
+        let bytes = frame(LOGIN_HEADER, &[[0].as_slice(), b"bob"].concat());
+        let login = read_login(&mut bytes.as_slice()).await.unwrap();
+        assert!(!login.cancel_on_disconnect && !login.paper_trading);
+    }
+
+    #[tokio::test]
+    async fn a_session_must_start_with_a_login() {
+        let bytes = frame(STATUS_REQUEST_HEADER, &[]);
+        assert!(read_login(&mut bytes.as_slice()).await.is_err());
+        let bytes = frame(LOGIN_HEADER, &[]);
+        assert!(read_login(&mut bytes.as_slice()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn messages_are_read_back_to_back_from_one_stream() {
+        let cancel = CancelOrderMessage { order_id: "order-1".into(), client_order_id: "client-1".into() };
+        let bytes = [
+            frame(CANCEL_HEADER, &cancel.to_bytes().unwrap()),
+            frame(STATUS_REQUEST_HEADER, &[]),
+            frame(STATUS_REQUEST_HEADER, b"client-1"),
+            frame(POSITION_REQUEST_HEADER, b"TICKER"),
+            frame(MARKET_DATA_SUBSCRIBE_HEADER, &[[TOP_OF_BOOK_FLAG | TICKER_UPDATES_FLAG].as_slice(), b"TICKER"].concat()),
+            frame(MARKET_DATA_UNSUBSCRIBE_HEADER, b"TICKER"),
+        ].concat();
+        let mut input = bytes.as_slice();
+
+        match read_next(&mut input).await.unwrap() {
+            IncomingMessage::Cancel(read) => assert_eq!((read.order_id.as_str(), read.client_order_id.as_str()), ("order-1", "client-1")),
+            _ => panic!("expected a cancel"),
+        }
+        assert!(matches!(read_next(&mut input).await.unwrap(), IncomingMessage::StatusRequest(StatusRequest::AllOpenOrders)));
+        assert!(matches!(read_next(&mut input).await.unwrap(), IncomingMessage::StatusRequest(StatusRequest::Order(id)) if id == "client-1"));
+        assert!(matches!(read_next(&mut input).await.unwrap(), IncomingMessage::PositionRequest(PositionRequest::Ticker(ticker)) if ticker == "TICKER"));
+        match read_next(&mut input).await.unwrap() {
+            IncomingMessage::MarketDataSubscribe(request) => {
+                assert_eq!(request.ticker, "TICKER");
+                assert!(request.top_of_book && !request.trades && request.ticker_updates);
+            },
+            _ => panic!("expected a market data subscription"),
+        }
+        assert!(matches!(read_next(&mut input).await.unwrap(), IncomingMessage::MarketDataUnsubscribe(ticker) if ticker == "TICKER"));
+        assert!(input.is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_message_cut_short_reports_the_closed_connection() {
+        let bytes = frame(POSITION_REQUEST_HEADER, b"TICKER");
+        let error = read_next(&mut &bytes[..bytes.len() - 1]).await.err().unwrap();
+        assert_eq!(error.to_string(), "Connection closed by peer");
+        let error = read_next(&mut &[][..]).await.err().unwrap();
+        assert_eq!(error.to_string(), "Connection closed by peer");
+    }
+
+    #[tokio::test]
+    async fn an_unknown_header_is_refused() {
+        let bytes = frame(u8::MAX, &[]);
+        assert!(read_next(&mut bytes.as_slice()).await.is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::Result;
+use serde::Serialize;
+use bincode::serialize;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use queue_client::queue_data::orders::{OrderConfirmMessage, OrderRejectMessage};
+use queue_client::queue_data::cancels::{CancelConfirmMessage, CancelRejectMessage};
+use queue_client::queue_data::fills::{FeedStatusMessage, Fill};
+use queue_client::queue_data::market_data::{TickerMessage, TradeMessage};
+
+use crate::constants::{ORDER_CONFIRM_HEADER, ORDER_REJECT_HEADER, CANCEL_CONFIRM_HEADER, CANCEL_REJECT_HEADER, FILL_HEADER, FEED_STATUS_HEADER, TRADE_HEADER, TICKER_HEADER};
+
+/// A trait all messages sent from the OMS to a trading client must implement
+pub trait ClientMessage: Serialize {
+    fn header() -> u8;
+}
+
+impl ClientMessage for OrderConfirmMessage {
+    fn header() -> u8 {
+        ORDER_CONFIRM_HEADER
+    }
+}
+
+impl ClientMessage for OrderRejectMessage {
+    fn header() -> u8 {
+        ORDER_REJECT_HEADER
+    }
+}
+
+impl ClientMessage for CancelConfirmMessage {
+    fn header() -> u8 {
+        CANCEL_CONFIRM_HEADER
+    }
+}
+
+impl ClientMessage for CancelRejectMessage {
+    fn header() -> u8 {
+        CANCEL_REJECT_HEADER
+    }
+}
+
+impl ClientMessage for Fill {
+    fn header() -> u8 {
+        FILL_HEADER
+    }
+}
+
+impl ClientMessage for FeedStatusMessage {
+    fn header() -> u8 {
+        FEED_STATUS_HEADER
+    }
+}
+
+impl ClientMessage for TradeMessage {
+    fn header() -> u8 {
+        TRADE_HEADER
+    }
+}
+
+impl ClientMessage for TickerMessage {
+    fn header() -> u8 {
+        TICKER_HEADER
+    }
+}
+
+/*
+Writes a message to the client's connection as a header byte, a length byte and the serialized message.
+*/
+pub async fn write_message<T: ClientMessage, W: AsyncWrite + Unpin>(output: &mut W, message: &T) -> Result<()> {
+    let body = serialize(message)?;
+    let length = match u8::try_from(body.len()) {
+        Ok(length) => length,
+        Err(_) => return Err(anyhow::anyhow!("Message of {} bytes is too long to frame", body.len())),
+    };
+    let mut frame = Vec::with_capacity(body.len() + 2);
+    frame.push(T::header());
+    frame.push(length);
+    frame.extend_from_slice(&body);
+    output.write_all(&frame).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bincode::deserialize;
+
+    #[tokio::test]
+    async fn a_message_is_framed_as_header_length_and_body() {
+        let confirm = CancelConfirmMessage { order_id: "order-1".into(), client_order_id: "client-1".into() };
+        let mut output = Vec::new();
+        write_message(&mut output, &confirm).await.unwrap();
+
+        assert_eq!(output[0], CANCEL_CONFIRM_HEADER);
+        assert_eq!(output[1] as usize, output.len() - 2);
+        let read: CancelConfirmMessage = deserialize(&output[2..]).unwrap();
+        assert_eq!((read.order_id.as_str(), read.client_order_id.as_str()), ("order-1", "client-1"));
+    }
+
+    #[tokio::test]
+    async fn a_message_too_long_to_frame_writes_nothing() {
+        let confirm = CancelConfirmMessage { order_id: "x".repeat(300), client_order_id: "client-1".into() };
+        let mut output = Vec::new();
+        assert!(write_message(&mut output, &confirm).await.is_err());
+        assert!(output.is_empty());
+    }
+}
@@ -5,42 +5,72 @@ use lapin::{
     Channel
 };
 use anyhow::Result;
+use journal::{RecordKind, SharedJournal};
 
 use crate::queue_data::data_core::QueueData;
+use crate::queue_names::QueueNames;
+use crate::queues::Endpoint;
 
 pub struct Consumer<T: QueueData> {
-    channel: Channel,
+    endpoint: Endpoint,
     queue_name: String,
+    journal: Option<SharedJournal>,
     phantom_data: PhantomData<T>,
 }
 
 impl<T: QueueData> Consumer<T> {
 
     pub async fn new(channel: Channel) -> Result<Self> {
-        let queue_name = T::class().to_string();
+        Self::named(channel, &QueueNames::default()).await
+    }
+
+    /// Consume from the queue the configured names give this class, rather than the queue named after it.
+    pub async fn named(channel: Channel, queue_names: &QueueNames) -> Result<Self> {
+        let queue_name = queue_names.name(&T::class());
         // Declare the queue
         channel
             .queue_declare(&queue_name, QueueDeclareOptions::default(), FieldTable::default())
             .await?;
 
-        Ok(Consumer { channel, queue_name, phantom_data: PhantomData })
+        Ok(Self::on(Endpoint::RabbitMq(channel), queue_name))
     }
 
+    pub(crate) fn on(endpoint: Endpoint, queue_name: String) -> Self {
+        Consumer { endpoint, queue_name, journal: None, phantom_data: PhantomData }
+    }
+
+    /// Record every message consumed from now on in the journal, before it is acknowledged,
+    /// so that a message the process dies holding is still on the queue or in the journal.
+    pub fn with_journal(mut self, journal: SharedJournal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// The next message, or `None` once the queue is empty. An empty queue is first waited
+    /// on briefly, so that polling it in a loop does not spin.
     pub async fn get_next(&self) -> Result<Option<T>> {
-        let delivery = self.channel.basic_get(&self.queue_name, BasicGetOptions::default()).await?;
-        if let Some(delivery) = delivery {
-            let message = T::from_bytes(&delivery.data)?;
-            self.channel.basic_ack(delivery.delivery_tag, BasicAckOptions::default()).await?;
+        self.next(true).await
+    }
+
+    async fn next(&self, wait: bool) -> Result<Option<T>> {
+        let received = self.endpoint.get(&self.queue_name, wait).await?;
+        if let Some(received) = received {
+            let message = T::from_bytes(&received.data)?;
+            if let Some(ref journal) = self.journal {
+                journal::append_bytes(journal, RecordKind::QueueConsume, &self.queue_name, received.data.clone())?;
+            }
+            self.endpoint.ack(&received).await?;
             Ok(Some(message))
         } else {
             Ok(None)
         }
     }
 
+    /// Everything on the queue. Only an empty queue is waited on, never the end of a non-empty one.
     pub async fn get_all(&self) -> Result<Vec<T>> {
         let mut messages = Vec::new();
         loop {
-            match self.get_next().await {
+            match self.next(messages.is_empty()).await {
                 Ok(Some(message)) => messages.push(message),
                 Ok(None) => break,
                 Err(err) => return Err(err),
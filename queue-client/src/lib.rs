@@ -1,4 +1,6 @@
 pub mod queue_data;
 pub mod consumer;
 pub mod producer;
+pub mod queue_names;
+pub mod queues;
 
@@ -5,44 +5,61 @@ use lapin::{
     Channel
 };
 use anyhow::Result;
+use journal::{RecordKind, SharedJournal};
 
 use crate::queue_data::data_core::QueueData;
+use crate::queue_names::QueueNames;
+use crate::queues::Endpoint;
 
 // Your struct representing the producer
 pub struct Producer<T: QueueData> {
-    channel: Channel,
+    endpoint: Endpoint,
     queue_name: String,
+    journal: Option<SharedJournal>,
     phantom_data: PhantomData<T>
 }
 
 impl<T: QueueData> Producer<T> {
-    // Create a new producer
+    // Create a new producer on the queue named after its class
     pub async fn new(channel: Channel) -> Result<Self> {
-        let queue_name = T::class().to_string();
+        Self::named(channel, &QueueNames::default()).await
+    }
+
+    // Create a new producer on the queue the configured names give its class
+    pub async fn named(channel: Channel, queue_names: &QueueNames) -> Result<Self> {
+        let queue_name = queue_names.name(&T::class());
         // Declare the exchange
         channel
             .queue_declare(&queue_name, QueueDeclareOptions::default(), FieldTable::default())
             .await?;
 
-        Ok(Producer {
-            channel,
-            queue_name: queue_name,
+        Ok(Self::on(Endpoint::RabbitMq(channel), queue_name))
+    }
+
+    // Create a producer on a queue already declared, such as an in-process one
+    pub(crate) fn on(endpoint: Endpoint, queue_name: String) -> Self {
+        Producer {
+            endpoint,
+            queue_name,
+            journal: None,
             phantom_data: PhantomData
-        })
+        }
+    }
+
+    // Record every message published from now on in the journal, before it is published,
+    // so that a message on the queue is always in the journal too
+    pub fn with_journal(mut self, journal: SharedJournal) -> Self {
+        self.journal = Some(journal);
+        self
     }
 
     // Publish a single element to the queue
     pub async fn publish(&self, message: T) -> Result<()> {
         let serialized_data = message.to_bytes()?;
-        print!("{:?}", serialized_data);
-        self.channel.basic_publish(
-            "",
-            &self.queue_name,
-            BasicPublishOptions::default(),
-            serialized_data.as_slice(),
-            Default::default(),
-        ).await?;
-        Ok(())
+        if let Some(ref journal) = self.journal {
+            journal::append_bytes(journal, RecordKind::QueuePublish, &self.queue_name, serialized_data.clone())?;
+        }
+        self.endpoint.publish(&self.queue_name, serialized_data.as_slice()).await
     }
 
     // Publish a vector of elements to the queue
@@ -23,4 +23,19 @@ impl QueueData for CancelConfirmMessage {
     fn class() -> QueueClass {
         QueueClass::CancelConfirm
     }
+}
+
+/// The exchange refused to cancel the order, which is still working unless it has already
+/// filled or expired
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CancelRejectMessage {
+    pub order_id: String,
+    pub client_order_id: String,
+    pub reason: String
+}
+
+impl QueueData for CancelRejectMessage {
+    fn class() -> QueueClass {
+        QueueClass::CancelReject
+    }
 }
\ No newline at end of file
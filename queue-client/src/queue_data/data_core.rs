@@ -3,22 +3,43 @@ use serde::{Deserialize, de::DeserializeOwned, Serialize};
 use anyhow::Result;
 use bincode::{serialize, deserialize};
 
-use crate::queue_data::{
-    cancels::{CancelConfirmMessage, CancelOrderMessage}, 
-    fills::FillMessage, 
-    orders::CreateOrderMessage, 
-    orders::OrderConfirmMessage
-};
-
 /// Types of queue that queue data can be written to
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum QueueClass {
     Order, 
     OrderConfirm,
+    OrderReject,
+    OrderUpdate,
     Cancel, 
     CancelConfirm,
-    Fill
+    CancelReject,
+    Fill,
+    FeedStatus,
+    MarketSubscription,
+    Book,
+    Trade,
+    Ticker,
+    MarketLifecycle
+}
+
+impl QueueClass {
+    pub const ALL: [QueueClass; 14] = [
+        QueueClass::Order,
+        QueueClass::OrderConfirm,
+        QueueClass::OrderReject,
+        QueueClass::OrderUpdate,
+        QueueClass::Cancel,
+        QueueClass::CancelConfirm,
+        QueueClass::CancelReject,
+        QueueClass::Fill,
+        QueueClass::FeedStatus,
+        QueueClass::MarketSubscription,
+        QueueClass::Book,
+        QueueClass::Trade,
+        QueueClass::Ticker,
+        QueueClass::MarketLifecycle
+    ];
 }
 
 impl fmt::Display for QueueClass {
@@ -27,8 +48,17 @@ impl fmt::Display for QueueClass {
             QueueClass::Order => write!(f, "order"),
             QueueClass::Cancel => write!(f, "cancel"),
             QueueClass::OrderConfirm => write!(f, "order_confirm"),
+            QueueClass::OrderReject => write!(f, "order_reject"),
+            QueueClass::OrderUpdate => write!(f, "order_update"),
             QueueClass::CancelConfirm => write!(f, "cancel_confirm"),
-            QueueClass::Fill => write!(f, "fill")
+            QueueClass::CancelReject => write!(f, "cancel_reject"),
+            QueueClass::Fill => write!(f, "fill"),
+            QueueClass::FeedStatus => write!(f, "feed_status"),
+            QueueClass::MarketSubscription => write!(f, "market_subscription"),
+            QueueClass::Book => write!(f, "book"),
+            QueueClass::Trade => write!(f, "trade"),
+            QueueClass::Ticker => write!(f, "ticker"),
+            QueueClass::MarketLifecycle => write!(f, "market_lifecycle")
         }
     }
 }
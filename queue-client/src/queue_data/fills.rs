@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 use crate::queue_data::data_core::{QueueClass, QueueData};
 
 use kalshi::{Action, Side};
+use chrono::DateTime;
 
 
 /// A kalshi market data message
@@ -10,14 +11,33 @@ use kalshi::{Action, Side};
 pub struct FillMessage {
     #[serde(rename="type")]
     msg_type: String,
-    sid: u32,
-    seq: u32,
+    pub sid: u32,
+    pub seq: u32,
     pub msg: Fill
 }
 
+impl FillMessage {
+
+    /// Wrap a fill delivered on the websocket subscription `sid`
+    pub fn new(sid: u32, seq: u32, msg: Fill) -> Self {
+        FillMessage {
+            msg_type: "fill".into(),
+            sid,
+            seq,
+            msg
+        }
+    }
+
+    /// Wrap a fill recovered from Kalshi's REST API, which has no
+    /// websocket subscription, so its sid and seq are zero.
+    pub fn backfilled(msg: Fill) -> Self {
+        FillMessage::new(0, 0, msg)
+    }
+}
+
 /// A fill message, i.e. a message containing a fill that has
 /// occurred on a ticker
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Fill {
     pub trade_id: String,
     pub order_id: String,
@@ -35,4 +55,39 @@ impl QueueData for FillMessage {
     fn class() -> QueueClass {
         QueueClass::Fill
     }
+}
+
+/// Whether the fill feed is connected to Kalshi and how long it has gone without hearing from it,
+/// published every few seconds. Fills are stale while the feed is down or silent for too long.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FeedStatusMessage {
+    pub connected: bool,
+    pub stale: bool,
+    pub silent_for_ms: u64
+}
+
+impl QueueData for FeedStatusMessage {
+    fn class() -> QueueClass {
+        QueueClass::FeedStatus
+    }
+}
+
+impl TryFrom<kalshi::Fill> for Fill {
+    type Error = anyhow::Error;
+
+    /// Convert a fill from Kalshi's REST API into the form the websocket feed delivers
+    fn try_from(fill: kalshi::Fill) -> Result<Self, Self::Error> {
+        Ok(Fill {
+            trade_id: fill.trade_id,
+            order_id: fill.order_id,
+            market_ticker: fill.ticker,
+            is_taker: fill.is_taker,
+            side: fill.side,
+            yes_price: fill.yes_price as i32,
+            no_price: fill.no_price as i32,
+            count: fill.count,
+            action: fill.action,
+            ts: DateTime::parse_from_rfc3339(&fill.created_time)?.timestamp()
+        })
+    }
 }
\ No newline at end of file
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+
+use crate::queue_data::data_core::{QueueData, QueueClass};
+
+use kalshi::Side;
+use chrono::DateTime;
+
+/// A request for the websocket feed to publish the book, trades and ticker updates of a market
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MarketSubscriptionMessage {
+    pub market_ticker: String
+}
+
+impl QueueData for MarketSubscriptionMessage {
+    fn class() -> QueueClass {
+        QueueClass::MarketSubscription
+    }
+}
+
+/// The quantity resting at one price, in cents, on one side of a book
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriceLevel {
+    pub price: i32,
+    pub quantity: i32
+}
+
+/// The top levels of a market's book. Kalshi books hold only bids: a YES bid
+/// at p is the same as a NO ask at 100 - p, and a NO bid at p is a YES ask at 100 - p.
+/// Both sides are ordered best (highest) bid first.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BookMessage {
+    pub market_ticker: String,
+    pub yes_bids: Vec<PriceLevel>,
+    pub no_bids: Vec<PriceLevel>,
+    pub ts: i64
+}
+
+impl QueueData for BookMessage {
+    fn class() -> QueueClass {
+        QueueClass::Book
+    }
+}
+
+impl BookMessage {
+
+    pub fn best_yes_bid(&self) -> Option<PriceLevel> {
+        self.yes_bids.first().copied()
+    }
+
+    pub fn best_no_bid(&self) -> Option<PriceLevel> {
+        self.no_bids.first().copied()
+    }
+
+    /// The cheapest price YES can be bought at, implied by the best NO bid
+    pub fn best_yes_ask(&self) -> Option<PriceLevel> {
+        self.best_no_bid().map(|level| PriceLevel { price: 100 - level.price, quantity: level.quantity })
+    }
+
+    /// The midpoint of the best YES bid and ask, if both sides have liquidity
+    pub fn yes_mid(&self) -> Option<f64> {
+        match (self.best_yes_bid(), self.best_yes_ask()) {
+            (Some(bid), Some(ask)) => Some((bid.price + ask.price) as f64 / 2.0),
+            _ => None
+        }
+    }
+}
+
+/// A trade between any two participants in a market, with prices in cents
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TradeMessage {
+    pub trade_id: String,
+    pub market_ticker: String,
+    pub yes_price: i32,
+    pub no_price: i32,
+    pub count: i32,
+    pub taker_side: Side,
+    pub ts: i64
+}
+
+impl QueueData for TradeMessage {
+    fn class() -> QueueClass {
+        QueueClass::Trade
+    }
+}
+
+/// A summary of a market's last price, top of book and activity, with prices in cents
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TickerMessage {
+    pub market_ticker: String,
+    pub price: i32,
+    pub yes_bid: i32,
+    pub yes_ask: i32,
+    pub volume: i64,
+    pub open_interest: i64,
+    pub ts: i64
+}
+
+impl QueueData for TickerMessage {
+    fn class() -> QueueClass {
+        QueueClass::Ticker
+    }
+}
+
+/// Where a market is in its life. Orders can only be placed while it is open;
+/// once it is determined its result is known, and once settled its positions are paid out.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarketStatus {
+    Open,
+    Closed,
+    Determined,
+    Settled
+}
+
+impl MarketStatus {
+
+    /// Translate a market status from Kalshi's REST API
+    pub fn from_kalshi(status: &str) -> Option<MarketStatus> {
+        match status {
+            "initialized" | "open" | "active" => Some(MarketStatus::Open),
+            "closed" | "inactive" => Some(MarketStatus::Closed),
+            "determined" => Some(MarketStatus::Determined),
+            "settled" | "finalized" => Some(MarketStatus::Settled),
+            _ => None
+        }
+    }
+}
+
+/// A change in a market's status. `result` is the side that won, once the market is determined
+/// and if it resolved to YES or NO; `close_ts` is when the market stops trading, in seconds.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MarketLifecycleMessage {
+    pub market_ticker: String,
+    pub status: MarketStatus,
+    pub close_ts: Option<i64>,
+    pub result: Option<Side>,
+    pub ts: i64
+}
+
+impl QueueData for MarketLifecycleMessage {
+    fn class() -> QueueClass {
+        QueueClass::MarketLifecycle
+    }
+}
+
+impl TryFrom<kalshi::Market> for MarketLifecycleMessage {
+    type Error = anyhow::Error;
+
+    /// Convert a market looked up on Kalshi's REST API into the form the lifecycle feed delivers
+    fn try_from(market: kalshi::Market) -> Result<Self, Self::Error> {
+        let status = match MarketStatus::from_kalshi(&market.status) {
+            Some(status) => status,
+            None => return Err(anyhow::anyhow!("Unknown status {:?} for market {}", market.status, market.ticker))
+        };
+        Ok(MarketLifecycleMessage {
+            market_ticker: market.ticker,
+            status,
+            close_ts: Some(DateTime::parse_from_rfc3339(&market.close_time)?.timestamp()),
+            result: market_result(&market.result),
+            ts: chrono::Utc::now().timestamp()
+        })
+    }
+}
+
+/// Translate a market result from Kalshi, which is empty until the market is determined
+pub fn market_result(result: &str) -> Option<Side> {
+    match result {
+        "yes" => Some(Side::Yes),
+        "no" => Some(Side::No),
+        _ => None
+    }
+}
@@ -1,4 +1,5 @@
 pub mod data_core;
 pub mod cancels;
 pub mod orders;
-pub mod fills;
\ No newline at end of file
+pub mod fills;
+pub mod market_data;
\ No newline at end of file
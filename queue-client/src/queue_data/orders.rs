@@ -48,4 +48,66 @@ impl OrderConfirmMessage {
             client_order_id,
         }
     }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OrderRejectMessage {
+    pub client_order_id: String,
+    pub reason: String
+}
+
+impl QueueData for OrderRejectMessage {
+    fn class() -> QueueClass {
+        QueueClass::OrderReject
+    }
+}
+
+impl OrderRejectMessage {
+    pub fn new(client_order_id: String, reason: String) -> Self {
+        OrderRejectMessage {
+            client_order_id,
+            reason,
+        }
+    }
+}
+
+/// A change to an order reported by Kalshi's order-update feed rather than a REST response,
+/// such as an exchange-side expiry or a cancel or amend made in Kalshi's web UI
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OrderUpdateMessage {
+    pub order_id: String,
+    pub client_order_id: String,
+    pub ticker: String,
+    pub state: OrderState,
+    pub yes_price: i32,
+    pub filled_count: i32,
+    pub remaining_count: i32,
+    pub ts: i64
+}
+
+impl QueueData for OrderUpdateMessage {
+    fn class() -> QueueClass {
+        QueueClass::OrderUpdate
+    }
+}
+
+/// The lifecycle states of an order managed by the OMS
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderState {
+    PendingNew,
+    Open,
+    PartiallyFilled,
+    Filled,
+    PendingCancel,
+    Cancelled,
+    Rejected,
+    Expired
+}
+
+impl OrderState {
+    /// Whether an order in this state may still trade on the exchange
+    pub fn is_open(&self) -> bool {
+        matches!(self, OrderState::PendingNew | OrderState::Open | OrderState::PartiallyFilled | OrderState::PendingCancel)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,39 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::queue_data::data_core::QueueClass;
+
+/// What each queue is called on the broker. A queue is named after its class, such as
+/// `order_confirm`, unless renamed, and every name starts with the prefix, so that several
+/// OMSs can share one broker without reading each other's messages.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QueueNames {
+    pub prefix: String,
+    /// Names by class, replacing the class's own name.
+    pub names: BTreeMap<String, String>
+}
+
+impl QueueNames {
+    pub fn name(&self, class: &QueueClass) -> String {
+        let class = class.to_string();
+        format!("{}{}", self.prefix, self.names.get(&class).unwrap_or(&class))
+    }
+
+    /// Refuse renames of classes that do not exist, and names that would put two classes on one queue.
+    pub fn validate(&self) -> Result<()> {
+        let classes: Vec<String> = QueueClass::ALL.iter().map(QueueClass::to_string).collect();
+        if let Some(unknown) = self.names.keys().find(|class| !classes.contains(class)) {
+            return Err(anyhow::anyhow!("{:?} is not a queue; queues are {}", unknown, classes.join(", ")));
+        }
+        let mut seen = HashSet::new();
+        for class in QueueClass::ALL.iter() {
+            let name = self.name(class);
+            if name.is_empty() || !seen.insert(name.clone()) {
+                return Err(anyhow::anyhow!("Queue {} would be named {:?}, which is empty or already taken", class, name));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::Result;
+use lapin::{options::*, Channel, Connection, ConnectionProperties};
+use tokio::sync::Notify;
+
+use crate::consumer::Consumer;
+use crate::producer::Producer;
+use crate::queue_data::data_core::QueueData;
+use crate::queue_names::QueueNames;
+
+/// How long an idle in-process consumer waits for a publish before reporting its queue empty.
+/// Any publish wakes it early, so this only bounds how often an idle polling loop spins.
+const IN_PROCESS_IDLE_WAIT: Duration = Duration::from_millis(10);
+
+/// How long a consumer waits before asking the broker again after finding its queue empty.
+/// The broker answers an empty poll straight away, so without it an idle polling loop spins.
+const RABBIT_MQ_IDLE_WAIT: Duration = Duration::from_millis(10);
+
+/// Where the OMS's queues live: on a RabbitMQ broker shared by separate processes, or in
+/// memory, shared by components running as tasks of one process. Producers and consumers
+/// behave the same either way, journaling included.
+#[derive(Clone)]
+pub struct Queues {
+    transport: Transport,
+    names: QueueNames
+}
+
+#[derive(Clone)]
+enum Transport {
+    RabbitMq(Arc<Connection>),
+    InProcess(InProcessQueues)
+}
+
+impl Queues {
+    pub async fn rabbit_mq(url: &str, names: QueueNames) -> Result<Queues> {
+        let connection = Connection::connect(url, ConnectionProperties::default()).await?;
+        Ok(Queues { transport: Transport::RabbitMq(Arc::new(connection)), names })
+    }
+
+    pub fn in_process(names: QueueNames) -> Queues {
+        Queues { transport: Transport::InProcess(InProcessQueues::default()), names }
+    }
+
+    pub fn names(&self) -> &QueueNames {
+        &self.names
+    }
+
+    pub async fn producer<T: QueueData>(&self) -> Result<Producer<T>> {
+        match &self.transport {
+            Transport::RabbitMq(connection) => Producer::named(connection.create_channel().await?, &self.names).await,
+            Transport::InProcess(queues) => {
+                let queue_name = self.names.name(&T::class());
+                Ok(Producer::on(queues.endpoint(&queue_name), queue_name))
+            }
+        }
+    }
+
+    pub async fn consumer<T: QueueData>(&self) -> Result<Consumer<T>> {
+        match &self.transport {
+            Transport::RabbitMq(connection) => Consumer::named(connection.create_channel().await?, &self.names).await,
+            Transport::InProcess(queues) => {
+                let queue_name = self.names.name(&T::class());
+                Ok(Consumer::on(queues.endpoint(&queue_name), queue_name))
+            }
+        }
+    }
+}
+
+/// One in-process queue of serialized messages.
+type InProcessQueue = Arc<Mutex<VecDeque<Vec<u8>>>>;
+
+/// What a producer publishes to or a consumer reads from.
+pub(crate) enum Endpoint {
+    RabbitMq(Channel),
+    InProcess(InProcessQueues, InProcessQueue)
+}
+
+/// A message taken off a queue, and the tag to acknowledge it with on the broker.
+pub(crate) struct Received {
+    pub data: Vec<u8>,
+    pub delivery_tag: Option<u64>
+}
+
+impl Endpoint {
+
+    pub(crate) async fn publish(&self, queue_name: &str, data: &[u8]) -> Result<()> {
+        match self {
+            Endpoint::RabbitMq(channel) => {
+                channel.basic_publish("", queue_name, BasicPublishOptions::default(), data, Default::default()).await?;
+            },
+            Endpoint::InProcess(queues, queue) => {
+                queue.lock().unwrap().push_back(data.to_vec());
+                queues.inner.published.notify_waiters();
+            }
+        }
+        Ok(())
+    }
+
+    /// Take the next message, if there is one. An idle queue is waited on a little when `wait`
+    /// is set, since nothing else would stop its consumer's polling loop spinning.
+    pub(crate) async fn get(&self, queue_name: &str, wait: bool) -> Result<Option<Received>> {
+        match self {
+            Endpoint::RabbitMq(channel) => {
+                let mut delivery = channel.basic_get(queue_name, BasicGetOptions::default()).await?;
+                if delivery.is_none() && wait {
+                    tokio::time::sleep(RABBIT_MQ_IDLE_WAIT).await;
+                    delivery = channel.basic_get(queue_name, BasicGetOptions::default()).await?;
+                }
+                Ok(delivery.map(|delivery| Received { delivery_tag: Some(delivery.delivery_tag), data: delivery.delivery.data }))
+            },
+            Endpoint::InProcess(queues, queue) => {
+                // listen before looking, so that a publish between the two is not missed
+                let published = queues.inner.published.notified();
+                tokio::pin!(published);
+                published.as_mut().enable();
+                let mut data = queue.lock().unwrap().pop_front();
+                if data.is_none() && wait {
+                    let _ = tokio::time::timeout(IN_PROCESS_IDLE_WAIT, published).await;
+                    data = queue.lock().unwrap().pop_front();
+                }
+                Ok(data.map(|data| Received { data, delivery_tag: None }))
+            }
+        }
+    }
+
+    pub(crate) async fn ack(&self, received: &Received) -> Result<()> {
+        if let (Endpoint::RabbitMq(channel), Some(delivery_tag)) = (self, received.delivery_tag) {
+            channel.basic_ack(delivery_tag, BasicAckOptions::default()).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Queues in memory, by name, holding serialized messages just as the broker would.
+#[derive(Clone, Default)]
+pub(crate) struct InProcessQueues {
+    inner: Arc<InProcessInner>
+}
+
+#[derive(Default)]
+struct InProcessInner {
+    queues: Mutex<HashMap<String, InProcessQueue>>,
+    published: Notify
+}
+
+impl InProcessQueues {
+    fn endpoint(&self, queue_name: &str) -> Endpoint {
+        let queue = self.inner.queues.lock().unwrap().entry(queue_name.to_string()).or_default().clone();
+        Endpoint::InProcess(self.clone(), queue)
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::order::BookSide;
+
+/// The resting orders of one market as a YES book: bids and asks by YES price, each level
+/// holding order ids in time priority. Alongside them is the live liquidity of the real
+/// market, as the quantity at each YES price, which has no orders on this exchange behind it.
+#[derive(Default)]
+pub(crate) struct Book {
+    bids: BTreeMap<i64, VecDeque<String>>,
+    asks: BTreeMap<i64, VecDeque<String>>,
+    live_bids: BTreeMap<i64, i32>,
+    live_asks: BTreeMap<i64, i32>
+}
+
+impl Book {
+    fn levels(&mut self, side: BookSide) -> &mut BTreeMap<i64, VecDeque<String>> {
+        match side {
+            BookSide::Bid => &mut self.bids,
+            BookSide::Ask => &mut self.asks
+        }
+    }
+
+    /// Queue an order at the back of its price level.
+    pub(crate) fn insert(&mut self, side: BookSide, yes_price: i64, order_id: String) {
+        self.levels(side).entry(yes_price).or_default().push_back(order_id);
+    }
+
+    pub(crate) fn remove(&mut self, side: BookSide, yes_price: i64, order_id: &str) {
+        let levels = self.levels(side);
+        if let Some(level) = levels.get_mut(&yes_price) {
+            level.retain(|resting| resting != order_id);
+            if level.is_empty() {
+                levels.remove(&yes_price);
+            }
+        }
+    }
+
+    /// The first order in time at the best price on the side opposite `taker_side`, if it
+    /// trades with a taker limited to `limit_price`.
+    pub(crate) fn best_match(&self, taker_side: BookSide, limit_price: i64) -> Option<(i64, &str)> {
+        let best = match taker_side {
+            BookSide::Bid => self.asks.iter().next(),
+            BookSide::Ask => self.bids.iter().next_back()
+        };
+        let (price, level) = best?;
+        if !taker_side.crosses(limit_price, *price) {
+            return None;
+        }
+        level.front().map(|order_id| (*price, order_id.as_str()))
+    }
+
+    /// The first order in time at the best price on one side, if there is one.
+    pub(crate) fn best_resting(&self, side: BookSide) -> Option<(i64, &str)> {
+        let best = match side {
+            BookSide::Bid => self.bids.iter().next_back(),
+            BookSide::Ask => self.asks.iter().next()
+        };
+        let (price, level) = best?;
+        level.front().map(|order_id| (*price, order_id.as_str()))
+    }
+
+    /// Replace the live liquidity with the quantities now resting at each YES price.
+    pub(crate) fn set_live(&mut self, bids: Vec<(i64, i32)>, asks: Vec<(i64, i32)>) {
+        let positive = |(_, quantity): &(i64, i32)| *quantity > 0;
+        self.live_bids = bids.into_iter().filter(positive).collect();
+        self.live_asks = asks.into_iter().filter(positive).collect();
+    }
+
+    /// The best live price and quantity on the side opposite `taker_side`, if it trades
+    /// with a taker limited to `limit_price`.
+    pub(crate) fn best_live(&self, taker_side: BookSide, limit_price: i64) -> Option<(i64, i32)> {
+        let best = match taker_side {
+            BookSide::Bid => self.live_asks.iter().next(),
+            BookSide::Ask => self.live_bids.iter().next_back()
+        };
+        let (price, quantity) = best?;
+        if !taker_side.crosses(limit_price, *price) {
+            return None;
+        }
+        Some((*price, *quantity))
+    }
+
+    /// Use up live liquidity opposite `taker_side`, which stays gone until the next update.
+    pub(crate) fn take_live(&mut self, taker_side: BookSide, yes_price: i64, count: i32) {
+        let levels = match taker_side {
+            BookSide::Bid => &mut self.live_asks,
+            BookSide::Ask => &mut self.live_bids
+        };
+        if let Some(quantity) = levels.get_mut(&yes_price) {
+            *quantity -= count;
+            if *quantity <= 0 {
+                levels.remove(&yes_price);
+            }
+        }
+    }
+}
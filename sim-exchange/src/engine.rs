@@ -0,0 +1,499 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::book::Book;
+use crate::error::SimError;
+use crate::order::{BookSide, NewOrder, OrderStatus, OrderType, SimFill, SimOrder};
+
+/// What placing or amending an order did: the order as it stands afterwards, and every fill
+/// it traded, for itself as taker and for the resting orders it matched as makers.
+#[derive(Debug, Clone)]
+pub struct Execution {
+    pub order: SimOrder,
+    pub fills: Vec<SimFill>
+}
+
+/// A simulated exchange for Kalshi-style binary markets. Each market has one YES book that
+/// YES and NO orders share, matched with price-time priority: an incoming order trades with
+/// the best-priced resting orders on the other side, oldest first at each price, at the
+/// resting order's price, and whatever is left of a limit order rests. A market can also be
+/// given the live liquidity of the real one with `set_live_book`, which orders trade against
+/// too, ahead of resting orders at the same price since it was there first.
+pub struct MatchingEngine {
+    id_prefix: String,
+    books: HashMap<String, Book>,
+    orders: HashMap<String, SimOrder>,
+    client_order_ids: HashSet<String>,
+    next_order_id: u64,
+    next_trade_id: u64,
+    next_priority: u64
+}
+
+impl MatchingEngine {
+
+    /// A new engine with no orders. Order and trade ids start with `id_prefix`, so that
+    /// they cannot be mistaken for ids from another exchange.
+    pub fn new(id_prefix: &str) -> MatchingEngine {
+        MatchingEngine {
+            id_prefix: id_prefix.to_string(),
+            books: HashMap::new(),
+            orders: HashMap::new(),
+            client_order_ids: HashSet::new(),
+            next_order_id: 0,
+            next_trade_id: 0,
+            next_priority: 0
+        }
+    }
+
+    pub fn order(&self, order_id: &str) -> Option<&SimOrder> {
+        self.orders.get(order_id)
+    }
+
+    /// Every order placed, in no particular order.
+    pub fn orders(&self) -> impl Iterator<Item = &SimOrder> {
+        self.orders.values()
+    }
+
+    /// Place an order, matching it against the book. A limit order rests with whatever
+    /// does not fill; a market order cancels its remainder.
+    pub fn submit(&mut self, order: NewOrder, now: i64) -> Result<Execution, SimError> {
+        if order.count <= 0 {
+            return Err(SimError::InvalidOrder("count must be positive".to_string()));
+        }
+        if self.client_order_ids.contains(&order.client_order_id) {
+            return Err(SimError::DuplicateClientOrderId(order.client_order_id));
+        }
+        if order.expiration_ts.is_some_and(|expiration_ts| expiration_ts <= now) {
+            return Err(SimError::InvalidOrder("expiration_ts is in the past".to_string()));
+        }
+        let book_side = BookSide::of(order.side, order.action);
+        let yes_price = match order.order_type {
+            OrderType::Limit => limit_yes_price(order.yes_price, order.no_price)?,
+            // a market order takes any price
+            OrderType::Market => match book_side {
+                BookSide::Bid => 99,
+                BookSide::Ask => 1
+            }
+        };
+
+        self.next_order_id += 1;
+        let order_id = format!("{}-{:012}", self.id_prefix, self.next_order_id);
+        self.client_order_ids.insert(order.client_order_id.clone());
+        let sim_order = SimOrder {
+            order_id: order_id.clone(),
+            client_order_id: order.client_order_id,
+            ticker: order.ticker,
+            side: order.side,
+            action: order.action,
+            order_type: order.order_type,
+            status: OrderStatus::Resting,
+            yes_price,
+            place_count: order.count,
+            remaining_count: order.count,
+            taker_fill_count: 0,
+            maker_fill_count: 0,
+            created_ts: now,
+            expiration_ts: order.expiration_ts,
+            priority: self.take_priority()
+        };
+        self.orders.insert(order_id.clone(), sim_order);
+
+        let fills = self.execute(&order_id, now);
+        Ok(Execution { order: self.orders[&order_id].clone(), fills })
+    }
+
+    /// Cancel a resting order, returning it and how many contracts the cancel took off the book.
+    pub fn cancel(&mut self, order_id: &str, now: i64) -> Result<(SimOrder, i32), SimError> {
+        self.expire(now);
+        let order = self.resting(order_id)?;
+        let (side, yes_price, ticker) = (order.book_side(), order.yes_price, order.ticker.clone());
+        if let Some(book) = self.books.get_mut(&ticker) {
+            book.remove(side, yes_price, order_id);
+        }
+
+        let order = self.orders.get_mut(order_id).expect("resting order is known");
+        let reduced_by = order.remaining_count;
+        order.remaining_count = 0;
+        order.status = OrderStatus::Canceled;
+        Ok((order.clone(), reduced_by))
+    }
+
+    /// Change the size or price of a resting order, returning it as it was before and what
+    /// the amend did. `count` is the new total size, including contracts already filled.
+    /// Only shrinking an order at the same price keeps its place in the queue; any other
+    /// amend sends it to the back, and a new price may trade straight away.
+    pub fn amend(&mut self, order_id: &str, count: i32, yes_price: Option<i64>, no_price: Option<i64>, now: i64) -> Result<(SimOrder, Execution), SimError> {
+        self.expire(now);
+        let old_order = self.resting(order_id)?.clone();
+        let new_yes_price = limit_yes_price(yes_price, no_price)?;
+        if count <= old_order.fill_count() {
+            return Err(SimError::InvalidOrder("count must exceed the contracts already filled".to_string()));
+        }
+
+        let remaining_count = count - old_order.fill_count();
+        let keeps_priority = new_yes_price == old_order.yes_price && remaining_count <= old_order.remaining_count;
+        let priority = if keeps_priority { old_order.priority } else { self.take_priority() };
+        let book = self.books.entry(old_order.ticker.clone()).or_default();
+        if !keeps_priority {
+            book.remove(old_order.book_side(), old_order.yes_price, order_id);
+        }
+
+        let order = self.orders.get_mut(order_id).expect("resting order is known");
+        order.place_count = count;
+        order.remaining_count = remaining_count;
+        order.yes_price = new_yes_price;
+        order.priority = priority;
+
+        let fills = if keeps_priority { Vec::new() } else { self.execute(order_id, now) };
+        Ok((old_order, Execution { order: self.orders[order_id].clone(), fills }))
+    }
+
+    /// Replace a market's live liquidity with the quantity resting at each YES price on the
+    /// real market's bids and asks. Resting orders the new liquidity crosses trade against it
+    /// straight away, as makers at their own prices; their fills are returned. Nothing on this
+    /// exchange is the other side of those trades, so only the resting order's fill is reported.
+    pub fn set_live_book(&mut self, ticker: &str, bids: Vec<(i64, i32)>, asks: Vec<(i64, i32)>, now: i64) -> Vec<SimFill> {
+        self.expire(now);
+        let book = self.books.entry(ticker.to_string()).or_default();
+        book.set_live(bids, asks);
+
+        let mut fills = Vec::new();
+        for side in [BookSide::Bid, BookSide::Ask] {
+            while let Some((price, order_id)) = book.best_resting(side).map(|(price, order_id)| (price, order_id.to_string())) {
+                let (live_price, quantity) = match book.best_live(side, price) {
+                    Some(live) => live,
+                    None => break
+                };
+                let order = self.orders.get_mut(&order_id).expect("resting order is known");
+                let count = order.remaining_count.min(quantity);
+                book.take_live(side, live_price, count);
+                self.next_trade_id += 1;
+                let trade_id = format!("{}-trade-{:012}", self.id_prefix, self.next_trade_id);
+
+                order.remaining_count -= count;
+                order.maker_fill_count += count;
+                if order.remaining_count == 0 {
+                    order.status = OrderStatus::Executed;
+                    book.remove(side, price, &order_id);
+                }
+                fills.push(fill(order, &trade_id, price, count, false, now));
+            }
+        }
+        fills
+    }
+
+    /// Cancel every resting order whose expiration time has passed, returning them.
+    pub fn expire(&mut self, now: i64) -> Vec<SimOrder> {
+        let expired: Vec<String> = self.orders.values()
+            .filter(|order| order.status == OrderStatus::Resting && order.expiration_ts.is_some_and(|expiration_ts| expiration_ts <= now))
+            .map(|order| order.order_id.clone())
+            .collect();
+        expired.iter().filter_map(|order_id| self.cancel_resting(order_id)).collect()
+    }
+
+    /// Cancel every resting order in a market, as when it closes, returning them.
+    pub fn close_market(&mut self, ticker: &str) -> Vec<SimOrder> {
+        let resting: Vec<String> = self.orders.values()
+            .filter(|order| order.status == OrderStatus::Resting && order.ticker == ticker)
+            .map(|order| order.order_id.clone())
+            .collect();
+        resting.iter().filter_map(|order_id| self.cancel_resting(order_id)).collect()
+    }
+
+    fn cancel_resting(&mut self, order_id: &str) -> Option<SimOrder> {
+        let order = self.orders.get_mut(order_id)?;
+        if let Some(book) = self.books.get_mut(&order.ticker) {
+            book.remove(order.book_side(), order.yes_price, order_id);
+        }
+        order.remaining_count = 0;
+        order.status = OrderStatus::Canceled;
+        Some(order.clone())
+    }
+
+    fn resting(&self, order_id: &str) -> Result<&SimOrder, SimError> {
+        let order = self.orders.get(order_id).ok_or_else(|| SimError::UnknownOrder(order_id.to_string()))?;
+        match order.status {
+            OrderStatus::Resting => Ok(order),
+            _ => Err(SimError::NotResting(order_id.to_string()))
+        }
+    }
+
+    fn take_priority(&mut self) -> u64 {
+        self.next_priority += 1;
+        self.next_priority
+    }
+
+    /// Match an order not on the book against the live liquidity and resting orders it
+    /// crosses, then rest what is left of it, or cancel the rest of a market order.
+    fn execute(&mut self, taker_id: &str, now: i64) -> Vec<SimFill> {
+        let (ticker, taker_side, limit_price) = {
+            let taker = &self.orders[taker_id];
+            (taker.ticker.clone(), taker.book_side(), taker.yes_price)
+        };
+        let book = self.books.entry(ticker.clone()).or_default();
+        let mut fills = Vec::new();
+
+        while self.orders[taker_id].remaining_count > 0 {
+            let resting = book.best_match(taker_side, limit_price).map(|(price, maker_id)| (price, maker_id.to_string()));
+            // live liquidity was there first, so it trades ahead of resting orders at the same price
+            let live = book.best_live(taker_side, limit_price)
+                .filter(|(live_price, _)| resting.as_ref().is_none_or(|(price, _)| taker_side.crosses(*price, *live_price)));
+            let (price, maker_id) = match (live, resting) {
+                (None, None) => break,
+                (Some((price, quantity)), _) => {
+                    let taker = self.orders.get_mut(taker_id).expect("taker is known");
+                    let count = taker.remaining_count.min(quantity);
+                    book.take_live(taker_side, price, count);
+                    self.next_trade_id += 1;
+                    let trade_id = format!("{}-trade-{:012}", self.id_prefix, self.next_trade_id);
+                    taker.remaining_count -= count;
+                    taker.taker_fill_count += count;
+                    fills.push(fill(taker, &trade_id, price, count, true, now));
+                    continue;
+                },
+                (None, Some(resting)) => resting
+            };
+            let count = self.orders[taker_id].remaining_count.min(self.orders[&maker_id].remaining_count);
+            self.next_trade_id += 1;
+            let trade_id = format!("{}-trade-{:012}", self.id_prefix, self.next_trade_id);
+
+            let maker = self.orders.get_mut(&maker_id).expect("resting order is known");
+            maker.remaining_count -= count;
+            maker.maker_fill_count += count;
+            if maker.remaining_count == 0 {
+                maker.status = OrderStatus::Executed;
+                book.remove(maker.book_side(), price, &maker_id);
+            }
+            fills.push(fill(maker, &trade_id, price, count, false, now));
+
+            let taker = self.orders.get_mut(taker_id).expect("taker is known");
+            taker.remaining_count -= count;
+            taker.taker_fill_count += count;
+            fills.push(fill(taker, &trade_id, price, count, true, now));
+        }
+
+        let taker = self.orders.get_mut(taker_id).expect("taker is known");
+        if taker.remaining_count == 0 {
+            taker.status = OrderStatus::Executed;
+        } else if taker.order_type == OrderType::Market {
+            taker.remaining_count = 0;
+            taker.status = if taker.fill_count() > 0 { OrderStatus::Executed } else { OrderStatus::Canceled };
+        } else {
+            book.insert(taker_side, taker.yes_price, taker_id.to_string());
+        }
+        fills
+    }
+}
+
+fn fill(order: &SimOrder, trade_id: &str, yes_price: i64, count: i32, is_taker: bool, ts: i64) -> SimFill {
+    SimFill {
+        trade_id: trade_id.to_string(),
+        order_id: order.order_id.clone(),
+        client_order_id: order.client_order_id.clone(),
+        ticker: order.ticker.clone(),
+        side: order.side,
+        action: order.action,
+        yes_price,
+        count,
+        is_taker,
+        ts
+    }
+}
+
+/// The YES price of a limit order, which must give exactly one of its YES and NO prices,
+/// between 1 and 99 cents.
+fn limit_yes_price(yes_price: Option<i64>, no_price: Option<i64>) -> Result<i64, SimError> {
+    let yes_price = match (yes_price, no_price) {
+        (Some(yes_price), None) => yes_price,
+        (None, Some(no_price)) => 100 - no_price,
+        _ => return Err(SimError::InvalidOrder("a limit order needs exactly one of yes_price and no_price".to_string()))
+    };
+    if !(1..=99).contains(&yes_price) {
+        return Err(SimError::InvalidOrder("price must be between 1 and 99".to_string()));
+    }
+    Ok(yes_price)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::{Action, Side};
+
+    fn limit(client_order_id: &str, side: Side, action: Action, count: i32, yes_price: Option<i64>, no_price: Option<i64>) -> NewOrder {
+        NewOrder {
+            client_order_id: client_order_id.to_string(),
+            ticker: "TICKER".to_string(),
+            side,
+            action,
+            order_type: OrderType::Limit,
+            count,
+            yes_price,
+            no_price,
+            expiration_ts: None
+        }
+    }
+
+    fn market(client_order_id: &str, side: Side, action: Action, count: i32) -> NewOrder {
+        NewOrder { order_type: OrderType::Market, ..limit(client_order_id, side, action, count, None, None) }
+    }
+
+    fn buy_yes(client_order_id: &str, count: i32, yes_price: i64) -> NewOrder {
+        limit(client_order_id, Side::Yes, Action::Buy, count, Some(yes_price), None)
+    }
+
+    fn sell_yes(client_order_id: &str, count: i32, yes_price: i64) -> NewOrder {
+        limit(client_order_id, Side::Yes, Action::Sell, count, Some(yes_price), None)
+    }
+
+    #[test]
+    fn a_no_bid_trades_with_a_yes_bid_at_one_hundred_minus_its_price() {
+        let mut engine = MatchingEngine::new("sim");
+        let maker = engine.submit(buy_yes("yes", 10, 40), 0).unwrap().order;
+
+        // a NO bid at 59 is a YES ask at 41, which does not reach the YES bid at 40
+        let rests = engine.submit(limit("no-59", Side::No, Action::Buy, 5, None, Some(59)), 0).unwrap();
+        assert!(rests.fills.is_empty());
+        assert_eq!(rests.order.status, OrderStatus::Resting);
+        assert_eq!(rests.order.no_price(), 59);
+
+        let execution = engine.submit(limit("no-60", Side::No, Action::Buy, 4, None, Some(60)), 0).unwrap();
+        assert_eq!(execution.order.status, OrderStatus::Executed);
+        assert_eq!(execution.fills.len(), 2);
+        let (maker_fill, taker_fill) = (&execution.fills[0], &execution.fills[1]);
+        assert_eq!(maker_fill.trade_id, taker_fill.trade_id);
+        assert_eq!((maker_fill.order_id.as_str(), maker_fill.side, maker_fill.is_taker), (maker.order_id.as_str(), Side::Yes, false));
+        assert_eq!((taker_fill.side, taker_fill.is_taker), (Side::No, true));
+        assert_eq!((taker_fill.yes_price, taker_fill.no_price(), taker_fill.count), (40, 60, 4));
+        assert_eq!(engine.order(&maker.order_id).unwrap().remaining_count, 6);
+        assert_eq!(engine.order(&maker.order_id).unwrap().maker_fill_count, 4);
+    }
+
+    #[test]
+    fn selling_no_bids_for_yes() {
+        let mut engine = MatchingEngine::new("sim");
+        engine.submit(sell_yes("ask", 3, 45), 0).unwrap();
+        // selling NO at 55 is buying YES at 45
+        let execution = engine.submit(limit("sell-no", Side::No, Action::Sell, 3, None, Some(55)), 0).unwrap();
+        assert_eq!(execution.order.taker_fill_count, 3);
+        assert_eq!(execution.fills[1].yes_price, 45);
+    }
+
+    #[test]
+    fn orders_match_best_price_first_then_oldest_first_at_the_resting_price() {
+        let mut engine = MatchingEngine::new("sim");
+        let first = engine.submit(sell_yes("first", 2, 45), 0).unwrap().order.order_id;
+        let second = engine.submit(sell_yes("second", 2, 45), 0).unwrap().order.order_id;
+        let best = engine.submit(sell_yes("best", 2, 44), 0).unwrap().order.order_id;
+
+        let execution = engine.submit(buy_yes("taker", 5, 50), 0).unwrap();
+        let makers: Vec<(&str, i64, i32)> = execution.fills.iter()
+            .filter(|fill| !fill.is_taker)
+            .map(|fill| (fill.order_id.as_str(), fill.yes_price, fill.count))
+            .collect();
+        assert_eq!(makers, vec![(best.as_str(), 44, 2), (first.as_str(), 45, 2), (second.as_str(), 45, 1)]);
+        assert_eq!(execution.fills.iter().filter(|fill| fill.is_taker).count(), 3);
+    }
+
+    #[test]
+    fn a_limit_order_rests_its_remainder_and_a_market_order_cancels_it() {
+        let mut engine = MatchingEngine::new("sim");
+        engine.submit(sell_yes("ask", 3, 45), 0).unwrap();
+        let execution = engine.submit(market("market", Side::Yes, Action::Buy, 5), 0).unwrap();
+        assert_eq!((execution.order.status, execution.order.fill_count(), execution.order.remaining_count), (OrderStatus::Executed, 3, 0));
+
+        let nothing = engine.submit(market("nothing", Side::Yes, Action::Buy, 5), 0).unwrap();
+        assert_eq!((nothing.order.status, nothing.order.fill_count()), (OrderStatus::Canceled, 0));
+
+        engine.submit(sell_yes("ask-2", 3, 45), 0).unwrap();
+        let partly = engine.submit(buy_yes("limit", 5, 45), 0).unwrap();
+        assert_eq!((partly.order.status, partly.order.remaining_count), (OrderStatus::Resting, 2));
+    }
+
+    #[test]
+    fn shrinking_an_order_keeps_its_priority_and_any_other_amend_loses_it() {
+        let mut engine = MatchingEngine::new("sim");
+        let first = engine.submit(buy_yes("first", 5, 40), 0).unwrap().order.order_id;
+        let second = engine.submit(buy_yes("second", 5, 40), 0).unwrap().order.order_id;
+
+        let (before, execution) = engine.amend(&first, 3, Some(40), None, 0).unwrap();
+        assert_eq!(before.remaining_count, 5);
+        assert_eq!(execution.order.remaining_count, 3);
+        let fills = engine.submit(sell_yes("taker", 1, 40), 0).unwrap().fills;
+        assert_eq!(fills[0].order_id, first);
+
+        // growing it sends it behind the second order
+        engine.amend(&first, 6, Some(40), None, 0).unwrap();
+        let fills = engine.submit(sell_yes("taker-2", 1, 40), 0).unwrap().fills;
+        assert_eq!(fills[0].order_id, second);
+
+        // a new price that crosses trades straight away
+        engine.submit(sell_yes("ask", 2, 42), 0).unwrap();
+        let (_, execution) = engine.amend(&second, 6, Some(42), None, 0).unwrap();
+        assert_eq!(execution.fills.iter().filter(|fill| fill.is_taker).map(|fill| fill.count).sum::<i32>(), 2);
+
+        assert!(engine.amend(&first, 1, Some(40), None, 0).is_err(), "an amend must leave more than the filled count");
+    }
+
+    #[test]
+    fn cancelled_expired_and_closed_orders_leave_the_book() {
+        let mut engine = MatchingEngine::new("sim");
+        let cancelled = engine.submit(buy_yes("cancelled", 5, 40), 0).unwrap().order.order_id;
+        let (order, reduced_by) = engine.cancel(&cancelled, 0).unwrap();
+        assert_eq!((order.status, reduced_by), (OrderStatus::Canceled, 5));
+        assert_eq!(engine.cancel(&cancelled, 0).unwrap_err(), SimError::NotResting(cancelled.clone()));
+        assert_eq!(engine.cancel("missing", 0).unwrap_err(), SimError::UnknownOrder("missing".to_string()));
+
+        let expiring = NewOrder { expiration_ts: Some(10), ..buy_yes("expiring", 5, 40) };
+        let expiring = engine.submit(expiring, 0).unwrap().order.order_id;
+        assert!(engine.expire(9).is_empty());
+        assert_eq!(engine.expire(10).iter().map(|order| order.order_id.clone()).collect::<Vec<_>>(), vec![expiring]);
+
+        engine.submit(buy_yes("open", 5, 40), 20).unwrap();
+        assert_eq!(engine.close_market("TICKER").len(), 1);
+        assert!(engine.submit(sell_yes("taker", 1, 1), 20).unwrap().fills.is_empty(), "nothing is left to trade with");
+    }
+
+    #[test]
+    fn orders_take_live_liquidity_ahead_of_resting_orders_at_the_same_price() {
+        let mut engine = MatchingEngine::new("sim");
+        let resting = engine.submit(sell_yes("resting", 5, 45), 0).unwrap().order.order_id;
+        assert!(engine.set_live_book("TICKER", vec![(40, 10)], vec![(44, 2), (45, 3)], 0).is_empty());
+
+        let execution = engine.submit(buy_yes("taker", 7, 45), 0).unwrap();
+        let trades: Vec<(&str, i64, i32, bool)> = execution.fills.iter()
+            .map(|fill| (fill.client_order_id.as_str(), fill.yes_price, fill.count, fill.is_taker))
+            .collect();
+        // live liquidity has no maker here, so it only produces the taker's fill
+        assert_eq!(trades, vec![("taker", 44, 2, true), ("taker", 45, 3, true), ("resting", 45, 2, false), ("taker", 45, 2, true)]);
+        assert_eq!(engine.order(&resting).unwrap().remaining_count, 3);
+
+        // what was taken stays gone until the next update
+        let nothing_live = engine.submit(buy_yes("again", 1, 44), 0).unwrap();
+        assert!(nothing_live.fills.is_empty());
+    }
+
+    #[test]
+    fn a_live_book_that_crosses_resting_orders_fills_them_at_their_own_prices() {
+        let mut engine = MatchingEngine::new("sim");
+        let bid = engine.submit(buy_yes("bid", 5, 40), 0).unwrap().order.order_id;
+        let ask = engine.submit(sell_yes("ask", 5, 60), 0).unwrap().order.order_id;
+
+        let fills = engine.set_live_book("TICKER", vec![(62, 2)], vec![(38, 10)], 0);
+        let trades: Vec<(&str, i64, i32, bool)> = fills.iter()
+            .map(|fill| (fill.client_order_id.as_str(), fill.yes_price, fill.count, fill.is_taker))
+            .collect();
+        assert_eq!(trades, vec![("bid", 40, 5, false), ("ask", 60, 2, false)]);
+        assert_eq!(engine.order(&bid).unwrap().status, OrderStatus::Executed);
+        assert_eq!(engine.order(&ask).unwrap().remaining_count, 3);
+    }
+
+    #[test]
+    fn invalid_orders_are_refused() {
+        let mut engine = MatchingEngine::new("sim");
+        assert!(matches!(engine.submit(buy_yes("zero", 0, 40), 0), Err(SimError::InvalidOrder(_))));
+        assert!(matches!(engine.submit(buy_yes("free", 1, 0), 0), Err(SimError::InvalidOrder(_))));
+        assert!(matches!(engine.submit(limit("both", Side::Yes, Action::Buy, 1, Some(40), Some(60)), 0), Err(SimError::InvalidOrder(_))));
+        assert!(matches!(engine.submit(NewOrder { expiration_ts: Some(5), ..buy_yes("late", 1, 40) }, 10), Err(SimError::InvalidOrder(_))));
+        engine.submit(buy_yes("once", 1, 40), 0).unwrap();
+        assert_eq!(engine.submit(buy_yes("once", 1, 40), 0).unwrap_err(), SimError::DuplicateClientOrderId("once".to_string()));
+    }
+}
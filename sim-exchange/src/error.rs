@@ -0,0 +1,27 @@
+use std::fmt;
+
+/// Why the simulated exchange refused a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimError {
+    /// The request is malformed, such as a price outside 1 to 99 cents.
+    InvalidOrder(String),
+    /// The client order id has already been used.
+    DuplicateClientOrderId(String),
+    /// No order has this order id.
+    UnknownOrder(String),
+    /// The order has already been cancelled or executed.
+    NotResting(String)
+}
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimError::InvalidOrder(reason) => write!(f, "invalid order: {}", reason),
+            SimError::DuplicateClientOrderId(client_order_id) => write!(f, "client order id {} has already been used", client_order_id),
+            SimError::UnknownOrder(order_id) => write!(f, "no order {}", order_id),
+            SimError::NotResting(order_id) => write!(f, "order {} is not resting", order_id)
+        }
+    }
+}
+
+impl std::error::Error for SimError {}
@@ -0,0 +1,9 @@
+pub mod engine;
+pub mod error;
+pub mod order;
+
+mod book;
+
+pub use engine::{Execution, MatchingEngine};
+pub use error::SimError;
+pub use order::{Action, NewOrder, OrderStatus, OrderType, Side, SimFill, SimOrder};
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Yes,
+    No
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Buy,
+    Sell
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderType {
+    Limit,
+    Market
+}
+
+/// An order's status, named as Kalshi names them.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderStatus {
+    Resting,
+    Canceled,
+    Executed
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Resting => "resting",
+            OrderStatus::Canceled => "canceled",
+            OrderStatus::Executed => "executed"
+        }
+    }
+}
+
+/// Which side of a market's YES book an order sits on. Buying YES and selling NO both bid
+/// for YES; buying NO and selling YES both offer it, since a NO bid at p is a YES ask at 100 - p.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BookSide {
+    Bid,
+    Ask
+}
+
+impl BookSide {
+    pub(crate) fn of(side: Side, action: Action) -> BookSide {
+        match (side, action) {
+            (Side::Yes, Action::Buy) | (Side::No, Action::Sell) => BookSide::Bid,
+            (Side::No, Action::Buy) | (Side::Yes, Action::Sell) => BookSide::Ask
+        }
+    }
+
+    /// Whether a resting order on the other side at `resting_price` trades with an order
+    /// on this side limited to `limit_price`, both as YES prices.
+    pub(crate) fn crosses(&self, limit_price: i64, resting_price: i64) -> bool {
+        match self {
+            BookSide::Bid => resting_price <= limit_price,
+            BookSide::Ask => resting_price >= limit_price
+        }
+    }
+}
+
+/// An order to place, priced on whichever of its YES and NO prices it gives.
+#[derive(Debug, Clone)]
+pub struct NewOrder {
+    pub client_order_id: String,
+    pub ticker: String,
+    pub side: Side,
+    pub action: Action,
+    pub order_type: OrderType,
+    pub count: i32,
+    pub yes_price: Option<i64>,
+    pub no_price: Option<i64>,
+    pub expiration_ts: Option<i64>
+}
+
+/// An order on the simulated exchange. Prices are in cents and held as the YES price;
+/// the NO price is always 100 minus it.
+#[derive(Debug, Clone)]
+pub struct SimOrder {
+    pub order_id: String,
+    pub client_order_id: String,
+    pub ticker: String,
+    pub side: Side,
+    pub action: Action,
+    pub order_type: OrderType,
+    pub status: OrderStatus,
+    pub yes_price: i64,
+    pub place_count: i32,
+    pub remaining_count: i32,
+    pub taker_fill_count: i32,
+    pub maker_fill_count: i32,
+    pub created_ts: i64,
+    pub expiration_ts: Option<i64>,
+    /// Time priority within a price level: lower goes first.
+    pub(crate) priority: u64
+}
+
+impl SimOrder {
+    pub fn no_price(&self) -> i64 {
+        100 - self.yes_price
+    }
+
+    pub fn fill_count(&self) -> i32 {
+        self.taker_fill_count + self.maker_fill_count
+    }
+
+    pub(crate) fn book_side(&self) -> BookSide {
+        BookSide::of(self.side, self.action)
+    }
+}
+
+/// One side of a trade, in the terms of the order it filled. Both sides of a trade share
+/// its trade id and YES price.
+#[derive(Debug, Clone)]
+pub struct SimFill {
+    pub trade_id: String,
+    pub order_id: String,
+    pub client_order_id: String,
+    pub ticker: String,
+    pub side: Side,
+    pub action: Action,
+    pub yes_price: i64,
+    pub count: i32,
+    pub is_taker: bool,
+    pub ts: i64
+}
+
+impl SimFill {
+    pub fn no_price(&self) -> i64 {
+        100 - self.yes_price
+    }
+}
@@ -3,16 +3,17 @@ use anyhow::Result;
 use queue_client::producer::Producer;
 use queue_client::queue_data::orders::CreateOrderMessage;
 use kalshi::{Action, Side, OrderType};
+use oms_config::OmsConfig;
 use uuid::Uuid;
 
 #[tokio::main]
 async fn main() -> Result<()> {
 
-    let addr = "amqp://localhost:5672";
-    let connection = Connection::connect(addr, ConnectionProperties::default()).await?;
+    let config = OmsConfig::load()?;
+    let connection = Connection::connect(&config.broker.url, ConnectionProperties::default()).await?;
     let channel = connection.create_channel().await?;
 
-    let producer = Producer::<CreateOrderMessage>::new(channel).await?;
+    let producer = Producer::<CreateOrderMessage>::named(channel, &config.broker.queues).await?;
 
     let mock_uuid = Uuid::new_v4();
     let clorid = format!("miles69-{}", mock_uuid);